@@ -0,0 +1,137 @@
+// Shorter forms of tags, used when a name grows past a length limit.
+// eg presentation -> pres, confidential -> conf
+//
+// Abbreviations are expanded again whenever names are read, so tags are
+// listed and queried in full. A name still too long once abbreviated has its
+// longest tags cut short, which cannot be undone.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::NameTag;
+
+#[derive(Debug, Default, Clone)]
+pub struct Abbreviations {
+    max_length: Option<usize>,
+    short: BTreeMap<String, String>,
+}
+
+impl Abbreviations {
+    /// Abbreviate tags in names longer than max_length bytes.
+    pub fn new(max_length: Option<usize>, short: BTreeMap<String, String>) -> Self {
+        Self { max_length, short }
+    }
+
+    /// The full tag an abbreviation stands for.
+    pub fn expand(&self, tag: &str) -> Option<&str> {
+        self.short
+            .iter()
+            .find(|(_, short)| short.as_str() == tag)
+            .map(|(long, _)| long.as_str())
+    }
+
+    /// Replace abbreviated tags with their full form.
+    pub fn expand_tags(&self, nametag: &mut NameTag) {
+        let short = nametag
+            .get_tags()
+            .filter_map(|tag| {
+                let tag = tag.to_str()?;
+                Some((tag.to_owned(), self.expand(tag)?.to_owned()))
+            })
+            .collect::<Vec<_>>();
+        for (short, long) in short {
+            nametag.remove_tag(short);
+            nametag.add_tag(long);
+        }
+    }
+
+    /// Shorten tags until the name fits, abbreviating those saving the most
+    /// first, then truncating the longest.
+    pub fn shorten(&self, nametag: &mut NameTag) {
+        let max_length = match self.max_length {
+            Some(max_length) => max_length,
+            None => return,
+        };
+        let mut long = nametag
+            .get_tags()
+            .filter_map(|tag| tag.to_str())
+            .filter_map(|tag| Some((tag.to_owned(), self.short.get(tag)?.clone())))
+            .collect::<Vec<_>>();
+        long.sort_by_key(|(long, short)| short.len() as isize - long.len() as isize);
+        for (long, short) in long {
+            if length(nametag) <= max_length {
+                return;
+            }
+            nametag.remove_tag(long);
+            nametag.add_tag(short);
+        }
+        while length(nametag) > max_length {
+            let longest = nametag
+                .get_tags()
+                .filter_map(|tag| tag.to_str())
+                .max_by_key(|tag| tag.chars().count())
+                .map(str::to_owned);
+            let longest = match longest {
+                Some(longest) if longest.chars().count() > 1 => longest,
+                _ => return,
+            };
+            let excess = length(nametag) - max_length;
+            let keep = longest.len().saturating_sub(excess).max(1);
+            let cut = (1..=keep)
+                .rev()
+                .find(|index| longest.is_char_boundary(*index))
+                .unwrap_or_else(|| longest.chars().next().map_or(1, char::len_utf8));
+            nametag.remove_tag(longest.as_str());
+            nametag.add_tag(&longest[..cut]);
+        }
+    }
+}
+
+// Length in bytes of the file name, leaving out its directory.
+fn length(nametag: &NameTag) -> usize {
+    PathBuf::from(nametag.clone())
+        .file_name()
+        .map_or(0, |name| name.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abbreviations(max_length: usize) -> Abbreviations {
+        let short = [("presentation", "pres"), ("confidential", "conf")]
+            .iter()
+            .map(|(long, short)| (long.to_string(), short.to_string()))
+            .collect();
+        Abbreviations::new(Some(max_length), short)
+    }
+
+    fn shorten(abbreviations: &Abbreviations, name: &str) -> String {
+        let mut nametag = NameTag::new(name);
+        abbreviations.shorten(&mut nametag);
+        PathBuf::from(nametag).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_shorten() {
+        let name = "a[confidential presentation].txt";
+        assert_eq!(name, shorten(&abbreviations(100), name));
+        assert_eq!(
+            "a[conf presentation].txt",
+            shorten(&abbreviations(24), name)
+        );
+        assert_eq!("a[conf pres].txt", shorten(&abbreviations(20), name));
+        assert_eq!("a[conf pr].txt", shorten(&abbreviations(14), name));
+    }
+    #[test]
+    fn test_expand_tags() {
+        let mut nametag = NameTag::new("a[conf pres x].txt");
+        abbreviations(10).expand_tags(&mut nametag);
+        assert_eq!(
+            vec!["confidential", "presentation", "x"],
+            nametag
+                .get_tags()
+                .map(|tag| tag.to_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+}
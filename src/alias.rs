@@ -0,0 +1,110 @@
+// Emoji tags, each paired with an ascii name.
+// eg aliases.star = "⭐"
+//
+// Either form finds files carrying the other in queries. Names can be
+// written in ascii only, for filesystems and tools that choke on emoji.
+use std::collections::BTreeMap;
+
+use crate::query::Query;
+use crate::NameTag;
+
+#[derive(Debug, Default, Clone)]
+pub struct Aliases {
+    emoji: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    /// Pair ascii names with emoji.
+    pub fn new(emoji: BTreeMap<String, String>) -> Self {
+        Self { emoji }
+    }
+
+    /// The other form of a tag. eg star -> ⭐, ⭐ -> star
+    pub fn other(&self, tag: &str) -> Option<&str> {
+        match self.emoji.get(tag) {
+            Some(emoji) => Some(emoji),
+            None => self
+                .emoji
+                .iter()
+                .find(|(_, emoji)| emoji.as_str() == tag)
+                .map(|(name, _)| name.as_str()),
+        }
+    }
+
+    /// The ascii name of an emoji tag, else the tag as it is.
+    pub fn ascii<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.emoji
+            .iter()
+            .find(|(_, emoji)| emoji.as_str() == tag)
+            .map_or(tag, |(name, _)| name.as_str())
+    }
+
+    /// A query matching either form of each tag in it.
+    pub fn widen(&self, query: &Query) -> Query {
+        match query {
+            Query::Tag(tag) => match self.other(tag) {
+                Some(other) => Query::Or(
+                    Box::new(Query::Tag(tag.clone())),
+                    Box::new(Query::Tag(other.to_owned())),
+                ),
+                None => Query::Tag(tag.clone()),
+            },
+            Query::Exact(tag) => match self.other(tag) {
+                Some(other) => Query::Exact(tag.clone()).or(Query::Exact(other.to_owned())),
+                None => Query::Exact(tag.clone()),
+            },
+            Query::Value(..) | Query::Compare(..) | Query::Range(..) | Query::Name(_) => {
+                query.clone()
+            }
+            Query::Not(query) => Query::Not(Box::new(self.widen(query))),
+            Query::And(left, right) => {
+                Query::And(Box::new(self.widen(left)), Box::new(self.widen(right)))
+            }
+            Query::Or(left, right) => {
+                Query::Or(Box::new(self.widen(left)), Box::new(self.widen(right)))
+            }
+        }
+    }
+
+    /// Replace emoji tags with their ascii name.
+    pub fn to_ascii(&self, nametag: &mut NameTag) {
+        let emoji = self
+            .emoji
+            .iter()
+            .filter(|(_, emoji)| nametag.has_tag(emoji.as_str()))
+            .map(|(name, emoji)| (name.clone(), emoji.clone()))
+            .collect::<Vec<_>>();
+        for (name, emoji) in emoji {
+            nametag.remove_tag(emoji);
+            nametag.add_tag(name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn aliases() -> Aliases {
+        let mut emoji = BTreeMap::new();
+        emoji.insert("star".to_owned(), "⭐".to_owned());
+        Aliases::new(emoji)
+    }
+
+    #[test]
+    fn test_widen() {
+        let query = aliases().widen(&"star AND NOT done".parse().unwrap());
+        assert!(query.matches(&["⭐"]));
+        assert!(query.matches(&["star"]));
+        assert!(!query.matches(&["⭐", "done"]));
+        let query = aliases().widen(&"⭐".parse().unwrap());
+        assert!(query.matches(&["star"]));
+    }
+    #[test]
+    fn test_to_ascii() {
+        let mut nametag = NameTag::new("a[⭐ x].txt");
+        aliases().to_ascii(&mut nametag);
+        assert_eq!("a[star x].txt", OsString::from(nametag));
+    }
+}
@@ -0,0 +1,133 @@
+// Log of committed changes with the message given for them, one checksummed
+// json line per commit. eg ~/.local/state/nametag/audit.log
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::batch::Rename;
+use crate::config::{civil_from_days, state_dir};
+use crate::jsonl;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Seconds since the unix epoch.
+    pub time: u64,
+    pub message: String,
+    pub renames: Vec<Rename>,
+    /// Files deleted outright, which cannot be undone.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<PathBuf>,
+}
+
+impl Entry {
+    /// An entry made now.
+    pub fn new(message: &str, renames: &[Rename]) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0),
+            message: message.to_owned(),
+            renames: renames.to_vec(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// An entry made now for files deleted.
+    pub fn removing(message: &str, removed: &[PathBuf]) -> Self {
+        Self {
+            removed: removed.to_vec(),
+            ..Self::new(message, &[])
+        }
+    }
+
+    /// When the entry was made, in UTC. eg 2020-01-31 09:05
+    pub fn date(&self) -> String {
+        let (year, month, day) = civil_from_days((self.time / 86400) as i64);
+        let minutes = self.time % 86400 / 60;
+        format!(
+            "{}-{:02}-{:02} {:02}:{:02}",
+            year,
+            month,
+            day,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+/// Where the audit log lives. eg ~/.local/state/nametag/audit.log
+pub fn default_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("audit.log"))
+}
+
+pub fn append(path: &Path, entry: &Entry) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&jsonl::encode(entry)?)
+}
+
+/// Entries in the order they were made, up to any that are damaged. A
+/// missing log has none.
+pub fn entries(path: &Path) -> io::Result<Vec<Entry>> {
+    jsonl::read(path)
+}
+
+/// Cut the log back to its intact entries, returning the number of bytes
+/// removed.
+pub fn repair(path: &Path) -> io::Result<usize> {
+    jsonl::repair::<Entry>(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_append_entries() {
+        let path = env::temp_dir().join("nametag-audit.log");
+        let _ = fs::remove_file(&path);
+        assert!(entries(&path).unwrap().is_empty());
+        let renames = vec![Rename {
+            from: PathBuf::from("a.txt"),
+            to: PathBuf::from("a[x].txt"),
+        }];
+        append(&path, &Entry::new("first", &renames)).unwrap();
+        append(&path, &Entry::new("second", &[])).unwrap();
+        let logged = entries(&path).unwrap();
+        assert_eq!(
+            vec!["first", "second"],
+            logged
+                .iter()
+                .map(|entry| entry.message.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(renames, logged[0].renames);
+    }
+    #[test]
+    fn test_removing() {
+        let path = env::temp_dir().join("nametag-audit-removing.log");
+        let _ = fs::remove_file(&path);
+        let removed = vec![PathBuf::from("a[trashed].txt")];
+        append(&path, &Entry::removing("sweep", &removed)).unwrap();
+        append(&path, &Entry::new("add", &[])).unwrap();
+        let logged = entries(&path).unwrap();
+        assert_eq!(removed, logged[0].removed);
+        assert!(logged[1].removed.is_empty());
+        assert!(!fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .contains("removed"));
+    }
+}
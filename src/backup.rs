@@ -0,0 +1,118 @@
+// Manifests of the tags files had before a batch taking tags from them,
+// kept apart from the journal and audit log as a last way back should
+// those be lost. eg ~/.local/state/nametag/backups/2020-01-31T090500.tsv
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::batch::Rename;
+use crate::config::{civil_from_days, state_dir};
+use crate::manifest::Manifest;
+
+/// Where backups are kept without backup_dir in config.
+/// eg ~/.local/state/nametag/backups
+pub fn default_dir() -> Option<PathBuf> {
+    Some(state_dir()?.join("backups"))
+}
+
+/// The files a batch of renames takes tags from, with the tags they have
+/// before it, by their paths joined to root. tags_of gives the tags of a name.
+pub fn removing<F: Fn(&Path) -> Vec<String>>(
+    root: &Path,
+    renames: &[Rename],
+    tags_of: F,
+) -> Manifest {
+    let mut manifest = Manifest::default();
+    for rename in renames {
+        let before = tags_of(&rename.from);
+        let after = tags_of(&rename.to);
+        if before.iter().any(|tag| !after.contains(tag)) {
+            manifest.insert(root.join(&rename.from), before);
+        }
+    }
+    manifest
+}
+
+/// Name of a backup made at a time, in seconds since the unix epoch, UTC.
+/// eg 2020-01-31T090500.tsv
+pub fn file_name(time: u64) -> String {
+    let (year, month, day) = civil_from_days((time / 86400) as i64);
+    let seconds = time % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}{:02}{:02}.tsv",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Write a manifest to a new file in dir, named for the time, returning
+/// its path. Backups made within the same second are numbered.
+pub fn write(dir: &Path, manifest: &Manifest, time: u64) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = file_name(time);
+    for count in 1.. {
+        let path = match count {
+            1 => dir.join(&name),
+            _ => dir.join(name.replace(".tsv", &format!("-{}.tsv", count))),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(manifest.render().as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn tags_of(path: &Path) -> Vec<String> {
+        match path.to_str().unwrap() {
+            "a[x y].txt" => vec!["x".to_owned(), "y".to_owned()],
+            "a[x].txt" | "b[x].txt" => vec!["x".to_owned()],
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_removing() {
+        let renames = vec![
+            Rename {
+                from: PathBuf::from("a[x y].txt"),
+                to: PathBuf::from("a[x].txt"),
+            },
+            Rename {
+                from: PathBuf::from("b.txt"),
+                to: PathBuf::from("b[x].txt"),
+            },
+        ];
+        let manifest = removing(Path::new("/root"), &renames, tags_of);
+        assert_eq!("/root/a[x y].txt\tx y\n", manifest.render());
+    }
+    #[test]
+    fn test_file_name() {
+        assert_eq!("2020-01-31T090503.tsv", file_name(1580461503));
+    }
+    #[test]
+    fn test_write() {
+        let dir = env::temp_dir().join("nametag-backup");
+        let _ = fs::remove_dir_all(&dir);
+        let mut manifest = Manifest::default();
+        manifest.insert("a[x].txt", vec!["x".to_owned()]);
+        let first = write(&dir, &manifest, 0).unwrap();
+        let second = write(&dir, &manifest, 0).unwrap();
+        assert_eq!(dir.join("1970-01-01T000000.tsv"), first);
+        assert_eq!(dir.join("1970-01-01T000000-2.tsv"), second);
+        assert_eq!("a[x].txt\tx\n", fs::read_to_string(second).unwrap());
+    }
+}
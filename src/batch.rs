@@ -0,0 +1,564 @@
+// A set of renames, planned up front and then applied together.
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::Cancel;
+use crate::conflict::{self, ConflictNamer};
+use crate::journal::Journal;
+
+// Names tried for a conflicting rename before giving up on it
+const MAX_ATTEMPTS: usize = 1000;
+
+// Renames between checkpoints of the journal, when not set
+const CHUNK_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// Why a planned rename could no longer be made when its turn came, as
+/// another process moved the files involved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precondition {
+    /// The file is no longer there.
+    Vanished,
+    /// Another file now has the new name.
+    Taken,
+}
+
+impl fmt::Display for Precondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Precondition::Vanished => write!(f, "Moved or removed since the batch was planned"),
+            Precondition::Taken => write!(f, "New name taken since the batch was planned"),
+        }
+    }
+}
+
+/// A rename left out as it could no longer be made.
+#[derive(Debug, PartialEq)]
+pub struct Skipped {
+    pub rename: Rename,
+    pub reason: Precondition,
+}
+
+#[derive(Debug, Default)]
+pub struct Batch {
+    renames: Vec<Rename>,
+    throttle: Option<Duration>,
+    // Files planned, including those left as they are
+    scanned: usize,
+    // Renames left out, as they conflicted or could not be planned
+    skipped: usize,
+    // New names that may replace the file already there
+    overwrites: BTreeSet<PathBuf>,
+    chunk_size: Option<usize>,
+    cancel: Cancel,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop between renames once cancelled. A journaled batch leaves its
+    /// journal behind, to be resumed or rolled back.
+    pub fn set_cancel(&mut self, cancel: Cancel) {
+        self.cancel = cancel;
+    }
+
+    /// Limit the renames performed per second, to share a busy disk.
+    pub fn set_throttle(&mut self, ops_per_sec: f64) {
+        self.throttle = if ops_per_sec > 0.0 {
+            Some(Duration::from_secs_f64(1.0 / ops_per_sec))
+        } else {
+            None
+        };
+    }
+
+    /// Renames applied between checkpoints of the journal, each forced to
+    /// disk. Smaller chunks lose less progress to a crash, at some cost in speed.
+    pub fn set_chunk_size(&mut self, renames: usize) {
+        self.chunk_size = Some(renames.max(1));
+    }
+
+    /// Plan a rename. Renames to the same path are dropped.
+    pub fn push<F: Into<PathBuf>, T: Into<PathBuf>>(&mut self, from: F, to: T) {
+        let (from, to) = (from.into(), to.into());
+        self.scanned += 1;
+        if from != to {
+            self.renames.push(Rename { from, to });
+        }
+    }
+
+    pub fn renames(&self) -> &[Rename] {
+        &self.renames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.renames.len()
+    }
+
+    /// Files the batch was planned from, whether or not they are renamed.
+    pub fn scanned(&self) -> usize {
+        self.scanned
+    }
+
+    /// Renames left out of the batch.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Note a file looked at but left out of the batch.
+    pub fn skip(&mut self) {
+        self.scanned += 1;
+        self.skipped += 1;
+    }
+
+    /// Take out renames that would overwrite another file, either one already
+    /// on disk or the destination of an earlier rename in the batch.
+    pub fn remove_conflicts(&mut self) -> Vec<Rename> {
+        let mut targets = BTreeSet::new();
+        let mut conflicts = Vec::new();
+        self.renames.retain(|rename| {
+            let clobbers = rename.to.exists() && !same_file(&rename.from, &rename.to);
+            if clobbers || !targets.insert(rename.to.clone()) {
+                conflicts.push(rename.clone());
+                false
+            } else {
+                true
+            }
+        });
+        self.skipped += conflicts.len();
+        conflicts
+    }
+
+    /// Put back a rename taken out as a conflict, once settled. With
+    /// overwrite, it replaces any file already at its new name.
+    pub fn readmit(&mut self, rename: Rename, overwrite: bool) {
+        self.skipped = self.skipped.saturating_sub(1);
+        if overwrite {
+            self.overwrites.insert(rename.to.clone());
+        }
+        self.renames.push(rename);
+    }
+
+    /// Give renames that would overwrite another file a new name instead,
+    /// taking out any left without a free name.
+    pub fn resolve_conflicts(&mut self, namer: &dyn ConflictNamer) -> Vec<Rename> {
+        let mut targets = BTreeSet::new();
+        let mut conflicts = Vec::new();
+        let mut resolved = Vec::new();
+        let taken = |to: &Path, from: &Path, targets: &BTreeSet<PathBuf>| {
+            (to.exists() && !same_file(from, to)) || targets.contains(to)
+        };
+        for mut rename in self.renames.drain(..) {
+            if taken(&rename.to, &rename.from, &targets) {
+                let free = (1..=MAX_ATTEMPTS)
+                    .filter_map(|attempt| {
+                        conflict::candidate(namer, &rename.to, &rename.from, attempt)
+                    })
+                    .find(|to| !taken(to, &rename.from, &targets));
+                match free {
+                    Some(to) => rename.to = to,
+                    None => {
+                        conflicts.push(rename);
+                        continue;
+                    }
+                }
+            }
+            targets.insert(rename.to.clone());
+            resolved.push(rename);
+        }
+        self.renames = resolved;
+        self.skipped += conflicts.len();
+        conflicts
+    }
+
+    /// Directories holding files to be renamed.
+    pub fn directories(&self) -> BTreeSet<&Path> {
+        self.renames
+            .iter()
+            .map(|rename| rename.from.parent().unwrap_or_else(|| Path::new("")))
+            .collect()
+    }
+
+    /// Check renames are permitted in every directory of the batch, so a
+    /// share that refuses them fails before anything changes rather than
+    /// halfway through.
+    pub fn probe(&self) -> io::Result<()> {
+        self.directories().into_iter().try_for_each(probe_rename)
+    }
+
+    /// Perform the renames in order, stopping at the first failure. Renames
+    /// of files moved by another process since planning are skipped, and
+    /// taken out of the batch.
+    pub fn apply(&mut self) -> io::Result<Vec<Skipped>> {
+        let mut pace = Pace::new(self.throttle);
+        let mut skipped = Vec::new();
+        for (index, rename) in self.renames.iter().enumerate() {
+            self.cancel.check()?;
+            pace.wait();
+            let overwrite = self.overwrites.contains(&rename.to);
+            if let Some(reason) = rename_checked(rename, overwrite)? {
+                skipped.push((index, reason));
+            }
+        }
+        Ok(self.take_skipped(skipped))
+    }
+
+    /// As apply, recording progress in a journal so an interrupted batch can
+    /// be resumed or rolled back. See Journal::recover
+    pub fn apply_journaled(&mut self, journal: &Path) -> io::Result<Vec<Skipped>> {
+        let mut journal = Journal::begin(journal, self)?;
+        let mut pace = Pace::new(self.throttle);
+        let mut skipped = Vec::new();
+        let chunk_size = self.chunk_size.unwrap_or(CHUNK_SIZE);
+        for (index, rename) in self.renames.iter().enumerate() {
+            self.cancel.check()?;
+            pace.wait();
+            match rename_checked(rename, self.overwrites.contains(&rename.to))? {
+                Some(reason) => skipped.push((index, reason)),
+                None => journal.confirm(index)?,
+            }
+            if (index + 1) % chunk_size == 0 {
+                journal.checkpoint(index + 1)?;
+            }
+        }
+        journal.finish()?;
+        Ok(self.take_skipped(skipped))
+    }
+
+    // Take out renames that were skipped, given by their index.
+    fn take_skipped(&mut self, skipped: Vec<(usize, Precondition)>) -> Vec<Skipped> {
+        self.skipped += skipped.len();
+        // Removed from the end, so earlier indexes still hold.
+        let mut taken = skipped
+            .into_iter()
+            .rev()
+            .map(|(index, reason)| Skipped {
+                rename: self.renames.remove(index),
+                reason,
+            })
+            .collect::<Vec<_>>();
+        taken.reverse();
+        taken
+    }
+}
+
+// Why a rename can no longer be made, if it cannot.
+fn precondition(rename: &Rename, overwrite: bool) -> Option<Precondition> {
+    if fs::symlink_metadata(&rename.from).is_err() {
+        Some(Precondition::Vanished)
+    } else if !overwrite
+        && fs::symlink_metadata(&rename.to).is_ok()
+        && !same_file(&rename.from, &rename.to)
+    {
+        Some(Precondition::Taken)
+    } else {
+        None
+    }
+}
+
+// Rename a file, unless it can no longer be renamed as planned. The file
+// vanishing between the check and the rename counts the same. With
+// overwrite, a file taking the new name is replaced.
+pub(crate) fn rename_checked(rename: &Rename, overwrite: bool) -> io::Result<Option<Precondition>> {
+    if let Some(reason) = precondition(rename, overwrite) {
+        return Ok(Some(reason));
+    }
+    match fs::rename(&rename.from, &rename.to) {
+        Ok(()) => Ok(None),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !rename.from.exists() => {
+            Ok(Some(Precondition::Vanished))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// Whether two paths lead to the same file. eg differing only by case on a
+// case insensitive file system.
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Spaces operations at least an interval apart
+struct Pace {
+    interval: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Pace {
+    fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    fn wait(&mut self) {
+        if let (Some(interval), Some(last)) = (self.interval, self.last) {
+            if let Some(remaining) = interval.checked_sub(last.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+/// Lower the CPU and, on Linux, IO scheduling priority of this process, so a
+/// large batch yields to other users of a shared machine or disk.
+#[cfg(unix)]
+pub fn lower_priority() -> io::Result<()> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        let priority = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn lower_priority() -> io::Result<()> {
+    Ok(())
+}
+
+/// Create and rename a scratch file in a directory, to find out if renames are
+/// allowed there. Network shares (NFS root squash, SMB ACLs) can permit
+/// reading while refusing changes.
+pub fn probe_rename(dir: &Path) -> io::Result<()> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let created = dir.join(format!(".nametag-probe-{}", process::id()));
+    let renamed = dir.join(format!(".nametag-probe-{}-renamed", process::id()));
+    let diagnose = |action: &str, err: io::Error| {
+        let hint = match err.kind() {
+            io::ErrorKind::PermissionDenied => {
+                " (read-only share, or ownership squashed on a network mount?)"
+            }
+            _ => "",
+        };
+        io::Error::new(
+            err.kind(),
+            format!(
+                "Cannot {} files in {}: {}{}",
+                action,
+                dir.display(),
+                err,
+                hint
+            ),
+        )
+    };
+    fs::File::create(&created).map_err(|err| diagnose("create", err))?;
+    if let Err(err) = fs::rename(&created, &renamed) {
+        let _ = fs::remove_file(&created);
+        return Err(diagnose("rename", err));
+    }
+    fs::remove_file(&renamed).map_err(|err| diagnose("remove", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_batch_skips_unchanged() {
+        let mut batch = Batch::new();
+        batch.push("a.txt", "a.txt");
+        batch.push("dir/b.txt", "dir/b[tag].txt");
+        assert_eq!(1, batch.len());
+        assert_eq!(2, batch.scanned());
+        assert_eq!(
+            vec![Path::new("dir")],
+            batch.directories().into_iter().collect::<Vec<_>>()
+        );
+    }
+    #[test]
+    fn test_remove_conflicts() {
+        let dir = env::temp_dir().join("nametag-batch-conflicts");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["a.txt", "b.txt", "c.txt", "a[x].txt"] {
+            fs::File::create(dir.join(name)).unwrap();
+        }
+        let mut batch = Batch::new();
+        batch.push(dir.join("a.txt"), dir.join("a[x].txt"));
+        batch.push(dir.join("b.txt"), dir.join("d.txt"));
+        batch.push(dir.join("c.txt"), dir.join("d.txt"));
+        let conflicts = batch.remove_conflicts();
+        assert_eq!(
+            vec![dir.join("a.txt"), dir.join("c.txt")],
+            conflicts
+                .into_iter()
+                .map(|rename| rename.from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, batch.len());
+        assert_eq!(2, batch.skipped());
+    }
+    #[test]
+    fn test_apply_skips_moved_files() {
+        let dir = env::temp_dir().join("nametag-batch-moved");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["a.txt", "b.txt", "c.txt"] {
+            fs::File::create(dir.join(name)).unwrap();
+        }
+        let mut batch = Batch::new();
+        for name in &["a", "b", "c"] {
+            batch.push(
+                dir.join(format!("{}.txt", name)),
+                dir.join(format!("{}[x].txt", name)),
+            );
+        }
+        // Another process moves files after planning.
+        fs::remove_file(dir.join("b.txt")).unwrap();
+        fs::File::create(dir.join("c[x].txt")).unwrap();
+        let skipped = batch.apply().unwrap();
+        assert_eq!(
+            vec![
+                (dir.join("b.txt"), Precondition::Vanished),
+                (dir.join("c.txt"), Precondition::Taken)
+            ],
+            skipped
+                .into_iter()
+                .map(|skipped| (skipped.rename.from, skipped.reason))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, batch.len());
+        assert_eq!(2, batch.skipped());
+        assert!(dir.join("a[x].txt").exists());
+        assert!(dir.join("c.txt").exists());
+    }
+    #[test]
+    fn test_apply_cancelled() {
+        let dir = env::temp_dir().join("nametag-batch-cancelled");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::File::create(dir.join("a.txt")).unwrap();
+        let mut batch = Batch::new();
+        batch.push(dir.join("a.txt"), dir.join("a[x].txt"));
+        let cancel = Cancel::default();
+        batch.set_cancel(cancel.clone());
+        cancel.cancel();
+        assert_eq!(
+            io::ErrorKind::Interrupted,
+            batch.apply().unwrap_err().kind()
+        );
+        assert!(dir.join("a.txt").exists());
+    }
+    #[test]
+    fn test_readmit_overwrite() {
+        let dir = env::temp_dir().join("nametag-batch-readmit");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "new").unwrap();
+        fs::write(dir.join("a[x].txt"), "old").unwrap();
+        let mut batch = Batch::new();
+        batch.push(dir.join("a.txt"), dir.join("a[x].txt"));
+        let conflicts = batch.remove_conflicts();
+        assert_eq!(1, batch.skipped());
+        batch.readmit(conflicts[0].clone(), true);
+        assert_eq!(0, batch.skipped());
+        assert!(batch.apply().unwrap().is_empty());
+        assert_eq!("new", fs::read_to_string(dir.join("a[x].txt")).unwrap());
+        assert!(!dir.join("a.txt").exists());
+    }
+    #[test]
+    fn test_resolve_conflicts() {
+        let dir = env::temp_dir().join("nametag-batch-resolve");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["a.txt", "b.txt", "c.txt", "a[x].txt"] {
+            fs::File::create(dir.join(name)).unwrap();
+        }
+        let mut batch = Batch::new();
+        batch.push(dir.join("a.txt"), dir.join("a[x].txt"));
+        batch.push(dir.join("b.txt"), dir.join("d.txt"));
+        batch.push(dir.join("c.txt"), dir.join("d.txt"));
+        assert!(batch.resolve_conflicts(&conflict::Counter).is_empty());
+        assert_eq!(
+            vec![
+                dir.join("a (1)[x].txt"),
+                dir.join("d.txt"),
+                dir.join("d (1).txt")
+            ],
+            batch
+                .renames()
+                .iter()
+                .map(|rename| rename.to.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+    #[test]
+    fn test_throttle() {
+        let mut batch = Batch::new();
+        batch.set_throttle(100.0);
+        assert_eq!(Some(Duration::from_millis(10)), batch.throttle);
+        batch.set_throttle(0.0);
+        assert_eq!(None, batch.throttle);
+    }
+    #[test]
+    fn test_pace() {
+        let mut pace = Pace::new(Some(Duration::from_millis(20)));
+        let start = Instant::now();
+        pace.wait();
+        pace.wait();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+    #[test]
+    fn test_probe_rename() {
+        let dir = env::temp_dir();
+        probe_rename(&dir).unwrap();
+        assert!(!dir
+            .join(format!(".nametag-probe-{}-renamed", process::id()))
+            .exists());
+    }
+    #[test]
+    fn test_probe_rename_missing_dir() {
+        let dir = env::temp_dir()
+            .join("nametag-probe-missing")
+            .join("nowhere");
+        let err = probe_rename(&dir).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot create files in"));
+    }
+}
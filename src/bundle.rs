@@ -0,0 +1,74 @@
+// Named sets of tags, saved from the command line and applied together.
+// Stored beside the config. eg ~/.config/nametag/bundles.toml
+//
+// [bundle]
+// trip = ["iceland", "2023", "photo"]
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, ConfigError};
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bundles {
+    pub bundle: BTreeMap<String, Vec<String>>,
+}
+
+impl Bundles {
+    /// Load bundles from a file. A missing file holds no bundles.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ConfigError::Io(path.to_path_buf(), err)),
+        };
+        toml::from_str(&data).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+    }
+
+    /// Bundles live beside the config file. eg ~/.config/nametag/bundles.toml
+    pub fn default_path() -> Option<PathBuf> {
+        Some(Config::default_path()?.with_file_name("bundles.toml"))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, data)
+    }
+
+    /// Tags of a bundle.
+    pub fn get(&self, name: &str) -> Result<&[String], ConfigError> {
+        self.bundle
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| ConfigError::UnknownBundle(name.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_save_load() {
+        let path = env::temp_dir().join("nametag-bundles.toml");
+        let _ = fs::remove_file(&path);
+        assert_eq!(Bundles::default(), Bundles::load(&path).unwrap());
+        let mut bundles = Bundles::default();
+        bundles.bundle.insert(
+            "trip".to_owned(),
+            vec!["iceland".to_owned(), "photo".to_owned()],
+        );
+        bundles.save(&path).unwrap();
+        let loaded = Bundles::load(&path).unwrap();
+        assert_eq!(vec!["iceland", "photo"], loaded.get("trip").unwrap());
+        assert!(loaded.get("work").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+// Results of recent queries of trees without an index, so repeating a query
+// is instant. eg ~/.local/state/nametag/query-cache/1b7a6e0f2c5d9e31.json
+//
+// Each result is kept with the modification time of every directory that
+// was searched. Adding, removing or renaming a file changes the time of its
+// directory, so a result is only used while none of them have changed.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::state_dir;
+
+// Results kept, the least recently stored being dropped first
+const MAX_ENTRIES: usize = 32;
+
+// A directory changed this recently may change again without its time
+// moving, on filesystems with coarse times, so its results are not kept.
+const SETTLE_SECS: u64 = 2;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    dirs: Vec<(PathBuf, (u64, u32))>,
+    files: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct QueryCache {
+    dir: PathBuf,
+}
+
+impl QueryCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Where results are cached when not given explicitly.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("query-cache"))
+    }
+
+    /// A key for everything a result depends on. eg the roots and the query
+    pub fn key(parts: &[&str]) -> String {
+        let mut hash = Sha256::new();
+        for part in parts {
+            hash.update(part.as_bytes());
+            hash.update([0]);
+        }
+        hash.finalize()[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key).with_extension("json")
+    }
+
+    /// The files found for a key, if no directory searched has changed since.
+    pub fn get(&self, key: &str) -> Option<Vec<PathBuf>> {
+        let data = fs::read(self.path(key)).ok()?;
+        let entry: Entry = serde_json::from_slice(&data).ok()?;
+        for (dir, modified) in &entry.dirs {
+            if modified_time(dir).ok()? != *modified {
+                return None;
+            }
+        }
+        Some(entry.files)
+    }
+
+    /// Keep the files found for a key, along with the directories searched.
+    pub fn put(&self, key: &str, dirs: &[PathBuf], files: &[PathBuf]) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut entry = Entry {
+            dirs: Vec::new(),
+            files: files.to_vec(),
+        };
+        for dir in dirs {
+            let modified = modified_time(dir)?;
+            if modified.0 + SETTLE_SECS > now {
+                return Ok(());
+            }
+            entry.dirs.push((dir.clone(), modified));
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(key), serde_json::to_vec(&entry)?)?;
+        self.prune()
+    }
+
+    // Drop the oldest results beyond the most kept.
+    fn prune(&self) -> io::Result<()> {
+        let mut entries = fs::read_dir(&self.dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.metadata()?.modified()?, entry.path()))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        if entries.len() > MAX_ENTRIES {
+            entries.sort();
+            for (_, path) in &entries[..entries.len() - MAX_ENTRIES] {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn modified_time(path: &Path) -> io::Result<(u64, u32)> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_key() {
+        assert_eq!(QueryCache::key(&["a", "b"]), QueryCache::key(&["a", "b"]));
+        assert_ne!(QueryCache::key(&["a", "b"]), QueryCache::key(&["ab", ""]));
+    }
+    #[test]
+    fn test_key_settings() {
+        let root = env::temp_dir().join("nametag-query-cache-settings");
+        let _ = fs::remove_dir_all(&root);
+        let tree = root.join("tree");
+        fs::create_dir_all(&tree).unwrap();
+        let cache = QueryCache::new(root.join("cache"));
+        // As keyed by a query, eg the literal threshold then the key used.
+        let key = |below: &str, obfuscated: &str| QueryCache::key(&["x", below, obfuscated]);
+        let entry = Entry {
+            dirs: vec![(tree.clone(), modified_time(&tree).unwrap())],
+            files: vec![tree.join("a[x].txt")],
+        };
+        fs::create_dir_all(&cache.dir).unwrap();
+        let stored = key("Some(0.5)", "a1");
+        fs::write(cache.path(&stored), serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert!(cache.get(&stored).is_some());
+        assert_eq!(None, cache.get(&key("Some(0.8)", "a1")));
+        assert_eq!(None, cache.get(&key("None", "a1")));
+        assert_eq!(None, cache.get(&key("Some(0.5)", "b2")));
+    }
+    #[test]
+    fn test_stale_entry() {
+        let root = env::temp_dir().join("nametag-query-cache");
+        let _ = fs::remove_dir_all(&root);
+        let tree = root.join("tree");
+        fs::create_dir_all(&tree).unwrap();
+        let cache = QueryCache::new(root.join("cache"));
+        let files = vec![tree.join("a[x].txt")];
+        // Stored as though searched long ago, as new directories are not kept.
+        let entry = Entry {
+            dirs: vec![(tree.clone(), modified_time(&tree).unwrap())],
+            files: files.clone(),
+        };
+        fs::create_dir_all(&cache.dir).unwrap();
+        fs::write(cache.path("k"), serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert_eq!(Some(files), cache.get("k"));
+        let stale = Entry {
+            dirs: vec![(tree.clone(), (0, 0))],
+            ..entry
+        };
+        fs::write(cache.path("k"), serde_json::to_vec(&stale).unwrap()).unwrap();
+        assert_eq!(None, cache.get("k"));
+        // Just changed, so not kept.
+        cache.put("new", &[tree], &[]).unwrap();
+        assert_eq!(None, cache.get("new"));
+    }
+}
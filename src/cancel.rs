@@ -0,0 +1,59 @@
+// Cancellation of long operations, such as walks, indexing and batches of
+// renames, from another thread or a signal handler. Operations check their
+// token at safe points, such as between directories or renames, and stop
+// there with an Interrupted error, leaving nothing half done.
+// eg let cancel = Cancel::default(); options.cancel = cancel.clone();
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag shared between an operation and whatever may cancel it.
+#[derive(Debug, Default, Clone)]
+pub struct Cancel {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancel {
+    /// Ask operations holding this token, or a clone of it, to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// An Interrupted error once cancelled, for operations to return at a
+    /// safe point.
+    pub fn check(&self) -> io::Result<()> {
+        match self.is_cancelled() {
+            true => Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled.")),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Tokens are equal when cancelling one cancels the other.
+impl PartialEq for Cancel {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel() {
+        let cancel = Cancel::default();
+        let shared = cancel.clone();
+        assert!(shared.check().is_ok());
+        cancel.cancel();
+        assert_eq!(
+            io::ErrorKind::Interrupted,
+            shared.check().unwrap_err().kind()
+        );
+        assert_ne!(cancel, Cancel::default());
+    }
+}
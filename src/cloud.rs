@@ -0,0 +1,101 @@
+// A tag cloud for the terminal, giving a sense of what an archive holds at
+// a glance. Terminals have one size of letter, so weight stands in for
+// size: the most used tags are bold, the least dim. Colors are those of the
+// ontology, and without color each tag is followed by its count.
+use crate::ontology::Ontology;
+
+/// Weights a tag may be given, the heaviest being the most used.
+pub const LEVELS: usize = 4;
+
+// Between tags on a line
+const GAP: &str = "  ";
+
+/// The weight of a count among counts running from least to most, from 1
+/// to LEVELS. On a log scale, as a few tags usually dwarf the rest.
+pub fn level(count: usize, least: usize, most: usize) -> usize {
+    if most <= least {
+        return LEVELS;
+    }
+    let span = (most as f64).ln() - (least.max(1) as f64).ln();
+    let share = ((count.max(1) as f64).ln() - (least.max(1) as f64).ln()) / span;
+    1 + (share * (LEVELS - 1) as f64).round() as usize
+}
+
+/// Tags with the number of files carrying each, in the order given, laid
+/// out in lines no wider than width. Colored from the ontology if given.
+pub fn render(counts: &[(String, usize)], width: usize, ontology: Option<&Ontology>) -> String {
+    let least = counts.iter().map(|(_, count)| *count).min().unwrap_or(0);
+    let most = counts.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let mut out = String::new();
+    let mut line_width = 0;
+    for (tag, count) in counts {
+        let (word, word_width) = match ontology {
+            Some(ontology) => {
+                let painted = ontology.color(tag).paint(tag);
+                let word = match level(*count, least, most) {
+                    1 => format!("\x1b[2m{}", painted),
+                    LEVELS => format!("\x1b[1m{}", painted),
+                    _ => painted,
+                };
+                (word, tag.chars().count())
+            }
+            None => {
+                let word = format!("{}({})", tag, count);
+                let word_width = word.chars().count();
+                (word, word_width)
+            }
+        };
+        if line_width > 0 && line_width + GAP.len() + word_width > width {
+            out.push('\n');
+            line_width = 0;
+        }
+        if line_width > 0 {
+            out += GAP;
+            line_width += GAP.len();
+        }
+        out += &word;
+        line_width += word_width;
+    }
+    if line_width > 0 {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(counts: &[(&str, usize)]) -> Vec<(String, usize)> {
+        counts
+            .iter()
+            .map(|(tag, count)| (tag.to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn test_level() {
+        assert_eq!(1, level(1, 1, 1000));
+        assert_eq!(LEVELS, level(1000, 1, 1000));
+        assert_eq!(2, level(10, 1, 1000));
+        assert_eq!(LEVELS, level(5, 5, 5));
+    }
+    #[test]
+    fn test_render_plain() {
+        let counts = counts(&[("beach", 12), ("family", 3), ("receipts", 40)]);
+        assert_eq!(
+            "beach(12)  family(3)\nreceipts(40)\n",
+            render(&counts, 24, None)
+        );
+        assert_eq!("", render(&[], 24, None));
+    }
+    #[test]
+    fn test_render_color() {
+        let ontology = "[tag.beach]\ncolor = \"blue\"\n"
+            .parse::<Ontology>()
+            .unwrap();
+        let counts = counts(&[("beach", 100), ("family", 1)]);
+        let cloud = render(&counts, 80, Some(&ontology));
+        assert!(cloud.starts_with("\x1b[1m\x1b[34mbeach\x1b[0m  \x1b[2m"));
+    }
+}
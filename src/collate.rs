@@ -0,0 +1,123 @@
+// How tags and files are ordered in reports.
+// eg collation = "locale" in config, with the collation feature
+//
+// Byte order is the stable default. Locale order follows the levels of the
+// unicode collation algorithm, in a simplified form: letters compare without
+// accents or case first, then by accent, then lowercase before uppercase.
+// eg Äpfel, apple, banana, élan, Zebra
+use std::cmp::Ordering;
+
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Collation {
+    #[default]
+    Bytes,
+    #[cfg(feature = "collation")]
+    Locale,
+}
+
+impl Collation {
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Bytes => a.cmp(b),
+            #[cfg(feature = "collation")]
+            Collation::Locale => locale::key(a).cmp(&locale::key(b)).then_with(|| a.cmp(b)),
+        }
+    }
+
+    pub fn sort<S: AsRef<str>>(self, items: &mut [S]) {
+        items.sort_by(|a, b| self.compare(a.as_ref(), b.as_ref()));
+    }
+}
+
+#[cfg(feature = "collation")]
+mod locale {
+    // Latin letters with accents, and the letters they are sorted as.
+    const FOLDS: &[(&str, &str)] = &[
+        ("àáâãäåāăą", "a"),
+        ("çćĉċč", "c"),
+        ("ďđð", "d"),
+        ("èéêëēĕėęě", "e"),
+        ("ĝğġģ", "g"),
+        ("ĥħ", "h"),
+        ("ìíîïĩīĭįı", "i"),
+        ("ĵ", "j"),
+        ("ķ", "k"),
+        ("ĺļľŀł", "l"),
+        ("ñńņňŉ", "n"),
+        ("òóôõöøōŏő", "o"),
+        ("ŕŗř", "r"),
+        ("śŝşš", "s"),
+        ("ţťŧ", "t"),
+        ("ùúûüũūŭůűų", "u"),
+        ("ŵ", "w"),
+        ("ýÿŷ", "y"),
+        ("źżž", "z"),
+        ("æ", "ae"),
+        ("œ", "oe"),
+        ("ß", "ss"),
+        ("þ", "th"),
+    ];
+
+    // Primary weights, accents and case of each character, compared in turn.
+    pub(super) type Key = (Vec<(u8, char)>, Vec<char>, Vec<bool>);
+
+    pub(super) fn key(text: &str) -> Key {
+        let mut key = Key::default();
+        for c in text.chars() {
+            let lower = c.to_lowercase().next().unwrap_or(c);
+            let base = FOLDS
+                .iter()
+                .find(|(accented, _)| accented.contains(lower))
+                .map(|(_, base)| *base);
+            let class = if c.is_alphabetic() {
+                2
+            } else if c.is_numeric() {
+                1
+            } else {
+                0
+            };
+            match base {
+                Some(base) => key.0.extend(base.chars().map(|c| (class, c))),
+                None => key.0.push((class, lower)),
+            }
+            key.1.push(if base.is_some() { lower } else { '\0' });
+            key.2.push(c != lower);
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes() {
+        let mut tags = vec!["élan", "Zebra", "apple"];
+        Collation::Bytes.sort(&mut tags);
+        assert_eq!(vec!["Zebra", "apple", "élan"], tags);
+    }
+    #[cfg(feature = "collation")]
+    #[test]
+    fn test_locale() {
+        let mut tags = vec![
+            "élan", "Zebra", "banana", "Äpfel", "apple", "elan", "Elan", "2019",
+        ];
+        Collation::Locale.sort(&mut tags);
+        assert_eq!(
+            vec!["2019", "Äpfel", "apple", "banana", "elan", "Elan", "élan", "Zebra"],
+            tags
+        );
+        assert_eq!(
+            Ordering::Less,
+            Collation::Locale.compare("strasse", "straße")
+        );
+        assert_eq!(
+            Ordering::Less,
+            Collation::Locale.compare("straße", "strasser")
+        );
+    }
+}
@@ -0,0 +1,87 @@
+// Terminal colors for tags. Named in the ontology, or picked from a palette by hashing the tag.
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// An ANSI foreground code. eg 31 for red
+    Ansi(u8),
+    /// A 24 bit color. eg "#ff8800"
+    Rgb(u8, u8, u8),
+}
+
+// Fallback colors for tags without one declared. Black and white are left
+// out as they vanish on one terminal background or the other.
+const PALETTE: [u8; 12] = [31, 32, 33, 34, 35, 36, 91, 92, 93, 94, 95, 96];
+
+impl Color {
+    /// A stable color for a tag, so it looks the same in every listing.
+    pub fn from_hash(tag: &str) -> Self {
+        // FNV-1a, as std's hasher is not stable between releases.
+        let hash = tag.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        Color::Ansi(PALETTE[(hash % PALETTE.len() as u64) as usize])
+    }
+
+    /// Wrap text in the escape codes for this color.
+    pub fn paint(&self, text: &str) -> String {
+        match self {
+            Color::Ansi(code) => format!("\x1b[{}m{}\x1b[0m", code, text),
+            Color::Rgb(r, g, b) => format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text),
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() != 6 || !hex.is_ascii() {
+                return Err("Hex colors take the form #rrggbb.");
+            }
+            let channel = |index: usize| {
+                u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| "Invalid hex color.")
+            };
+            return Ok(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+        let (bright, base) = match name.strip_prefix("bright-") {
+            Some(base) => (true, base),
+            None => (false, name),
+        };
+        let code = match base {
+            "black" => 30,
+            "red" => 31,
+            "green" => 32,
+            "yellow" => 33,
+            "blue" => 34,
+            "magenta" => 35,
+            "cyan" => 36,
+            "white" => 37,
+            _ => return Err("Unknown color name."),
+        };
+        Ok(Color::Ansi(if bright { code + 60 } else { code }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Ok(Color::Ansi(32)), "green".parse());
+        assert_eq!(Ok(Color::Ansi(92)), "bright-green".parse());
+        assert_eq!(Ok(Color::Rgb(255, 136, 0)), "#ff8800".parse());
+        assert!("#ff88".parse::<Color>().is_err());
+        assert!("mauve".parse::<Color>().is_err());
+    }
+    #[test]
+    fn test_hash_color_stable() {
+        assert_eq!(Color::from_hash("invoice"), Color::from_hash("invoice"));
+    }
+    #[test]
+    fn test_paint() {
+        assert_eq!("\x1b[31mtag\x1b[0m", Color::Ansi(31).paint("tag"));
+    }
+}
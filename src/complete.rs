@@ -0,0 +1,59 @@
+// Completions of a partly typed tag, for editors and the rename dialogs
+// of file managers, ranked by how often files near at hand use each tag.
+// eg nametag complete inv --dir ~/finance -> invoice, investment
+use std::collections::BTreeMap;
+
+/// Tags counted by how often they are used, to complete from.
+#[derive(Debug, Default)]
+pub struct Completions {
+    counts: BTreeMap<String, usize>,
+}
+
+impl Completions {
+    /// Count the tags of a file.
+    pub fn add<S: AsRef<str>>(&mut self, tags: &[S]) {
+        for tag in tags {
+            *self.counts.entry(tag.as_ref().to_owned()).or_insert(0) += 1;
+        }
+    }
+
+    /// Offer a tag that may not be used yet, such as one from the ontology.
+    pub fn know(&mut self, tag: &str) {
+        self.counts.entry(tag.to_owned()).or_insert(0);
+    }
+
+    /// Tags starting with a prefix, ignoring case, the most used first and
+    /// otherwise in order. At most limit are given.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<(&str, usize)> {
+        let prefix = prefix.to_lowercase();
+        let mut found = self
+            .counts
+            .iter()
+            .filter(|(tag, _)| tag.to_lowercase().starts_with(&prefix))
+            .map(|(tag, count)| (tag.as_str(), *count))
+            .collect::<Vec<_>>();
+        found.sort_by(|(_, a), (_, b)| b.cmp(a));
+        found.truncate(limit);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete() {
+        let mut completions = Completions::default();
+        completions.add(&["invoice", "work"]);
+        completions.add(&["investment", "invoice"]);
+        completions.know("inventory");
+        completions.know("invoice");
+        assert_eq!(
+            vec![("invoice", 2), ("investment", 1), ("inventory", 0)],
+            completions.complete("INV", 10)
+        );
+        assert_eq!(vec![("invoice", 2)], completions.complete("inv", 1));
+        assert!(completions.complete("x", 10).is_empty());
+    }
+}
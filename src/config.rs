@@ -0,0 +1,456 @@
+// User configuration, read from a toml file.
+// eg ~/.config/nametag/config.toml
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::abbreviate::Abbreviations;
+use crate::alias::Aliases;
+use crate::collate::Collation;
+use crate::conflict::ConflictNaming;
+use crate::derive::DerivedRule;
+use crate::format::TagFormat;
+use crate::guard::RootGuard;
+use crate::limits::Limits;
+use crate::obfuscate::Obfuscator;
+use crate::paths::PathMatching;
+use crate::policy::Policy;
+use crate::redact::Redaction;
+use crate::slug::SlugMode;
+use crate::walk::WalkOptions;
+use crate::workspace;
+use crate::Extension;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Named groups of tags. eg preset.meeting = ["work", "meeting", "date:auto"]
+    pub preset: BTreeMap<String, Vec<String>>,
+    /// Rules adding tags based on other tags. eg [[derived]] when = ["raw"], add = ["photo"]
+    pub derived: Vec<DerivedRule>,
+    /// Refuse to run any command that would modify files.
+    pub read_only: bool,
+    /// Named sets of directories treated as one. eg collection.photos = ["~/Pictures", "/media/disk/photos"]
+    pub collection: BTreeMap<String, Vec<PathBuf>>,
+    /// Tags hidden in file names behind a key. eg sensitive = ["confidential"]
+    pub sensitive: Vec<String>,
+    /// File holding the key for sensitive tags. $NAMETAG_KEY takes precedence.
+    pub key_file: Option<PathBuf>,
+    /// Named profiles of tags to mask and paths to shorten in output.
+    pub redact: BTreeMap<String, Redaction>,
+    /// Tag combinations that may not appear together. eg forbid = [["confidential", "public"]]
+    pub forbid: Vec<Vec<String>>,
+    /// Relative path patterns of files whose tags "check" guards. eg protected = ["contracts/*"]
+    pub protected: Vec<String>,
+    /// How tag text from outside sources, eg file manager tags read by sync,
+    /// is made safe: transliterate, strip or percent-encode.
+    pub slug: SlugMode,
+    /// Rename files that would collide instead of skipping them. eg conflict = "counter"
+    pub conflict: Option<ConflictNaming>,
+    /// Where tags go in names with several periods: first-dot or last-dot. eg archive.tar[x].gz
+    pub extension: Extension,
+    /// Syntax of tags in names: brackets, braces, suffix or a name from formats.
+    pub format: Option<String>,
+    /// Named tag syntaxes. eg formats.pipes = { open = "|", close = "|", separator = "+" }
+    pub formats: BTreeMap<String, TagFormat>,
+    /// Longest file name, in bytes, that tagging may produce. Longer names have
+    /// their tags abbreviated, then truncated.
+    pub max_length: Option<usize>,
+    /// Shorter forms of tags for names over max_length. eg abbreviations.presentation = "pres"
+    pub abbreviations: BTreeMap<String, String>,
+    /// Emoji tags by an ascii name, either finding both in queries. eg aliases.star = "⭐"
+    pub aliases: BTreeMap<String, String>,
+    /// Commands under short names, given the arguments after them as $1, $2 or $@.
+    /// eg alias.done = "add -t done"
+    pub alias: BTreeMap<String, String>,
+    /// Write emoji tags in names by their ascii name.
+    pub ascii: bool,
+    /// Order of tags and files in reports: bytes, or locale with the collation feature.
+    pub collation: Collation,
+    /// Shallowest directory, counted from the filesystem root, that may be
+    /// changed recursively. eg min_depth = 3 refuses /media/disk
+    pub min_depth: usize,
+    /// How directories are searched. eg walk = { symlinks = "follow", threads = 4 }
+    pub walk: WalkOptions,
+    /// Renames applied between checkpoints of the journal, bounding the
+    /// progress a crash can lose. Defaults to 1000.
+    pub chunk_size: Option<usize>,
+    /// Where manifests of the tags files had are written before batches
+    /// taking tags from them. Defaults to backups in the state directory.
+    pub backup_dir: Option<PathBuf>,
+    /// How paths recorded on different systems are matched up: exact,
+    /// native or portable. eg path_matching = "portable" for archives
+    /// shared between macOS and Linux.
+    pub path_matching: PathMatching,
+    /// Bounds on the length and number of tags, kept by edits and reported
+    /// by lint. eg limits = { min_tag_length = 1, max_tags = 20 }
+    pub limits: Limits,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    UnknownPreset(String),
+    UnknownValue(String),
+    UnknownCollection(String),
+    UnknownRedaction(String),
+    UnknownBundle(String),
+    UnknownFormat(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::UnknownPreset(name) => write!(f, "No preset named \"{}\"", name),
+            ConfigError::UnknownValue(tag) => {
+                write!(f, "No computed value available for \"{}\"", tag)
+            }
+            ConfigError::UnknownCollection(name) => {
+                write!(f, "No collection named \"{}\"", name)
+            }
+            ConfigError::UnknownRedaction(name) => {
+                write!(f, "No redaction profile named \"{}\"", name)
+            }
+            ConfigError::UnknownBundle(name) => write!(f, "No bundle named \"{}\"", name),
+            ConfigError::UnknownFormat(name) => write!(f, "No tag format named \"{}\"", name),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Load config from a file. A missing file at the default location is an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let data = fs::read_to_string(&path).map_err(|err| ConfigError::Io(path.clone(), err))?;
+        data.parse()
+            .map_err(|err| ConfigError::Parse(path.clone(), err))
+    }
+
+    /// Where config lives when not given explicitly.
+    /// $NAMETAG_CONFIG, else that of the workspace, else $XDG_CONFIG_HOME/nametag/config.toml,
+    /// else ~/.config/nametag/config.toml
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("NAMETAG_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        if let Some(path) = workspace::config_path() {
+            return Some(path);
+        }
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("nametag").join("config.toml"))
+    }
+
+    /// Expand a preset into its tags, computing any "name:auto" values.
+    pub fn expand_preset(&self, name: &str) -> Result<Vec<String>, ConfigError> {
+        let tags = self
+            .preset
+            .get(name)
+            .ok_or_else(|| ConfigError::UnknownPreset(name.to_owned()))?;
+        tags.iter().map(|tag| expand_tag(tag)).collect()
+    }
+
+    /// Root directories of a collection, with a leading ~ expanded to the home directory.
+    pub fn collection(&self, name: &str) -> Result<Vec<PathBuf>, ConfigError> {
+        let roots = self
+            .collection
+            .get(name)
+            .ok_or_else(|| ConfigError::UnknownCollection(name.to_owned()))?;
+        Ok(roots.iter().map(|root| expand_home(root)).collect())
+    }
+
+    pub fn policy(&self) -> Policy {
+        Policy::new(self.forbid.clone())
+    }
+
+    /// Guard against changing too broad a directory recursively.
+    pub fn root_guard(&self) -> RootGuard {
+        let home = env::var_os("HOME").map(PathBuf::from);
+        RootGuard::new(
+            self.min_depth,
+            home.and_then(|home| home.canonicalize().ok()),
+        )
+    }
+
+    /// A redaction profile.
+    pub fn redaction(&self, name: &str) -> Result<Redaction, ConfigError> {
+        self.redact
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ConfigError::UnknownRedaction(name.to_owned()))
+    }
+
+    /// The tag syntax in use.
+    pub fn tag_format(&self) -> Result<TagFormat, ConfigError> {
+        match &self.format {
+            Some(name) => self.named_format(name),
+            None => Ok(TagFormat::default()),
+        }
+    }
+
+    /// A tag syntax by name. Formats from config take precedence over built in ones.
+    pub fn named_format(&self, name: &str) -> Result<TagFormat, ConfigError> {
+        self.formats
+            .get(name)
+            .cloned()
+            .or_else(|| TagFormat::named(name))
+            .ok_or_else(|| ConfigError::UnknownFormat(name.to_owned()))
+    }
+
+    pub fn abbreviations(&self) -> Abbreviations {
+        Abbreviations::new(self.max_length, self.abbreviations.clone())
+    }
+
+    pub fn aliases(&self) -> Aliases {
+        Aliases::new(self.aliases.clone())
+    }
+
+    /// Obfuscator for sensitive tags, if a key is available.
+    pub fn obfuscator(&self) -> Result<Option<Obfuscator>, ConfigError> {
+        let key = match (env::var_os("NAMETAG_KEY"), &self.key_file) {
+            (Some(key), _) => key.into_encoded_bytes(),
+            (None, Some(path)) => {
+                let path = expand_home(path);
+                let key = fs::read_to_string(&path).map_err(|err| ConfigError::Io(path, err))?;
+                key.trim().as_bytes().to_vec()
+            }
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(Obfuscator::new(key, self.sensitive.clone())))
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        toml::from_str(data)
+    }
+}
+
+/// Where runtime state, such as the batch journal, is kept.
+/// $NAMETAG_STATE_DIR, else $XDG_STATE_HOME/nametag, else ~/.local/state/nametag
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("NAMETAG_STATE_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(base.join("nametag"))
+}
+
+// Computed values replace "auto". eg date:auto -> date=2020-04-30
+fn expand_tag(tag: &str) -> Result<String, ConfigError> {
+    let key = match tag.strip_suffix(":auto") {
+        Some(key) => key,
+        None => return Ok(tag.to_owned()),
+    };
+    let (year, month, day) = today();
+    let value = match key {
+        "date" => format!("{:04}-{:02}-{:02}", year, month, day),
+        "year" => format!("{:04}", year),
+        "month" => format!("{:02}", month),
+        "day" => format!("{:02}", day),
+        _ => return Err(ConfigError::UnknownValue(tag.to_owned())),
+    };
+    Ok(format!("{}={}", key, value))
+}
+
+// Current UTC date as (year, month, day)
+pub(crate) fn today() -> (i64, u32, u32) {
+    civil_from_days(days_today())
+}
+
+// Days since 1970-01-01 to the current UTC date
+pub(crate) fn days_today() -> i64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0);
+    secs.div_euclid(86400)
+}
+
+// Calendar date to days since 1970-01-01, the inverse of civil_from_days.
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Days since 1970-01-01 to a calendar date. http://howardhinnant.github.io/date_algorithms.html
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_preset() {
+        let config: Config = r#"preset.meeting = ["work", "meeting"]"#.parse().unwrap();
+        assert_eq!(
+            vec!["work", "meeting"],
+            config.expand_preset("meeting").unwrap()
+        );
+    }
+    #[test]
+    fn test_expand_preset_computed() {
+        let config: Config = r#"preset.log = ["year:auto"]"#.parse().unwrap();
+        let tags = config.expand_preset("log").unwrap();
+        assert!(tags[0].starts_with("year="));
+    }
+    #[test]
+    fn test_expand_preset_missing() {
+        let config = Config::default();
+        assert!(config.expand_preset("meeting").is_err());
+    }
+    #[test]
+    fn test_derived_rules() {
+        let config: Config = r#"
+            [[derived]]
+            when = ["raw", "year<2015"]
+            add = ["legacy"]
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(vec!["legacy"], config.derived[0].add);
+    }
+    #[test]
+    fn test_read_only() {
+        let config: Config = "read_only = true".parse().unwrap();
+        assert!(config.read_only);
+        assert!(!Config::default().read_only);
+    }
+    #[test]
+    fn test_collection() {
+        let config: Config = r#"collection.photos = ["/media/disk/photos", "/mnt/nas"]"#
+            .parse()
+            .unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("/media/disk/photos"),
+                PathBuf::from("/mnt/nas")
+            ],
+            config.collection("photos").unwrap()
+        );
+        assert!(config.collection("music").is_err());
+    }
+    #[test]
+    fn test_redaction() {
+        let config: Config = r#"
+            [redact.external]
+            tags = ["client=*"]
+            path_components = 1
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            "client=***",
+            config.redaction("external").unwrap().tag("client=acme")
+        );
+        assert!(config.redaction("internal").is_err());
+    }
+    #[test]
+    fn test_protected() {
+        let config: Config = r#"protected = ["contracts/*"]"#.parse().unwrap();
+        assert_eq!(vec!["contracts/*"], config.protected);
+    }
+    #[test]
+    fn test_slug() {
+        let config: Config = r#"slug = "percent-encode""#.parse().unwrap();
+        assert_eq!(SlugMode::PercentEncode, config.slug);
+        assert_eq!(SlugMode::Transliterate, Config::default().slug);
+    }
+    #[test]
+    fn test_conflict() {
+        let config: Config = r#"conflict = "hash""#.parse().unwrap();
+        assert_eq!(Some(ConflictNaming::Hash), config.conflict);
+        let config: Config = r#"conflict = { template = "{stem}~{n}" }"#.parse().unwrap();
+        assert_eq!(
+            Some(ConflictNaming::Template("{stem}~{n}".to_owned())),
+            config.conflict
+        );
+    }
+    #[test]
+    fn test_extension() {
+        let config: Config = r#"extension = "last-dot""#.parse().unwrap();
+        assert_eq!(Extension::LastDot, config.extension);
+    }
+    #[test]
+    fn test_tag_format() {
+        let config: Config = r#"
+            format = "pipes"
+            formats.pipes = { open = "|", close = "|", separator = "+" }
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!("+", config.tag_format().unwrap().separator);
+        let config: Config = r#"format = "braces""#.parse().unwrap();
+        assert_eq!("{", config.tag_format().unwrap().open);
+        let config: Config = r#"format = "parens""#.parse().unwrap();
+        assert!(config.tag_format().is_err());
+        assert_eq!(
+            TagFormat::default(),
+            Config::default().tag_format().unwrap()
+        );
+    }
+    #[test]
+    fn test_alias() {
+        let config: Config = r#"alias.done = "add -t done""#.parse().unwrap();
+        assert_eq!("add -t done", config.alias["done"]);
+    }
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!((1970, 1, 1), civil_from_days(0));
+        assert_eq!((2000, 2, 29), civil_from_days(11016));
+        assert_eq!((1969, 12, 31), civil_from_days(-1));
+        for days in [-1, 0, 11016, 20742] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days, days_from_civil(year, month, day));
+        }
+    }
+}
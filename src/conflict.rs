@@ -0,0 +1,157 @@
+// Alternative names for renames that would overwrite another file.
+// eg a[x].txt -> a (1)[x].txt
+//
+// conflict = "counter", "timestamp", "hash" or { template = "{stem}~{n}" }
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::civil_from_days;
+
+/// Picks a new stem for a file name, which comes before its tags and extension.
+pub trait ConflictNamer {
+    /// Candidate stem for a rename on its nth attempt, counting from 1.
+    fn stem(&self, stem: &str, from: &Path, attempt: usize) -> String;
+}
+
+/// eg a (1)
+pub struct Counter;
+
+impl ConflictNamer for Counter {
+    fn stem(&self, stem: &str, _from: &Path, attempt: usize) -> String {
+        format!("{} ({})", stem, attempt)
+    }
+}
+
+/// eg a-20231005T142301, numbered after the first attempt.
+pub struct Timestamp;
+
+impl ConflictNamer for Timestamp {
+    fn stem(&self, stem: &str, _from: &Path, attempt: usize) -> String {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let time = secs.rem_euclid(86400);
+        let stamp = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        );
+        match attempt {
+            1 => format!("{}-{}", stem, stamp),
+            _ => format!("{}-{}-{}", stem, stamp, attempt),
+        }
+    }
+}
+
+/// eg a-3f2a9c1b, hashed from the original path so it is stable between runs.
+pub struct ShortHash;
+
+impl ConflictNamer for ShortHash {
+    fn stem(&self, stem: &str, from: &Path, attempt: usize) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(from.as_os_str().as_encoded_bytes());
+        hasher.update(attempt.to_be_bytes());
+        let hash = hasher.finalize();
+        let hex = hash[..4]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        format!("{}-{}", stem, hex)
+    }
+}
+
+/// A user template, where {stem} is the original stem and {n} the attempt. eg "{stem}~{n}"
+pub struct Template(pub String);
+
+impl ConflictNamer for Template {
+    fn stem(&self, stem: &str, _from: &Path, attempt: usize) -> String {
+        self.0
+            .replace("{stem}", stem)
+            .replace("{n}", &attempt.to_string())
+    }
+}
+
+/// A strategy as written in config.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictNaming {
+    Counter,
+    Timestamp,
+    Hash,
+    Template(String),
+}
+
+impl ConflictNaming {
+    pub fn namer(&self) -> Box<dyn ConflictNamer> {
+        match self {
+            ConflictNaming::Counter => Box::new(Counter),
+            ConflictNaming::Timestamp => Box::new(Timestamp),
+            ConflictNaming::Hash => Box::new(ShortHash),
+            ConflictNaming::Template(template) => Box::new(Template(template.clone())),
+        }
+    }
+}
+
+/// A path with its stem renamed. None if the name is not valid unicode.
+pub fn candidate(
+    namer: &dyn ConflictNamer,
+    to: &Path,
+    from: &Path,
+    attempt: usize,
+) -> Option<PathBuf> {
+    let name = to.file_name().and_then(OsStr::to_str)?;
+    // The stem runs up to the tags or the extension, whichever comes first.
+    let split = name.find(['[', '.']).unwrap_or(name.len());
+    let stem = namer.stem(&name[..split], from, attempt);
+    Some(to.with_file_name(format!("{}{}", stem, &name[split..])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter() {
+        let from = Path::new("/a/b.txt");
+        assert_eq!(
+            Some(PathBuf::from("/a/b (2)[x].txt")),
+            candidate(&Counter, Path::new("/a/b[x].txt"), from, 2)
+        );
+        assert_eq!(
+            Some(PathBuf::from("/a/b (1)")),
+            candidate(&Counter, Path::new("/a/b"), from, 1)
+        );
+    }
+    #[test]
+    fn test_hash() {
+        let to = Path::new("b[x].txt");
+        let first = candidate(&ShortHash, to, Path::new("a.txt"), 1).unwrap();
+        assert_eq!(
+            first,
+            candidate(&ShortHash, to, Path::new("a.txt"), 1).unwrap()
+        );
+        assert_ne!(
+            first,
+            candidate(&ShortHash, to, Path::new("a.txt"), 2).unwrap()
+        );
+        assert_eq!("b-".len() + 8 + "[x].txt".len(), first.as_os_str().len());
+    }
+    #[test]
+    fn test_template() {
+        let template = Template("{stem}~{n}".to_owned());
+        assert_eq!(
+            Some(PathBuf::from("b~3.txt")),
+            candidate(&template, Path::new("b.txt"), Path::new("a.txt"), 3)
+        );
+    }
+}
@@ -0,0 +1,342 @@
+// Derive tags from the presence of other tags.
+// eg when = ["raw", "year<2015"], add = ["legacy"]
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::NameTag;
+
+/// A rule as written in config.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DerivedRule {
+    /// Conditions that must all hold. eg "raw", "!draft", "year<2015"
+    pub when: Vec<String>,
+    /// Tags added when the conditions hold.
+    pub add: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeriveError {
+    BadCondition(String),
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for DeriveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeriveError::BadCondition(cond) => write!(f, "Invalid rule condition \"{}\"", cond),
+            DeriveError::Cycle(tags) => {
+                write!(
+                    f,
+                    "Derived rules form a cycle through: {}",
+                    tags.join(" -> ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeriveError {}
+
+/// A comparison of a numeric value, in rule conditions and queries. eg year<2015
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compare {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Compare {
+    /// Text either side of the comparison in it, if any. eg "year<=2015"
+    /// -> ("year", LessEqual, "2015")
+    pub fn split(text: &str) -> Option<(&str, Compare, &str)> {
+        // Longest operators first, so "<=" is not read as "<".
+        for compare in &[
+            Compare::LessEqual,
+            Compare::GreaterEqual,
+            Compare::Less,
+            Compare::Greater,
+        ] {
+            if let Some(index) = text.find(compare.symbol()) {
+                return Some((
+                    &text[..index],
+                    *compare,
+                    &text[index + compare.symbol().len()..],
+                ));
+            }
+        }
+        None
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Compare::Less => "<",
+            Compare::LessEqual => "<=",
+            Compare::Greater => ">",
+            Compare::GreaterEqual => ">=",
+        }
+    }
+
+    /// Whether a value compares as asked with a target.
+    pub fn holds(self, value: f64, target: f64) -> bool {
+        match self {
+            Compare::Less => value < target,
+            Compare::LessEqual => value <= target,
+            Compare::Greater => value > target,
+            Compare::GreaterEqual => value >= target,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Condition {
+    Present(String),
+    Absent(String),
+    Value(String, Compare, f64),
+}
+
+impl Condition {
+    fn parse(data: &str) -> Result<Self, DeriveError> {
+        let bad = || DeriveError::BadCondition(data.to_owned());
+        if let Some(tag) = data.strip_prefix('!') {
+            if tag.is_empty() {
+                return Err(bad());
+            }
+            return Ok(Condition::Absent(tag.to_owned()));
+        }
+        if let Some((key, compare, value)) = Compare::split(data) {
+            let value = value.parse().map_err(|_| bad())?;
+            if key.is_empty() {
+                return Err(bad());
+            }
+            return Ok(Condition::Value(key.to_owned(), compare, value));
+        }
+        if data.is_empty() {
+            return Err(bad());
+        }
+        Ok(Condition::Present(data.to_owned()))
+    }
+
+    // Tag name this condition depends upon
+    fn key(&self) -> &str {
+        match self {
+            Condition::Present(tag) | Condition::Absent(tag) | Condition::Value(tag, _, _) => tag,
+        }
+    }
+
+    // Does a tag added by another rule influence this condition
+    fn depends_on(&self, tag: &str) -> bool {
+        match self {
+            Condition::Value(key, _, _) => tag
+                .split_once('=')
+                .is_some_and(|(tag_key, _)| tag_key == key),
+            _ => self.key() == tag,
+        }
+    }
+
+    fn matches(&self, nametag: &NameTag) -> bool {
+        match self {
+            Condition::Present(tag) => nametag.has_tag(tag),
+            Condition::Absent(tag) => !nametag.has_tag(tag),
+            // Values come from inline tags and the machine block alike, and
+            // any one of several values may satisfy the comparison.
+            Condition::Value(key, compare, target) => nametag
+                .get_values(key)
+                .iter()
+                .filter_map(|value| value.parse::<f64>().ok())
+                .any(|value| compare.holds(value, *target)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    when: Vec<Condition>,
+    add: Vec<String>,
+}
+
+/// Rules checked for cycles and sorted so each is evaluated once, after any
+/// rule that could add a tag it depends upon.
+#[derive(Debug, Default)]
+pub struct DerivedRules {
+    rules: Vec<Rule>,
+}
+
+impl DerivedRules {
+    pub fn new(rules: &[DerivedRule]) -> Result<Self, DeriveError> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                Ok(Rule {
+                    when: rule
+                        .when
+                        .iter()
+                        .map(|cond| Condition::parse(cond))
+                        .collect::<Result<_, _>>()?,
+                    add: rule.add.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let order = Self::sort(&rules)?;
+        let mut slots = rules.into_iter().map(Some).collect::<Vec<_>>();
+        Ok(Self {
+            rules: order
+                .into_iter()
+                .filter_map(|index| slots[index].take())
+                .collect(),
+        })
+    }
+
+    /// Add derived tags, returning the tags that were newly added.
+    pub fn apply(&self, nametag: &mut NameTag) -> Vec<String> {
+        let mut added = Vec::new();
+        for rule in &self.rules {
+            if rule.when.iter().all(|cond| cond.matches(nametag)) {
+                for tag in &rule.add {
+                    if !nametag.has_tag(tag) {
+                        nametag.add_tag(tag);
+                        added.push(tag.clone());
+                    }
+                }
+            }
+        }
+        added
+    }
+
+    // Rule indices in dependency order, or the cycle preventing one
+    fn sort(rules: &[Rule]) -> Result<Vec<usize>, DeriveError> {
+        // For each rule, the rules adding a tag it depends upon
+        let providers = rules
+            .iter()
+            .map(|rule| {
+                rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| {
+                        other
+                            .add
+                            .iter()
+                            .any(|tag| rule.when.iter().any(|cond| cond.depends_on(tag)))
+                    })
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut visiting = Vec::new();
+        let mut order = Vec::with_capacity(rules.len());
+        for index in 0..rules.len() {
+            Self::visit(index, rules, &providers, &mut visiting, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    // Depth first walk of providers, recording rules once all their providers are recorded
+    fn visit(
+        index: usize,
+        rules: &[Rule],
+        providers: &[Vec<usize>],
+        visiting: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), DeriveError> {
+        if order.contains(&index) {
+            return Ok(());
+        }
+        if let Some(start) = visiting.iter().position(|i| *i == index) {
+            let cycle = visiting[start..]
+                .iter()
+                .chain(std::iter::once(&index))
+                .map(|i| rules[*i].add.join(" "))
+                .collect();
+            return Err(DeriveError::Cycle(cycle));
+        }
+        visiting.push(index);
+        for provider in &providers[index] {
+            Self::visit(*provider, rules, providers, visiting, order)?;
+        }
+        visiting.pop();
+        order.push(index);
+        Ok(())
+    }
+}
+
+impl DerivedRule {
+    pub fn new<W: Into<String>, A: Into<String>>(when: Vec<W>, add: Vec<A>) -> Self {
+        Self {
+            when: when.into_iter().map(Into::into).collect(),
+            add: add.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::TagFormat;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_derive_simple() {
+        let rules = DerivedRules::new(&[DerivedRule::new(vec!["raw"], vec!["photo"])]).unwrap();
+        let mut name_tag = NameTag::new("somefile[raw].cr2");
+        assert_eq!(vec!["photo"], rules.apply(&mut name_tag));
+        assert_eq!(
+            "somefile[photo raw].cr2",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+    #[test]
+    fn test_derive_value_compare() {
+        let rules =
+            DerivedRules::new(&[DerivedRule::new(vec!["raw", "year<2015"], vec!["legacy"])])
+                .unwrap();
+        let mut old = NameTag::new("a[raw year=2012].cr2");
+        let mut new = NameTag::new("b[raw year=2019].cr2");
+        assert_eq!(vec!["legacy"], rules.apply(&mut old));
+        assert!(rules.apply(&mut new).is_empty());
+    }
+    #[test]
+    fn test_derive_value_machine() {
+        let rules =
+            DerivedRules::new(&[DerivedRule::new(vec!["year<2015"], vec!["legacy"])]).unwrap();
+        let mut machine =
+            NameTag::with_format("a[raw]{year=2012}.cr2", TagFormat::named("dual").unwrap());
+        assert_eq!(vec!["legacy"], rules.apply(&mut machine));
+        let mut several = NameTag::new("b[year=2019+2012].cr2");
+        assert_eq!(vec!["legacy"], rules.apply(&mut several));
+    }
+    #[test]
+    fn test_derive_chain_out_of_order() {
+        let rules = DerivedRules::new(&[
+            DerivedRule::new(vec!["legacy"], vec!["archive"]),
+            DerivedRule::new(vec!["raw"], vec!["legacy"]),
+        ])
+        .unwrap();
+        let mut name_tag = NameTag::new("a[raw].cr2");
+        assert_eq!(vec!["legacy", "archive"], rules.apply(&mut name_tag));
+    }
+    #[test]
+    fn test_derive_absent() {
+        let rules = DerivedRules::new(&[DerivedRule::new(vec!["!done"], vec!["todo"])]).unwrap();
+        let mut name_tag = NameTag::new("a[done].txt");
+        assert!(rules.apply(&mut name_tag).is_empty());
+    }
+    #[test]
+    fn test_derive_cycle() {
+        let err = DerivedRules::new(&[
+            DerivedRule::new(vec!["a"], vec!["b"]),
+            DerivedRule::new(vec!["b"], vec!["a"]),
+        ])
+        .unwrap_err();
+        assert!(matches!(err, DeriveError::Cycle(_)));
+    }
+    #[test]
+    fn test_derive_bad_condition() {
+        assert_eq!(
+            DeriveError::BadCondition("year<old".to_owned()),
+            DerivedRules::new(&[DerivedRule::new(vec!["year<old"], vec!["x"])]).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// Suggestions for tidying the tags across many files, each with a command
+// carrying it out.
+//
+// Tags differing only in case, or by a letter or two, are likely the same
+// tag and are suggested for merging into the more used one. Tags with
+// numbers or values are only compared by case, as "2019" and "2018" are
+// rightly different. Tags on a single file are listed for review, and names
+// over the length limit for abbreviating.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    pub problem: String,
+    /// Shell command fixing the problem, if there is one.
+    pub command: Option<String>,
+}
+
+/// Suggestions for a set of files and their tags. Names longer than
+/// max_length bytes are reported, with a command shortening them if
+/// abbreviate is set, as retagging then shortens names.
+pub fn diagnose(
+    files: &[(PathBuf, Vec<String>)],
+    max_length: usize,
+    abbreviate: bool,
+) -> Vec<Suggestion> {
+    let mut usage = BTreeMap::<&str, Vec<&PathBuf>>::new();
+    for (path, tags) in files {
+        for tag in tags {
+            usage.entry(tag).or_default().push(path);
+        }
+    }
+    let mut suggestions = Vec::new();
+    let mut merged = Vec::new();
+    let tags = usage.keys().copied().collect::<Vec<_>>();
+    for (index, a) in tags.iter().enumerate() {
+        for b in &tags[index + 1..] {
+            let reason = match similarity(a, b) {
+                Some(reason) => reason,
+                None => continue,
+            };
+            // Keep the more used tag.
+            let (keep, drop) = if usage[b].len() > usage[a].len() {
+                (b, a)
+            } else {
+                (a, b)
+            };
+            let paths = &usage[drop];
+            merged.push(*drop);
+            suggestions.push(Suggestion {
+                problem: format!(
+                    "Merge \"{}\" ({} files) into \"{}\" ({} files), {}",
+                    drop,
+                    paths.len(),
+                    keep,
+                    usage[keep].len(),
+                    reason
+                ),
+                command: Some(format!(
+                    "nametag tx begin && nametag add -t {keep} -- {paths} && \
+                     nametag remove -t {drop} -- {paths} && nametag tx commit",
+                    keep = quote(keep),
+                    drop = quote(drop),
+                    paths = quote_paths(paths),
+                )),
+            });
+        }
+    }
+    for (tag, paths) in &usage {
+        if paths.len() == 1 && !merged.contains(tag) {
+            suggestions.push(Suggestion {
+                problem: format!("Review \"{}\", used only by {}", tag, paths[0].display()),
+                command: Some(format!(
+                    "nametag remove -t {} -- {}",
+                    quote(tag),
+                    quote_paths(paths)
+                )),
+            });
+        }
+    }
+    for (path, tags) in files {
+        let length = path.file_name().map_or(0, |name| name.len());
+        if length <= max_length {
+            continue;
+        }
+        let longest = tags.iter().max_by_key(|tag| tag.len());
+        suggestions.push(Suggestion {
+            problem: format!(
+                "Abbreviate the tags of {}, a name of {} bytes, over {}",
+                path.display(),
+                length,
+                max_length
+            ),
+            // Adding a tag already present changes nothing but the length.
+            command: longest.filter(|_| abbreviate).map(|tag| {
+                format!(
+                    "nametag add -t {} -- {}",
+                    quote(tag),
+                    quote(&path.to_string_lossy())
+                )
+            }),
+        });
+    }
+    suggestions
+}
+
+// Why two tags look like the same tag, if they do.
+fn similarity(a: &str, b: &str) -> Option<&'static str> {
+    if a.to_lowercase() == b.to_lowercase() {
+        return Some("differing in case");
+    }
+    let plain = |tag: &str| !tag.contains(|c: char| c.is_numeric() || c == '=');
+    if !plain(a) || !plain(b) {
+        return None;
+    }
+    let shortest = a.chars().count().min(b.chars().count());
+    let allowed = match shortest {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    if allowed > 0 && distance(a, b) <= allowed {
+        Some("similar in spelling")
+    } else {
+        None
+    }
+}
+
+// Edits turning one text into the other. See Levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(a != *b);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// An argument as it must be written in a shell command.
+fn quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_=+./,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+fn quote_paths(paths: &[&PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| quote(&path.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(files: &[(&str, &[&str])]) -> Vec<(PathBuf, Vec<String>)> {
+        files
+            .iter()
+            .map(|(path, tags)| {
+                let tags = tags.iter().map(|tag| tag.to_string()).collect();
+                (PathBuf::from(path), tags)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(Some("differing in case"), similarity("Photo", "photo"));
+        assert_eq!(Some("similar in spelling"), similarity("photo", "phota"));
+        assert_eq!(
+            Some("similar in spelling"),
+            similarity("holiday", "holyday")
+        );
+        assert_eq!(None, similarity("cat", "car"));
+        assert_eq!(None, similarity("2019", "2018"));
+        assert_eq!(None, similarity("photo", "video"));
+    }
+    #[test]
+    fn test_diagnose() {
+        let files = files(&[
+            ("a[photo].jpg", &["photo"]),
+            ("b[photo].jpg", &["photo"]),
+            ("c d[Photo work].jpg", &["Photo", "work"]),
+        ]);
+        let suggestions = diagnose(&files, 16, true);
+        assert_eq!(3, suggestions.len());
+        assert_eq!(
+            Some(
+                "nametag tx begin && nametag add -t photo -- 'c d[Photo work].jpg' && \
+                 nametag remove -t Photo -- 'c d[Photo work].jpg' && nametag tx commit"
+                    .to_owned()
+            ),
+            suggestions[0].command
+        );
+        assert_eq!(
+            "Review \"work\", used only by c d[Photo work].jpg",
+            suggestions[1].problem
+        );
+        assert_eq!(
+            Some("nametag add -t Photo -- 'c d[Photo work].jpg'".to_owned()),
+            suggestions[2].command
+        );
+    }
+    #[test]
+    fn test_quote() {
+        assert_eq!("'a[x].txt'", quote("a[x].txt"));
+        assert_eq!("plain", quote("plain"));
+        assert_eq!("'it'\\''s'", quote("it's"));
+    }
+}
@@ -0,0 +1,95 @@
+// Escape arbitrary tag content as %XX, so any tag can live in a file name.
+// eg "New York" -> New%20York
+//
+// Whitespace, commas, brackets and path separators would otherwise split or
+// end the tag, and bytes that are not utf8 do not survive every file system.
+use std::ffi::{OsStr, OsString};
+
+// Bytes that must be escaped even within valid utf8.
+fn is_reserved(byte: u8) -> bool {
+    byte.is_ascii_whitespace()
+        || byte.is_ascii_control()
+        || matches!(byte, b'%' | b',' | b'[' | b']' | b'/' | b'\\')
+}
+
+/// Tag content in escaped form.
+pub fn escape<T: AsRef<OsStr>>(tag: T) -> String {
+    let mut out = String::new();
+    for chunk in tag.as_ref().as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_ascii() && is_reserved(c as u8) {
+                out.push_str(&format!("%{:02X}", c as u8));
+            } else {
+                out.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+/// Original content of an escaped tag. A "%" not followed by two hex digits is kept as is.
+pub fn unescape<T: AsRef<OsStr>>(tag: T) -> OsString {
+    let bytes = tag.as_ref().as_encoded_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let decoded = match bytes[index] {
+            b'%' => bytes
+                .get(index + 1..index + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(byte) => {
+                out.push(byte);
+                index += 3;
+            }
+            None => {
+                out.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    // Decoded bytes may form any sequence, which is only a valid OsString on unix.
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(out)
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(&out).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("New%20York", escape("New York"));
+        assert_eq!("a%2Cb%5B1%5D%25", escape("a,b[1]%"));
+        assert_eq!("café", escape("café"));
+    }
+    #[test]
+    fn test_round_trip() {
+        for tag in &["New York", "a,b[1]%", "tab\tnew\nline", "café", "plain"] {
+            assert_eq!(OsString::from(tag), unescape(escape(tag)));
+        }
+        assert_eq!(OsString::from("100%"), unescape("100%"));
+        assert_eq!(OsString::from("%zz"), unescape("%zz"));
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_round_trip_bytes() {
+        use std::os::unix::ffi::OsStringExt;
+        let tag = OsString::from_vec(vec![b'a', 0xff, b'b']);
+        assert_eq!("a%FFb", escape(&tag));
+        assert_eq!(tag, unescape("a%FFb"));
+    }
+}
@@ -0,0 +1,108 @@
+// Output for programs, as events on stdout, one json object per line,
+// in place of text written for people, which may change between releases.
+// Each names its kind in "event":
+//   plan    a rename shown rather than made. {"from", "to"}
+//   apply   a rename made. {"from", "to"}
+//   skip    a file left as it was. {"path", "reason"}
+//   warn    a warning, or the error ending a run. {"level", "kind", "path", "message"}
+//   result  what a command reports. {"path", "tags"} for files, else {"text"}
+// eg {"event":"result","path":"a[x].txt","tags":["x"]}
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::log::{Kind, Record};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Output {
+    #[default]
+    Text,
+    JsonLines,
+}
+
+impl FromStr for Output {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(Output::Text),
+            "json-lines" => Ok(Output::JsonLines),
+            _ => Err("Expected one of text or json-lines."),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    Plan {
+        from: &'a Path,
+        to: &'a Path,
+    },
+    Apply {
+        from: &'a Path,
+        to: &'a Path,
+    },
+    Skip {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<&'a Path>,
+        reason: &'a str,
+    },
+    Warn(&'a Record),
+    Result {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<&'a Path>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tags: Option<&'a [String]>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<&'a str>,
+    },
+}
+
+impl<'a> Event<'a> {
+    /// A warning, or a skip for warnings of files left as they were.
+    pub fn from_record(record: &'a Record) -> Self {
+        match record.kind {
+            Some(Kind::Skipped) => Event::Skip {
+                path: record.path.as_deref(),
+                reason: &record.message,
+            },
+            _ => Event::Warn(record),
+        }
+    }
+
+    /// The event as a line, without its newline.
+    pub fn line(&self) -> String {
+        serde_json::to_string(self).expect("event serializes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        let tags = vec!["x".to_owned()];
+        let found = Event::Result {
+            path: Some(Path::new("a[x].txt")),
+            tags: Some(&tags),
+            text: None,
+        };
+        assert_eq!(
+            r#"{"event":"result","path":"a[x].txt","tags":["x"]}"#,
+            found.line()
+        );
+        let skipped = Record::warning(Kind::Skipped, Some(Path::new("a.txt")), "Taken");
+        assert_eq!(
+            r#"{"event":"skip","path":"a.txt","reason":"Taken"}"#,
+            Event::from_record(&skipped).line()
+        );
+        let error = Record::error("Cancelled.");
+        assert_eq!(
+            r#"{"event":"warn","level":"error","message":"Cancelled."}"#,
+            Event::from_record(&error).line()
+        );
+    }
+}
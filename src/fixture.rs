@@ -0,0 +1,199 @@
+// Synthetic trees of tagged files, for benchmarking and for trying out
+// config before using it on real archives. eg 10000 files over 50 tags
+//
+// Tags are drawn either uniformly or, as in most real archives, with a few
+// tags far more common than the rest. A share of names can be malformed,
+// with broken tag blocks such as an unclosed bracket. The same seed gives
+// the same tree.
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::format::TagFormat;
+use crate::fuzz::Rng;
+use crate::NameTag;
+
+const EXTENSIONS: [&str; 4] = [".txt", ".jpg", ".pdf", ".md"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    Uniform,
+    /// The nth most common tag is used 1/n as often as the most common.
+    Zipf,
+}
+
+impl FromStr for Distribution {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "uniform" => Ok(Distribution::Uniform),
+            "zipf" => Ok(Distribution::Zipf),
+            _ => Err("Expected one of uniform or zipf."),
+        }
+    }
+}
+
+/// A share of a whole, as a percentage or fraction. eg 1% or 0.01
+pub fn parse_ratio(text: &str) -> Result<f64, String> {
+    let ratio = match text.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map(|percent| percent / 100.0),
+        None => text.trim().parse::<f64>(),
+    }
+    .map_err(|_| format!("Expected a percentage or fraction, got \"{}\"", text))?;
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err(format!(
+            "Expected a share between 0% and 100%, got \"{}\"",
+            text
+        ));
+    }
+    Ok(ratio)
+}
+
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub files: usize,
+    /// Distinct tags to draw from.
+    pub tags: usize,
+    /// Most tags on a single file.
+    pub tags_per_file: usize,
+    /// Share of names with broken tag blocks.
+    pub malformed: f64,
+    pub distribution: Distribution,
+    /// Files in each directory, files being spread over as many as needed.
+    pub files_per_dir: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Generated {
+    /// Path relative to the root of the tree.
+    pub path: PathBuf,
+    pub malformed: bool,
+}
+
+impl Fixture {
+    /// Paths of the tree's files, in the syntax of the format.
+    pub fn generate(&self, rng: &mut Rng, format: &TagFormat) -> Vec<Generated> {
+        let tags = (1..=self.tags)
+            .map(|index| format!("tag{:0width$}", index, width = digits(self.tags)))
+            .collect::<Vec<_>>();
+        // Running totals of the weight of each tag, to draw from.
+        let mut total = 0.0;
+        let weights = (1..=tags.len())
+            .map(|rank| {
+                total += match self.distribution {
+                    Distribution::Uniform => 1.0,
+                    Distribution::Zipf => 1.0 / rank as f64,
+                };
+                total
+            })
+            .collect::<Vec<_>>();
+        let per_dir = self.files_per_dir.max(1);
+        let dirs = self.files.div_ceil(per_dir);
+        (0..self.files)
+            .map(|index| {
+                let stem = format!("file{:0width$}", index, width = digits(self.files));
+                let extension = EXTENSIONS[rng.below(EXTENSIONS.len())];
+                let mut chosen = Vec::new();
+                if !tags.is_empty() {
+                    for _ in 0..rng.below(self.tags_per_file + 1) {
+                        let point = unit(rng) * total;
+                        let tag = &tags[weights
+                            .partition_point(|weight| *weight <= point)
+                            .min(tags.len() - 1)];
+                        if !chosen.contains(&tag) {
+                            chosen.push(tag);
+                        }
+                    }
+                }
+                let malformed = unit(rng) < self.malformed;
+                let name = if malformed {
+                    let tag = chosen.first().map_or("tag", |tag| tag.as_str());
+                    let (open, close) = (&format.open, &format.close);
+                    match rng.below(3) {
+                        0 => format!("{}{}{}{}", stem, open, tag, extension),
+                        1 => format!("{}{}{}{}{}", stem, close, tag, open, extension),
+                        _ => format!("{}{}{}{}{}{}", stem, open, open, tag, close, extension),
+                    }
+                } else {
+                    let mut nametag =
+                        NameTag::with_format(format!("{}{}", stem, extension), format.clone());
+                    for tag in chosen {
+                        nametag.add_tag(tag.as_str());
+                    }
+                    PathBuf::from(nametag).to_string_lossy().into_owned()
+                };
+                let dir = format!("dir{:0width$}", index / per_dir, width = digits(dirs));
+                Generated {
+                    path: PathBuf::from(dir).join(name),
+                    malformed,
+                }
+            })
+            .collect()
+    }
+}
+
+// A number from 0 up to but excluding 1.
+fn unit(rng: &mut Rng) -> f64 {
+    (rng.next() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Digits needed to write numbers below count, so names sort in order.
+fn digits(count: usize) -> usize {
+    count.saturating_sub(1).to_string().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Extension;
+    use std::collections::BTreeMap;
+
+    fn fixture(distribution: Distribution, malformed: f64) -> Fixture {
+        Fixture {
+            files: 2000,
+            tags: 20,
+            tags_per_file: 3,
+            malformed,
+            distribution,
+            files_per_dir: 100,
+        }
+    }
+
+    #[test]
+    fn test_parse_ratio() {
+        assert_eq!(Ok(0.01), parse_ratio("1%"));
+        assert_eq!(Ok(0.25), parse_ratio("0.25"));
+        assert!(parse_ratio("150%").is_err());
+        assert!(parse_ratio("some").is_err());
+    }
+    #[test]
+    fn test_generate() {
+        let format = TagFormat::default();
+        let files = fixture(Distribution::Zipf, 0.1).generate(&mut Rng::new(3), &format);
+        assert_eq!(2000, files.len());
+        assert_eq!(PathBuf::from("dir00"), files[0].path.parent().unwrap());
+        assert_eq!(PathBuf::from("dir19"), files[1999].path.parent().unwrap());
+        let malformed = files.iter().filter(|file| file.malformed).count();
+        assert!((100..300).contains(&malformed), "{} malformed", malformed);
+        // The most common tag is used far more than the least.
+        let mut usage = BTreeMap::new();
+        for file in files.iter().filter(|file| !file.malformed) {
+            for tag in
+                NameTag::from_path_with_format(&file.path, Extension::FirstDot, format.clone())
+                    .get_tags()
+            {
+                *usage.entry(tag.to_string_lossy().into_owned()).or_insert(0) += 1;
+            }
+        }
+        assert!(usage["tag01"] > usage["tag20"] * 5);
+    }
+    #[test]
+    fn test_generate_repeats() {
+        let format = TagFormat::default();
+        let fixture = fixture(Distribution::Uniform, 0.01);
+        assert_eq!(
+            fixture.generate(&mut Rng::new(9), &format),
+            fixture.generate(&mut Rng::new(9), &format)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+// The syntax tags are written in within a name.
+// eg brackets: name[a b].txt, braces: name{a;b}.txt, suffix: name -- a b.txt
+// and dual, with a second block for value tags: name[a b]{k=v}.txt
+//
+// Schemes other than the built in ones are defined in config.
+// eg [formats.pipes] open = "|", close = "|", separator = "+"
+//
+// Spacing around tags follows existing names, with pad and space_before
+// setting the style of names tagged for the first time.
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Where tags are inserted in a name that has none.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Position {
+    /// eg name[a].txt
+    #[default]
+    BeforeExtension,
+    /// eg name.txt[a]
+    End,
+    /// eg [a]name.txt
+    Prefix,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagFormat {
+    /// Text starting the tags. Must not be empty.
+    pub open: String,
+    /// Text ending the tags. When empty, tags run up to the extension, or to
+    /// the end of the name when positioned there.
+    pub close: String,
+    /// Written between tags. Its characters, as well as whitespace and commas,
+    /// separate tags when reading.
+    pub separator: String,
+    pub position: Position,
+    /// Pad tags with a space inside their delimiters in new names. eg [ a b ]
+    pub pad: bool,
+    /// Put a space before tags in new names. eg name [a].txt
+    pub space_before: bool,
+    /// A second block following the first, holding value tags.
+    /// eg file[photo travel]{sha=abc,seq=004}.jpg
+    pub machine: Option<Box<TagFormat>>,
+    /// Further bytes separating tags, being the delimiters of the other block
+    /// when there are two, so neither block can hold the other's.
+    #[serde(skip)]
+    pub(crate) reserved: Vec<u8>,
+}
+
+impl Default for TagFormat {
+    fn default() -> Self {
+        Self {
+            open: "[".to_owned(),
+            close: "]".to_owned(),
+            separator: " ".to_owned(),
+            position: Position::BeforeExtension,
+            pad: false,
+            space_before: false,
+            machine: None,
+            reserved: Vec::new(),
+        }
+    }
+}
+
+impl TagFormat {
+    /// A built in scheme: brackets, braces, suffix or dual.
+    pub fn named(name: &str) -> Option<Self> {
+        let (open, close, separator) = match name {
+            "brackets" => return Some(Self::default()),
+            "dual" => {
+                return Some(Self {
+                    machine: Some(Box::new(Self {
+                        open: "{".to_owned(),
+                        close: "}".to_owned(),
+                        separator: ",".to_owned(),
+                        ..Self::default()
+                    })),
+                    ..Self::default()
+                })
+            }
+            "braces" => ("{", "}", ";"),
+            "suffix" => (" -- ", "", " "),
+            _ => return None,
+        };
+        Some(Self {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            separator: separator.to_owned(),
+            ..Self::default()
+        })
+    }
+
+    // Whether a byte separates tags. Single character delimiters count too,
+    // so nested brackets are flattened. eg [a [b]] -> a b
+    pub(crate) fn is_separator(&self, byte: u8) -> bool {
+        let delimiter = |text: &str| text.len() == 1 && text.as_bytes()[0] == byte;
+        byte.is_ascii_whitespace()
+            || byte == b','
+            || (byte.is_ascii() && self.separator.contains(byte as char))
+            || delimiter(&self.open)
+            || delimiter(&self.close)
+            || self.reserved.contains(&byte)
+    }
+
+    // Single character delimiters, which separate tags within other blocks.
+    pub(crate) fn delimiters(&self) -> Vec<u8> {
+        [&self.open, &self.close]
+            .iter()
+            .filter(|text| text.len() == 1)
+            .map(|text| text.as_bytes()[0])
+            .collect()
+    }
+}
+
+// A built in scheme, or delimiters around "..". eg braces, {..}
+impl FromStr for TagFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Some(format) = Self::named(text) {
+            return Ok(format);
+        }
+        match text.split_once("..") {
+            Some((open, close)) if !open.is_empty() => Ok(Self {
+                open: open.to_owned(),
+                close: close.to_owned(),
+                ..Self::default()
+            }),
+            _ => Err(format!(
+                "Expected brackets, braces, suffix, dual or delimiters around \"..\", got \"{}\"",
+                text
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named() {
+        assert_eq!(Some(TagFormat::default()), TagFormat::named("brackets"));
+        assert_eq!(";", TagFormat::named("braces").unwrap().separator);
+        assert_eq!(None, TagFormat::named("parens"));
+    }
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Ok(TagFormat::default()), "[..]".parse());
+        let format = "<<..>>".parse::<TagFormat>().unwrap();
+        assert_eq!(("<<", ">>"), (format.open.as_str(), format.close.as_str()));
+        assert_eq!(Ok(TagFormat::named("braces").unwrap()), "braces".parse());
+        assert!("..]".parse::<TagFormat>().is_err());
+        assert!("none".parse::<TagFormat>().is_err());
+    }
+    #[test]
+    fn test_is_separator() {
+        let format = TagFormat::named("braces").unwrap();
+        assert!(format.is_separator(b';'));
+        assert!(format.is_separator(b'{'));
+        assert!(format.is_separator(b' '));
+        assert!(!format.is_separator(b'['));
+        let format = TagFormat::named("suffix").unwrap();
+        assert!(!format.is_separator(b'-'));
+    }
+}
@@ -0,0 +1,157 @@
+// Randomized checking that names keep their tags when written back out.
+// eg parsing "a[x [y]].txt" then rendering and parsing again gives the same tags
+//
+// Names are built from fragments likely to confuse a parser: delimiters of
+// the format in use, stray brackets, odd whitespace and bytes that are not utf8.
+use std::ffi::OsString;
+
+use crate::format::TagFormat;
+use crate::NameTag;
+
+/// A small xorshift generator, so runs can be repeated from their seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Zero would only ever generate zero.
+        Self(seed.max(1))
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+/// A name that changed when rendered and parsed again.
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub name: Vec<u8>,
+    pub rendered: Vec<u8>,
+    pub rerendered: Vec<u8>,
+}
+
+/// A name made of random fragments.
+pub fn generate(rng: &mut Rng, format: &TagFormat) -> Vec<u8> {
+    let fragments: [&[u8]; 16] = [
+        b"name",
+        b"tag",
+        b".",
+        b".txt",
+        b" ",
+        b"  ",
+        b"\t",
+        b",",
+        b"[",
+        b"]",
+        b"{",
+        b"}",
+        b"=",
+        b"\xff",
+        b"\xc3",
+        "é".as_bytes(),
+    ];
+    let delimiters = [
+        format.open.as_bytes(),
+        format.close.as_bytes(),
+        format.separator.as_bytes(),
+    ];
+    let mut name = Vec::new();
+    for _ in 0..1 + rng.below(12) {
+        let index = rng.below(fragments.len() + delimiters.len());
+        match fragments.get(index) {
+            Some(fragment) => name.extend_from_slice(fragment),
+            None => name.extend_from_slice(delimiters[index - fragments.len()]),
+        }
+    }
+    name
+}
+
+fn os_string(bytes: &[u8]) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        OsString::from_vec(bytes.to_vec())
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+fn render(name: &[u8], format: &TagFormat) -> (Vec<u8>, Vec<OsString>) {
+    let nametag = NameTag::with_format(os_string(name), format.clone());
+    let tags = nametag.get_tags().cloned().collect();
+    (nametag.into(), tags)
+}
+
+/// Check a name renders to a form that parses back to the same tags and
+/// renders unchanged.
+pub fn check(name: &[u8], format: &TagFormat) -> Option<Failure> {
+    let (rendered, tags) = render(name, format);
+    let (rerendered, retags) = render(&rendered, format);
+    if rendered == rerendered && tags == retags {
+        return None;
+    }
+    Some(Failure {
+        name: name.to_vec(),
+        rendered,
+        rerendered,
+    })
+}
+
+/// The shortest failing name found by removing bytes from a failing one.
+pub fn minimize(failure: Failure, format: &TagFormat) -> Failure {
+    let mut failure = failure;
+    let mut index = 0;
+    while index < failure.name.len() {
+        let mut name = failure.name.clone();
+        name.remove(index);
+        match check(&name, format) {
+            Some(smaller) => {
+                failure = smaller;
+                index = 0;
+            }
+            None => index += 1,
+        }
+    }
+    failure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_repeats() {
+        let format = TagFormat::default();
+        let first = generate(&mut Rng::new(7), &format);
+        assert_eq!(first, generate(&mut Rng::new(7), &format));
+        assert!(!first.is_empty());
+    }
+    #[test]
+    fn test_check() {
+        let format = TagFormat::default();
+        assert_eq!(None, check(b"a[y x].txt", &format));
+        assert_eq!(None, check(b"a[x [y]].txt", &format));
+    }
+    #[test]
+    fn test_default_format_is_stable() {
+        for format in ["brackets", "braces", "suffix", "dual"] {
+            let format = TagFormat::named(format).unwrap();
+            let mut rng = Rng::new(1);
+            for _ in 0..2000 {
+                let name = generate(&mut rng, &format);
+                if let Some(failure) = check(&name, &format) {
+                    panic!("{:?}", minimize(failure, &format));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// The network of tags used together, for Graphviz or Gephi to draw, so the
+// way tags actually cluster can be set against the ontology's categories.
+// Each tag is a node weighted by the files carrying it, and each pair of
+// tags on a file an edge weighted by the files carrying both.
+// eg "beach" -- "family" [weight=3];
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::ontology::Ontology;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz. eg dot -Tsvg
+    Dot,
+    /// Gephi's xml format.
+    Gexf,
+}
+
+impl FromStr for GraphFormat {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "dot" => Ok(GraphFormat::Dot),
+            "gexf" => Ok(GraphFormat::Gexf),
+            _ => Err("Expected one of dot or gexf."),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Graph {
+    /// Files carrying each tag.
+    nodes: BTreeMap<String, usize>,
+    /// Files carrying each pair of tags, the lesser tag first.
+    edges: BTreeMap<(String, String), usize>,
+}
+
+impl Graph {
+    /// Count the tags of one file.
+    pub fn add_file(&mut self, tags: &[String]) {
+        let mut tags = tags.to_vec();
+        tags.sort();
+        tags.dedup();
+        for (index, tag) in tags.iter().enumerate() {
+            *self.nodes.entry(tag.clone()).or_insert(0) += 1;
+            for other in &tags[index + 1..] {
+                *self.edges.entry((tag.clone(), other.clone())).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// The graph as a file in a format, with each tag's category from the
+    /// ontology where it has one.
+    pub fn render(&self, format: GraphFormat, ontology: &Ontology) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(ontology),
+            GraphFormat::Gexf => self.gexf(ontology),
+        }
+    }
+
+    fn dot(&self, ontology: &Ontology) -> String {
+        let mut out = "graph tags {\n".to_owned();
+        for (tag, files) in &self.nodes {
+            out += &format!("  {} [weight={}", dot_quote(tag), files);
+            if let Some(category) = category(ontology, tag) {
+                out += &format!(", category={}", dot_quote(category));
+            }
+            out += "];\n";
+        }
+        for ((a, b), files) in &self.edges {
+            out += &format!(
+                "  {} -- {} [weight={}];\n",
+                dot_quote(a),
+                dot_quote(b),
+                files
+            );
+        }
+        out + "}\n"
+    }
+
+    fn gexf(&self, ontology: &Ontology) -> String {
+        let ids = self
+            .nodes
+            .keys()
+            .enumerate()
+            .map(|(index, tag)| (tag, index))
+            .collect::<BTreeMap<_, _>>();
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n  \
+             <graph defaultedgetype=\"undirected\">\n    \
+             <attributes class=\"node\">\n      \
+             <attribute id=\"files\" title=\"files\" type=\"integer\"/>\n      \
+             <attribute id=\"category\" title=\"category\" type=\"string\"/>\n    \
+             </attributes>\n    <nodes>\n",
+        );
+        for (tag, files) in &self.nodes {
+            out += &format!(
+                "      <node id=\"{}\" label=\"{}\"><attvalues><attvalue for=\"files\" value=\"{}\"/>",
+                ids[tag],
+                xml_escape(tag),
+                files
+            );
+            if let Some(category) = category(ontology, tag) {
+                out += &format!(
+                    "<attvalue for=\"category\" value=\"{}\"/>",
+                    xml_escape(category)
+                );
+            }
+            out += "</attvalues></node>\n";
+        }
+        out += "    </nodes>\n    <edges>\n";
+        for (index, ((a, b), files)) in self.edges.iter().enumerate() {
+            out += &format!(
+                "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"/>\n",
+                index, ids[a], ids[b], files
+            );
+        }
+        out + "    </edges>\n  </graph>\n</gexf>\n"
+    }
+}
+
+fn category<'a>(ontology: &'a Ontology, tag: &str) -> Option<&'a str> {
+    ontology.get(tag)?.category.as_deref()
+}
+
+// An ID in dot, quoted as tags may hold any character.
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> Graph {
+        let mut graph = Graph::default();
+        for tags in [&["beach", "family"][..], &["family", "beach"], &["a\"b"]] {
+            graph.add_file(&tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>());
+        }
+        graph
+    }
+
+    #[test]
+    fn test_dot() {
+        let ontology = "[tag.beach]\ncategory = \"travel\"\n"
+            .parse::<Ontology>()
+            .unwrap();
+        assert_eq!(
+            "graph tags {\n  \"a\\\"b\" [weight=1];\n  \"beach\" [weight=2, category=\"travel\"];\n  \
+             \"family\" [weight=2];\n  \"beach\" -- \"family\" [weight=2];\n}\n",
+            graph().render(GraphFormat::Dot, &ontology)
+        );
+    }
+    #[test]
+    fn test_gexf() {
+        let gexf = graph().render(GraphFormat::Gexf, &Ontology::default());
+        assert!(gexf.contains("<node id=\"0\" label=\"a&quot;b\">"));
+        assert!(gexf.contains("<edge id=\"0\" source=\"1\" target=\"2\" weight=\"2\"/>"));
+        assert!(gexf.ends_with("</gexf>\n"));
+    }
+}
@@ -0,0 +1,57 @@
+// Find tagged file names within arbitrary text, such as logs or exported
+// manifests. eg 'copied "/srv/a[tax 2019].pdf" ok' -> /srv/a[tax 2019].pdf
+//
+// A name is found around a bracketed group of tags, running outwards until
+// whitespace or punctuation that commonly surrounds paths. Names containing
+// spaces outside their tags are therefore only partly found.
+
+// Characters ending a name on either side.
+fn is_boundary(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '"' | '\'' | '`' | ',' | ';' | '|' | '<' | '>' | '=')
+}
+
+/// Tagged names within a line of text, in the order they appear.
+pub fn names(line: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(open) = line[from..].find('[').map(|index| from + index) {
+        let close = match line[open..].find(']') {
+            Some(index) => open + index,
+            None => break,
+        };
+        let tags = &line[open + 1..close];
+        // Something must come before the tags, so log levels like "[INFO]"
+        // and empty brackets are passed over.
+        let start = line[..open].rfind(is_boundary).map_or(0, |index| {
+            index + line[index..].chars().next().map_or(1, char::len_utf8)
+        });
+        if start == open || tags.trim().is_empty() || tags.contains('[') {
+            from = open + 1;
+            continue;
+        }
+        let end = line[close..]
+            .find(is_boundary)
+            .map_or(line.len(), |index| close + index);
+        found.push(&line[start..end]);
+        from = end;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            vec!["/srv/a[tax 2019].pdf"],
+            names("2020-01-01 [INFO] copied \"/srv/a[tax 2019].pdf\" ok")
+        );
+        assert_eq!(
+            vec!["a[x].txt", "dir/b[y,z]"],
+            names("a[x].txt,dir/b[y,z],c.txt")
+        );
+        assert_eq!(Vec::<&str>::new(), names("list[] of [things] [unclosed"));
+    }
+}
@@ -0,0 +1,108 @@
+// Directories too broad to change recursively, so a mistyped path does not
+// retag a whole disk. eg nametag maintain /
+//
+// Filesystem roots and the home directory are always refused, as are
+// directories shallower than a configured depth, which keeps mount points
+// such as /media/disk out of reach. --allow-root goes ahead regardless.
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, PartialEq)]
+pub enum Refusal {
+    FilesystemRoot(PathBuf),
+    Home(PathBuf),
+    Shallow(PathBuf, usize),
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refusal::FilesystemRoot(path) => {
+                write!(
+                    f,
+                    "Refusing to work recursively on the filesystem root {}",
+                    path.display()
+                )
+            }
+            Refusal::Home(path) => {
+                write!(
+                    f,
+                    "Refusing to work recursively on the home directory {}",
+                    path.display()
+                )
+            }
+            Refusal::Shallow(path, min_depth) => write!(
+                f,
+                "Refusing to work recursively on {}, less than {} directories deep",
+                path.display(),
+                min_depth
+            ),
+        }?;
+        write!(f, ". Use --allow-root if this is intended.")
+    }
+}
+
+impl std::error::Error for Refusal {}
+
+#[derive(Debug, Default, Clone)]
+pub struct RootGuard {
+    min_depth: usize,
+    home: Option<PathBuf>,
+}
+
+impl RootGuard {
+    /// Directories less than min_depth below the filesystem root are refused,
+    /// as are the roots themselves and home.
+    pub fn new(min_depth: usize, home: Option<PathBuf>) -> Self {
+        Self { min_depth, home }
+    }
+
+    /// Check a directory about to be walked. It should be absolute, with
+    /// links resolved, for the checks to be meaningful.
+    pub fn check(&self, dir: &Path) -> Result<(), Refusal> {
+        let depth = dir
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .count();
+        if depth == 0 {
+            return Err(Refusal::FilesystemRoot(dir.to_path_buf()));
+        }
+        if self.home.as_deref() == Some(dir) {
+            return Err(Refusal::Home(dir.to_path_buf()));
+        }
+        if depth < self.min_depth {
+            return Err(Refusal::Shallow(dir.to_path_buf(), self.min_depth));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let guard = RootGuard::new(2, Some(PathBuf::from("/home/me")));
+        assert_eq!(
+            Err(Refusal::FilesystemRoot(PathBuf::from("/"))),
+            guard.check(Path::new("/"))
+        );
+        assert_eq!(
+            Err(Refusal::Home(PathBuf::from("/home/me"))),
+            guard.check(Path::new("/home/me"))
+        );
+        assert_eq!(
+            Err(Refusal::Shallow(PathBuf::from("/media"), 2)),
+            guard.check(Path::new("/media"))
+        );
+        assert_eq!(Ok(()), guard.check(Path::new("/media/disk")));
+        assert_eq!(Ok(()), guard.check(Path::new("/home/me/photos")));
+    }
+    #[cfg(windows)]
+    #[test]
+    fn test_check_drive() {
+        let guard = RootGuard::default();
+        assert!(guard.check(Path::new("C:\\")).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+// Translations of the messages nametag shows, gettext style. A message is
+// looked up by its English text, which is shown as is when untranslated.
+// Translations live beside the config, one file per language.
+// eg ~/.config/nametag/locale/de.toml
+// "{} already exists" = "{} existiert bereits"
+//
+// Placeholders {} are filled in order. A translation with a different
+// number of them than its message is ignored, rather than losing details.
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::ConfigError;
+
+#[derive(Debug, Default)]
+pub struct Catalog {
+    messages: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    /// Translations for a language from a directory of them, trying the
+    /// language with its region then without. eg pt_BR.toml then pt.toml
+    /// Without either, messages are left in English.
+    pub fn load(dir: &Path, language: &str) -> Result<Self, ConfigError> {
+        let mut names = vec![language];
+        if let Some((base, _)) = language.split_once('_') {
+            names.push(base);
+        }
+        for name in names {
+            let path = dir.join(name).with_extension("toml");
+            match fs::read_to_string(&path) {
+                Ok(data) => {
+                    let messages =
+                        toml::from_str(&data).map_err(|err| ConfigError::Parse(path, err))?;
+                    return Ok(Self { messages });
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(ConfigError::Io(path, err)),
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// A message in the catalog's language.
+    pub fn translate<'a>(&'a self, message: &'a str) -> &'a str {
+        match self.messages.get(message) {
+            Some(translation) if placeholders(translation) == placeholders(message) => translation,
+            _ => message,
+        }
+    }
+
+    /// A message in the catalog's language, with its placeholders filled.
+    pub fn format(&self, message: &str, args: &[&dyn fmt::Display]) -> String {
+        let mut args = args.iter();
+        let mut parts = self.translate(message).split("{}");
+        let mut out = parts.next().unwrap_or_default().to_owned();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                out += &arg.to_string();
+            }
+            out += part;
+        }
+        out
+    }
+}
+
+fn placeholders(message: &str) -> usize {
+    message.matches("{}").count()
+}
+
+/// The language messages are wanted in, from $LC_ALL, $LC_MESSAGES or $LANG.
+/// eg de_DE.UTF-8 is de_DE. None for the C locale.
+pub fn language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            let end = value.find(['.', '@']).unwrap_or(value.len());
+            value[..end].to_owned()
+        })
+        .filter(|language| language != "C" && language != "POSIX")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::default();
+        for (message, translation) in [
+            ("{} already exists", "{} existiert bereits"),
+            ("Renamed {} files.", "Umbenannt."),
+        ] {
+            catalog
+                .messages
+                .insert(message.to_owned(), translation.to_owned());
+        }
+        catalog
+    }
+
+    #[test]
+    fn test_format() {
+        let catalog = catalog();
+        assert_eq!(
+            "a.txt existiert bereits",
+            catalog.format("{} already exists", &[&"a.txt"])
+        );
+        assert_eq!("Untranslated 2", catalog.format("Untranslated {}", &[&2]));
+        // The placeholder would be lost.
+        assert_eq!(
+            "Renamed 3 files.",
+            catalog.format("Renamed {} files.", &[&3])
+        );
+    }
+    #[test]
+    fn test_load() {
+        let dir = env::temp_dir().join("nametag-i18n");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("de.toml"), "\"Nothing\" = \"Nichts\"\n").unwrap();
+        let catalog = Catalog::load(&dir, "de_AT").unwrap();
+        assert_eq!("Nichts", catalog.translate("Nothing"));
+        let catalog = Catalog::load(&dir, "fr").unwrap();
+        assert_eq!("Nothing", catalog.translate("Nothing"));
+    }
+}
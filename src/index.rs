@@ -0,0 +1,734 @@
+// A persistent index of the tags on every file under a directory, so large
+// trees can be queried without walking them.
+// eg ~/.local/state/nametag/indexes/<hash of root>.json
+//
+// Tags live in names, and renaming a file changes the modification time of
+// its directory, so an update only reads directories whose time changed.
+// Names that are not valid unicode are left out.
+//
+// Searches look tags up by the text before any wildcard rather than testing
+// every tag, eg project/* reads only tags starting project/, and the terms
+// of an AND are checked the fewest files first. Names are searched through
+// the runs of three characters in them, so only files holding every run of
+// the text are read. eg name:~acme reads files with both "acm" and "cme"
+//
+// Builds save the directories read so far to a checkpoint beside the index
+// every so often and when cancelled, so a build interrupted on a large tree
+// picks up where it stopped instead of starting over.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cancel::Cancel;
+use crate::config::state_dir;
+use crate::format::TagFormat;
+use crate::query::Query;
+use crate::{Extension, NameTag};
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    /// Directory that was indexed.
+    pub root: PathBuf,
+    /// Relative path of each directory to what was found in it.
+    dirs: BTreeMap<PathBuf, Dir>,
+    /// Each tag to the relative paths of files carrying it.
+    tags: BTreeMap<String, BTreeSet<PathBuf>>,
+    /// Each run of three characters, lowercase, to the relative paths of
+    /// files whose names without tags hold it.
+    #[serde(default)]
+    grams: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct Dir {
+    /// Seconds and nanoseconds since the epoch.
+    modified: (u64, u32),
+    /// File names to their tags.
+    files: BTreeMap<String, Vec<String>>,
+    /// Names of tagged files to the names without their tags.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    untagged: BTreeMap<String, String>,
+    /// Names of subdirectories.
+    dirs: Vec<String>,
+}
+
+// How often a resumable build saves its checkpoint.
+const CHECKPOINT_EVERY: Duration = Duration::from_secs(30);
+
+// Directories found while scanning: the relative path, what was found in
+// it, and whether it was read rather than reused.
+type Found = (PathBuf, Dir, bool);
+
+// Files carrying each tag, by the tag queries know it as. eg an obfuscated
+// tag by its original
+type Names<'a> = BTreeMap<String, Vec<&'a BTreeSet<PathBuf>>>;
+
+// How names are read into tags
+#[derive(Clone, Copy)]
+struct Reader<'a> {
+    format: &'a TagFormat,
+    extension: Extension,
+    cancel: &'a Cancel,
+}
+
+impl Index {
+    /// Index every file under a directory, until cancelled.
+    pub fn build(
+        root: &Path,
+        format: &TagFormat,
+        extension: Extension,
+        cancel: &Cancel,
+    ) -> io::Result<Self> {
+        let mut index = Self {
+            root: fs::canonicalize(root)?,
+            ..Self::default()
+        };
+        index.update(format, extension, cancel)?;
+        Ok(index)
+    }
+
+    /// As build, saving the directories read so far to a checkpoint every
+    /// so often and when cancelled. A build interrupted, even by a crash,
+    /// resumes from its checkpoint, reading again only the directories not
+    /// yet read or changed since. The checkpoint is removed once done.
+    pub fn build_resumable(
+        root: &Path,
+        format: &TagFormat,
+        extension: Extension,
+        cancel: &Cancel,
+        checkpoint: &Path,
+    ) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        // A checkpoint torn by a crash is started over.
+        let mut index = match Self::load(checkpoint) {
+            Ok(index) if index.root == root => index,
+            _ => Self {
+                root,
+                ..Self::default()
+            },
+        };
+        let reader = Reader {
+            format,
+            extension,
+            cancel,
+        };
+        index.scan(reader, Some(checkpoint))?;
+        match fs::remove_file(checkpoint) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(index),
+        }
+    }
+
+    /// Where the checkpoint of a resumable build of a directory is kept.
+    pub fn checkpoint_path(root: &Path) -> Option<PathBuf> {
+        Some(Self::default_path(root)?.with_extension("checkpoint.json"))
+    }
+
+    /// Read again the directories changed since the index was built or last
+    /// updated, returning how many were read. Once cancelled, the index is
+    /// left as it was.
+    pub fn update(
+        &mut self,
+        format: &TagFormat,
+        extension: Extension,
+        cancel: &Cancel,
+    ) -> io::Result<usize> {
+        let reader = Reader {
+            format,
+            extension,
+            cancel,
+        };
+        self.scan(reader, None)
+    }
+
+    // As update, saving to a checkpoint if given.
+    fn scan(&mut self, reader: Reader<'_>, checkpoint: Option<&Path>) -> io::Result<usize> {
+        let mut dirs = BTreeMap::new();
+        let mut read = 0;
+        if let Err(err) = self.scan_into(reader, checkpoint, &mut dirs, &mut read) {
+            if let Some(path) = checkpoint {
+                self.save_checkpoint(&dirs, path)?;
+            }
+            return Err(err);
+        }
+        self.dirs = dirs;
+        self.tags.clear();
+        self.grams.clear();
+        for (relative, dir) in &self.dirs {
+            for (name, tags) in &dir.files {
+                for tag in tags {
+                    self.tags
+                        .entry(tag.clone())
+                        .or_default()
+                        .insert(relative.join(name));
+                }
+                let untagged = dir.untagged.get(name).unwrap_or(name);
+                for gram in grams(&untagged.to_lowercase()) {
+                    self.grams
+                        .entry(gram)
+                        .or_default()
+                        .insert(relative.join(name));
+                }
+            }
+        }
+        Ok(read)
+    }
+
+    fn scan_into(
+        &self,
+        reader: Reader<'_>,
+        checkpoint: Option<&Path>,
+        dirs: &mut BTreeMap<PathBuf, Dir>,
+        read: &mut usize,
+    ) -> io::Result<()> {
+        let previous = &self.dirs;
+        let root = scan_dir(&self.root, Path::new(""), previous, reader)?;
+        *read += usize::from(root.1);
+        let subdirs = root.0.dirs.iter().map(PathBuf::from).collect::<Vec<_>>();
+        dirs.insert(PathBuf::new(), root.0);
+        // Each thread walks whole subtrees of the root, sending back each
+        // directory as it is done.
+        let threads = thread::available_parallelism().map_or(1, usize::from);
+        let chunk = subdirs.len().div_ceil(threads).max(1);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            let handles = subdirs
+                .chunks(chunk)
+                .map(|chunk| {
+                    let root = &self.root;
+                    let sender = sender.clone();
+                    scope.spawn(move || {
+                        chunk.iter().try_for_each(|relative| {
+                            scan_tree(root, relative, previous, reader, &sender)
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            drop(sender);
+            let mut saved = Instant::now();
+            for (relative, dir, changed) in receiver {
+                *read += usize::from(changed);
+                dirs.insert(relative, dir);
+                if let Some(path) = checkpoint {
+                    if saved.elapsed() >= CHECKPOINT_EVERY {
+                        self.save_checkpoint(dirs, path)?;
+                        saved = Instant::now();
+                    }
+                }
+            }
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("Indexing thread panicked")))
+            })
+        })
+    }
+
+    // Save the directories read so far, along with those from before not
+    // yet read again, to resume from.
+    fn save_checkpoint(&self, dirs: &BTreeMap<PathBuf, Dir>, path: &Path) -> io::Result<()> {
+        let mut saved = self.dirs.clone();
+        saved.extend(
+            dirs.iter()
+                .map(|(relative, dir)| (relative.clone(), dir.clone())),
+        );
+        Self {
+            root: self.root.clone(),
+            dirs: saved,
+            ..Self::default()
+        }
+        .save(path)
+    }
+
+    /// Number of files indexed.
+    pub fn len(&self) -> usize {
+        self.dirs.values().map(|dir| dir.files.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Full paths of files whose tags satisfy a query. Tags are matched by
+    /// what reveal gives for them, if anything. eg the original of an obfuscated tag
+    pub fn search<F: Fn(&str) -> Option<String>>(&self, query: &Query, reveal: F) -> Vec<PathBuf> {
+        let mut names = Names::new();
+        for (tag, paths) in &self.tags {
+            names
+                .entry(reveal(tag).unwrap_or_else(|| tag.clone()))
+                .or_default()
+                .push(paths);
+        }
+        self.evaluate(query, &names)
+            .into_iter()
+            .map(|path| self.root.join(path))
+            .collect()
+    }
+
+    fn evaluate(&self, query: &Query, names: &Names<'_>) -> BTreeSet<PathBuf> {
+        match query {
+            Query::Name(text) => {
+                let text = text.to_lowercase();
+                self.candidates(&text)
+                    .into_iter()
+                    .filter(|path| self.has_name(path, &text))
+                    .collect()
+            }
+            Query::Not(query) => {
+                let excluded = self.evaluate(query, names);
+                self.files()
+                    .filter(|path| !excluded.contains(path))
+                    .collect()
+            }
+            // Files are found for the fewest first, then checked against the
+            // other terms, the most selective first.
+            Query::And(..) => {
+                let mut operands = query
+                    .operands()
+                    .into_iter()
+                    .map(|query| (self.estimate(query, names), query))
+                    .collect::<Vec<_>>();
+                operands.sort_by_key(|(estimate, _)| *estimate);
+                let mut found = self.evaluate(operands[0].1, names);
+                found.retain(|path| {
+                    operands[1..]
+                        .iter()
+                        .all(|(_, query)| self.holds(query, names, path))
+                });
+                found
+            }
+            Query::Or(..) => query
+                .operands()
+                .into_iter()
+                .flat_map(|query| self.evaluate(query, names))
+                .collect(),
+            term => matching(term, names)
+                .flat_map(|paths| paths.iter().cloned())
+                .collect(),
+        }
+    }
+
+    // Most files a query could match, from the number carrying each tag.
+    fn estimate(&self, query: &Query, names: &Names<'_>) -> usize {
+        match query {
+            Query::Name(text) => match self.grams.is_empty() {
+                true => self.len(),
+                false => grams(&text.to_lowercase())
+                    .iter()
+                    .map(|gram| self.grams.get(gram).map_or(0, BTreeSet::len))
+                    .min()
+                    .unwrap_or_else(|| self.len()),
+            },
+            Query::Not(query) => self.len().saturating_sub(self.estimate(query, names)),
+            Query::And(..) => query
+                .operands()
+                .into_iter()
+                .map(|query| self.estimate(query, names))
+                .min()
+                .unwrap_or(0),
+            Query::Or(..) => query
+                .operands()
+                .into_iter()
+                .map(|query| self.estimate(query, names))
+                .sum::<usize>()
+                .min(self.len()),
+            term => matching(term, names).map(BTreeSet::len).sum(),
+        }
+    }
+
+    // Whether one file satisfies a query.
+    fn holds(&self, query: &Query, names: &Names<'_>, path: &Path) -> bool {
+        match query {
+            Query::Name(text) => self.has_name(path, &text.to_lowercase()),
+            Query::Not(query) => !self.holds(query, names, path),
+            Query::And(..) => query
+                .operands()
+                .into_iter()
+                .all(|query| self.holds(query, names, path)),
+            Query::Or(..) => query
+                .operands()
+                .into_iter()
+                .any(|query| self.holds(query, names, path)),
+            term => matching(term, names).any(|paths| paths.contains(path)),
+        }
+    }
+
+    // Files whose names may hold lowercase text, being those holding each of
+    // its runs of three characters. Every file, for shorter text or an index
+    // built before names were.
+    fn candidates(&self, text: &str) -> Vec<PathBuf> {
+        let runs = grams(text);
+        if runs.is_empty() || self.grams.is_empty() {
+            return self.files().collect();
+        }
+        let mut sets = runs
+            .iter()
+            .map(|gram| self.grams.get(gram))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        sets.sort_by_key(|paths| paths.len());
+        match sets.split_first() {
+            Some((first, rest)) => first
+                .iter()
+                .filter(|path| rest.iter().all(|paths| paths.contains(*path)))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Whether the name of a file without its tags holds lowercase text.
+    fn has_name(&self, path: &Path, text: &str) -> bool {
+        let dir = path.parent().and_then(|parent| self.dirs.get(parent));
+        let name = path.file_name().and_then(|name| name.to_str());
+        match (dir, name) {
+            (Some(dir), Some(name)) if dir.files.contains_key(name) => dir
+                .untagged
+                .get(name)
+                .map_or(name, String::as_str)
+                .to_lowercase()
+                .contains(text),
+            _ => false,
+        }
+    }
+
+    // Relative paths of every file
+    fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.dirs
+            .iter()
+            .flat_map(|(relative, dir)| dir.files.keys().map(move |name| relative.join(name)))
+    }
+
+    /// Where the index of a directory is stored.
+    pub fn default_path(root: &Path) -> Option<PathBuf> {
+        let hash = Sha256::digest(root.as_os_str().as_encoded_bytes());
+        let name = hash[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        Some(state_dir()?.join("indexes").join(format!("{}.json", name)))
+    }
+
+    /// The index covering a path, being the index of the path itself or of
+    /// the nearest directory above it that has one.
+    pub fn find(path: &Path) -> io::Result<Option<Self>> {
+        let path = fs::canonicalize(path)?;
+        for dir in path.ancestors() {
+            let index_path = match Self::default_path(dir) {
+                Some(index_path) => index_path,
+                None => return Ok(None),
+            };
+            if index_path.exists() {
+                return Self::load(&index_path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec(self)?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+}
+
+// Runs of three characters in text. eg acme -> acm, cme
+fn grams(text: &str) -> BTreeSet<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    chars.windows(3).map(|run| run.iter().collect()).collect()
+}
+
+// Files carrying each tag a term matches, looking only at tags starting
+// with the text every match must start with.
+fn matching<'a>(
+    term: &'a Query,
+    names: &'a Names<'_>,
+) -> impl Iterator<Item = &'a BTreeSet<PathBuf>> + 'a {
+    let prefix = match term {
+        Query::Tag(pattern) => pattern
+            .find(['*', '?'])
+            .map_or(pattern.as_str(), |wildcard| &pattern[..wildcard])
+            .to_owned(),
+        Query::Exact(tag) => tag.clone(),
+        Query::Value(key, _) | Query::Compare(key, ..) | Query::Range(key, ..) => {
+            format!("{}=", key)
+        }
+        _ => String::new(),
+    };
+    names
+        .range::<str, _>((Bound::Included(prefix.as_str()), Bound::Unbounded))
+        .take_while(move |(name, _)| name.starts_with(&prefix))
+        .filter(move |(name, _)| term.matches_tag(name))
+        .flat_map(|(_, paths)| paths.iter().copied())
+}
+
+// A directory and those below it, appending what was found.
+fn scan_tree(
+    root: &Path,
+    relative: &Path,
+    previous: &BTreeMap<PathBuf, Dir>,
+    reader: Reader<'_>,
+    found: &Sender<Found>,
+) -> io::Result<()> {
+    let (dir, changed) = scan_dir(root, relative, previous, reader)?;
+    let subdirs = dir.dirs.clone();
+    found
+        .send((relative.to_path_buf(), dir, changed))
+        .map_err(|_| io::Error::other("Indexing stopped"))?;
+    for name in subdirs {
+        scan_tree(root, &relative.join(name), previous, reader, found)?;
+    }
+    Ok(())
+}
+
+// One directory, reusing what was found before if it has not changed since.
+// Also returns whether it was read.
+fn scan_dir(
+    root: &Path,
+    relative: &Path,
+    previous: &BTreeMap<PathBuf, Dir>,
+    reader: Reader<'_>,
+) -> io::Result<(Dir, bool)> {
+    reader.cancel.check()?;
+    let path = root.join(relative);
+    let modified = fs::metadata(&path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()));
+    if let Some(dir) = previous.get(relative) {
+        if dir.modified == modified {
+            return Ok((dir.clone(), false));
+        }
+    }
+    let mut dir = Dir {
+        modified,
+        ..Dir::default()
+    };
+    for entry in fs::read_dir(&path)? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            dir.dirs.push(name);
+        } else if file_type.is_file() {
+            let nametag =
+                NameTag::from_path_with_format(&name, reader.extension, reader.format.clone());
+            let tags = nametag
+                .get_tags()
+                .map(|tag| tag.to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            if !tags.is_empty() {
+                let untagged = nametag.untagged().to_string_lossy().into_owned();
+                dir.untagged.insert(name.clone(), untagged);
+            }
+            dir.files.insert(name, tags);
+        }
+    }
+    dir.dirs.sort();
+    Ok((dir, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn search(index: &Index, query: &str) -> Vec<PathBuf> {
+        index
+            .search(&query.parse().unwrap(), |_| None)
+            .into_iter()
+            .map(|path| path.strip_prefix(&index.root).unwrap().to_path_buf())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_search() {
+        let dir = env::temp_dir().join("nametag-index-build");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub").join("deeper")).unwrap();
+        fs::File::create(dir.join("a[x y].txt")).unwrap();
+        fs::File::create(dir.join("sub").join("b[x].txt")).unwrap();
+        fs::File::create(dir.join("sub").join("deeper").join("c.txt")).unwrap();
+        let index = Index::build(
+            &dir,
+            &TagFormat::default(),
+            Extension::FirstDot,
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(3, index.len());
+        assert_eq!(
+            vec![PathBuf::from("a[x y].txt"), PathBuf::from("sub/b[x].txt")],
+            search(&index, "x")
+        );
+        assert_eq!(
+            vec![PathBuf::from("sub/b[x].txt")],
+            search(&index, "x AND NOT y")
+        );
+        assert_eq!(
+            vec![
+                PathBuf::from("sub/b[x].txt"),
+                PathBuf::from("sub/deeper/c.txt")
+            ],
+            search(&index, "NOT y")
+        );
+    }
+    #[test]
+    fn test_search_planned() {
+        let dir = env::temp_dir().join("nametag-index-planned");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &[
+            "a[project-x rare].txt",
+            "b[project-y common].txt",
+            "c[common x=secret].txt",
+            "d[projects common].txt",
+        ] {
+            fs::File::create(dir.join(name)).unwrap();
+        }
+        let index = Index::build(
+            &dir,
+            &TagFormat::default(),
+            Extension::FirstDot,
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("a[project-x rare].txt"),
+                PathBuf::from("b[project-y common].txt")
+            ],
+            search(&index, "project-*")
+        );
+        assert_eq!(
+            vec![PathBuf::from("b[project-y common].txt")],
+            search(&index, "common AND project-* AND NOT rare")
+        );
+        let names = index
+            .tags
+            .iter()
+            .map(|(tag, paths)| (tag.clone(), vec![paths]))
+            .collect();
+        let estimate = |text: &str| index.estimate(&text.parse().unwrap(), &names);
+        assert_eq!(1, estimate("rare AND common"));
+        assert_eq!(3, estimate("common"));
+        assert_eq!(1, estimate("NOT common"));
+        // Tags are found by what reveal gives for them.
+        let revealed = index
+            .search(&Query::tag("hidden"), |tag| {
+                (tag == "x=secret").then(|| "hidden".to_owned())
+            })
+            .len();
+        assert_eq!(1, revealed);
+    }
+    #[test]
+    fn test_search_name() {
+        let dir = env::temp_dir().join("nametag-index-name");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in &[
+            "Acme invoice[paid].pdf",
+            "sub/acme.txt",
+            "b[acme].txt",
+            "ac.txt",
+        ] {
+            fs::File::create(dir.join(name)).unwrap();
+        }
+        let index = Index::build(
+            &dir,
+            &TagFormat::default(),
+            Extension::FirstDot,
+            &Cancel::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("Acme invoice[paid].pdf"),
+                PathBuf::from("sub/acme.txt")
+            ],
+            search(&index, "name:~acme")
+        );
+        assert_eq!(
+            vec![PathBuf::from("Acme invoice[paid].pdf")],
+            search(&index, "name:~ACME AND paid")
+        );
+        assert_eq!(
+            vec![PathBuf::from("ac.txt")],
+            search(&index, "name:~ac AND NOT name:~acm")
+        );
+        assert!(search(&index, "name:~zzz").is_empty());
+    }
+    #[test]
+    fn test_build_resumable() {
+        let dir = env::temp_dir().join("nametag-index-resumable");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::File::create(dir.join("sub").join("a[x].txt")).unwrap();
+        let (format, cancel) = (TagFormat::default(), Cancel::default());
+        let checkpoint = dir.with_extension("checkpoint.json");
+        // As though interrupted once "sub" was read, recording a file the
+        // resumed build only knows of from the checkpoint.
+        let mut partial = Index::build(&dir, &format, Extension::FirstDot, &cancel).unwrap();
+        partial.dirs.remove(Path::new(""));
+        let sub = partial.dirs.get_mut(Path::new("sub")).unwrap();
+        sub.files
+            .insert("b[y].txt".to_owned(), vec!["y".to_owned()]);
+        partial.save(&checkpoint).unwrap();
+        let index =
+            Index::build_resumable(&dir, &format, Extension::FirstDot, &cancel, &checkpoint)
+                .unwrap();
+        assert_eq!(vec![PathBuf::from("sub/b[y].txt")], search(&index, "y"));
+        assert_eq!(2, index.len());
+        assert!(!checkpoint.exists());
+        cancel.cancel();
+        assert!(
+            Index::build_resumable(&dir, &format, Extension::FirstDot, &cancel, &checkpoint)
+                .is_err()
+        );
+        assert_eq!(dir, Index::load(&checkpoint).unwrap().root);
+    }
+    #[test]
+    fn test_update() {
+        let dir = env::temp_dir().join("nametag-index-update");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::File::create(dir.join("sub").join("a.txt")).unwrap();
+        let format = TagFormat::default();
+        let mut index =
+            Index::build(&dir, &format, Extension::FirstDot, &Cancel::default()).unwrap();
+        assert_eq!(
+            0,
+            index
+                .update(&format, Extension::FirstDot, &Cancel::default())
+                .unwrap()
+        );
+        // Directory times may be coarse, so make the change visible.
+        let sub = dir.join("sub");
+        index.dirs.get_mut(Path::new("sub")).unwrap().modified = (0, 0);
+        fs::rename(sub.join("a.txt"), sub.join("a[x].txt")).unwrap();
+        assert_eq!(
+            1,
+            index
+                .update(&format, Extension::FirstDot, &Cancel::default())
+                .unwrap()
+        );
+        assert_eq!(vec![PathBuf::from("sub/a[x].txt")], search(&index, "x"));
+    }
+}
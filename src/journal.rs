@@ -0,0 +1,379 @@
+// Record of a batch in progress, so an interrupted batch can be finished or rolled back.
+//
+// The journal is a file of checksummed json lines. The first line holds the
+// planned renames, and each following line confirms one of them was performed.
+// Large batches are applied in chunks, each ending with a checkpoint forced
+// to disk, so a crash loses at most the confirmations of one chunk.
+// eg
+// {"plan":[{"from":"a.txt","to":"a[tag].txt"},{"from":"b.txt","to":"b[tag].txt"}]}	<checksum>
+// {"done":0}	<checksum>
+// {"checkpoint":1}	<checksum>
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::batch::{self, Batch, Rename, Skipped};
+use crate::config::state_dir;
+use crate::jsonl;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Record {
+    Plan(Vec<Rename>),
+    Done(usize),
+    /// Renames before this index were each performed or skipped.
+    Checkpoint(usize),
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Where the journal lives when not given explicitly. eg ~/.local/state/nametag/journal
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("journal"))
+    }
+
+    /// Start a journal for a batch. Fails if an unfinished batch is already recorded.
+    pub fn begin(path: &Path, batch: &Batch) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    err.kind(),
+                    "An unfinished batch was found. Run \"nametag resume\" to finish or roll it back.",
+                ),
+                _ => err,
+            })?;
+        let mut journal = Self {
+            path: path.to_path_buf(),
+            file,
+        };
+        // Paths are made absolute, so resuming works from any directory.
+        let cwd = env::current_dir()?;
+        let plan = batch
+            .renames()
+            .iter()
+            .map(|rename| Rename {
+                from: cwd.join(&rename.from),
+                to: cwd.join(&rename.to),
+            })
+            .collect();
+        journal.write(&Record::Plan(plan))?;
+        // The plan must survive a crash. Confirmations need not, as resuming
+        // checks the files themselves.
+        journal.file.sync_data()?;
+        Ok(journal)
+    }
+
+    /// Record that a planned rename was performed.
+    pub fn confirm(&mut self, index: usize) -> io::Result<()> {
+        self.write(&Record::Done(index))
+    }
+
+    /// Record that the renames before an index were dealt with, forcing
+    /// this and every confirmation before it to disk.
+    pub fn checkpoint(&mut self, index: usize) -> io::Result<()> {
+        self.write(&Record::Checkpoint(index))?;
+        self.file.sync_data()
+    }
+
+    /// The batch completed. Remove the journal.
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path)
+    }
+
+    /// Read back an unfinished batch, if there is one. A torn write at the
+    /// end of the journal, from a crash, is cut off first.
+    pub fn recover(path: &Path) -> io::Result<Option<Recovery>> {
+        Self::repair(path)?;
+        let records = jsonl::read::<Record>(path)?;
+        if records.is_empty() {
+            // Nothing is renamed before the plan is safely written.
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(None),
+            };
+        }
+        let mut plan = None;
+        let mut done = Vec::new();
+        let mut checkpoint = 0;
+        for record in records {
+            match record {
+                Record::Plan(renames) => plan = Some(renames),
+                Record::Done(index) => done.push(index),
+                Record::Checkpoint(index) => checkpoint = checkpoint.max(index),
+            }
+        }
+        let renames = plan.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No plan found in journal: {}", path.display()),
+            )
+        })?;
+        let mut confirmed = vec![false; renames.len()];
+        for index in done {
+            if let Some(slot) = confirmed.get_mut(index) {
+                *slot = true;
+            }
+        }
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Some(Recovery {
+            renames,
+            confirmed,
+            checkpoint,
+            journal: Self {
+                path: path.to_path_buf(),
+                file,
+            },
+        }))
+    }
+
+    /// Cut a journal back to its intact records, returning the number of
+    /// bytes removed.
+    pub fn repair(path: &Path) -> io::Result<usize> {
+        jsonl::repair::<Record>(path)
+    }
+
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        self.file.write_all(&jsonl::encode(record)?)
+    }
+}
+
+/// An unfinished batch read back from a journal.
+#[derive(Debug)]
+pub struct Recovery {
+    renames: Vec<Rename>,
+    confirmed: Vec<bool>,
+    checkpoint: usize,
+    journal: Journal,
+}
+
+impl Recovery {
+    /// The planned renames.
+    pub fn renames(&self) -> &[Rename] {
+        &self.renames
+    }
+
+    /// Renames dealt with as of the last checkpoint, none if there was none.
+    pub fn checkpoint(&self) -> usize {
+        self.checkpoint
+    }
+
+    /// Number of renames known to have been performed.
+    pub fn done(&self) -> usize {
+        (0..self.renames.len())
+            .filter(|index| self.is_done(*index))
+            .count()
+    }
+
+    // Confirmed in the journal, or evidently performed but not yet confirmed.
+    fn is_done(&self, index: usize) -> bool {
+        let rename = &self.renames[index];
+        self.confirmed[index] || (!rename.from.exists() && rename.to.exists())
+    }
+
+    /// Perform the renames still outstanding, then remove the journal.
+    /// Renames that can no longer be made are skipped and returned.
+    pub fn finish(mut self) -> io::Result<Vec<Skipped>> {
+        let mut skipped = Vec::new();
+        for index in 0..self.renames.len() {
+            if !self.is_done(index) {
+                let rename = &self.renames[index];
+                match batch::rename_checked(rename, false)? {
+                    Some(reason) => skipped.push(Skipped {
+                        rename: rename.clone(),
+                        reason,
+                    }),
+                    None => self.journal.confirm(index)?,
+                }
+            }
+        }
+        self.journal.finish()?;
+        Ok(skipped)
+    }
+
+    /// Undo the renames already performed, in reverse, then remove the journal.
+    /// Renames that can no longer be undone, as the original name was taken
+    /// again since, are skipped and returned.
+    pub fn rollback(self) -> io::Result<Vec<Skipped>> {
+        let mut skipped = Vec::new();
+        for index in (0..self.renames.len()).rev() {
+            if self.is_done(index) {
+                let rename = &self.renames[index];
+                let undo = Rename {
+                    from: rename.to.clone(),
+                    to: rename.from.clone(),
+                };
+                if let Some(reason) = batch::rename_checked(&undo, false)? {
+                    skipped.push(Skipped {
+                        rename: undo,
+                        reason,
+                    });
+                }
+            }
+        }
+        self.journal.finish()?;
+        Ok(skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch directory, emptied for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("nametag-journal-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Two files to rename, with the first done and confirmed.
+    fn interrupted(dir: &Path) -> Batch {
+        let mut batch = Batch::new();
+        for name in &["a", "b"] {
+            let from = dir.join(format!("{}.txt", name));
+            File::create(&from).unwrap();
+            batch.push(&from, dir.join(format!("{}[tag].txt", name)));
+        }
+        let mut journal = Journal::begin(&dir.join("journal"), &batch).unwrap();
+        let first = &batch.renames()[0];
+        fs::rename(&first.from, &first.to).unwrap();
+        journal.confirm(0).unwrap();
+        batch
+    }
+
+    #[test]
+    fn test_recover_nothing() {
+        let dir = scratch("nothing");
+        assert!(Journal::recover(&dir.join("journal")).unwrap().is_none());
+    }
+    #[test]
+    fn test_begin_refuses_unfinished() {
+        let dir = scratch("unfinished");
+        interrupted(&dir);
+        assert!(Journal::begin(&dir.join("journal"), &Batch::new()).is_err());
+    }
+    #[test]
+    fn test_resume_finish() {
+        let dir = scratch("finish");
+        interrupted(&dir);
+        let recovery = Journal::recover(&dir.join("journal")).unwrap().unwrap();
+        assert_eq!(1, recovery.done());
+        recovery.finish().unwrap();
+        assert!(dir.join("a[tag].txt").exists());
+        assert!(dir.join("b[tag].txt").exists());
+        assert!(!dir.join("journal").exists());
+    }
+    #[test]
+    fn test_resume_rollback() {
+        let dir = scratch("rollback");
+        interrupted(&dir);
+        let recovery = Journal::recover(&dir.join("journal")).unwrap().unwrap();
+        assert!(recovery.rollback().unwrap().is_empty());
+        assert!(dir.join("a.txt").exists());
+        assert!(dir.join("b.txt").exists());
+        assert!(!dir.join("journal").exists());
+    }
+    #[test]
+    fn test_resume_rollback_taken() {
+        let dir = scratch("rollback-taken");
+        interrupted(&dir);
+        fs::write(dir.join("a.txt"), "recreated").unwrap();
+        let recovery = Journal::recover(&dir.join("journal")).unwrap().unwrap();
+        let skipped = recovery.rollback().unwrap();
+        assert_eq!(1, skipped.len());
+        assert_eq!(dir.join("a[tag].txt"), skipped[0].rename.from);
+        assert_eq!("recreated", fs::read_to_string(dir.join("a.txt")).unwrap());
+        assert!(dir.join("a[tag].txt").exists());
+    }
+    #[test]
+    fn test_recover_torn_write() {
+        let dir = scratch("torn");
+        interrupted(&dir);
+        let path = dir.join("journal");
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"do")
+            .unwrap();
+        let recovery = Journal::recover(&path).unwrap().unwrap();
+        assert_eq!(1, recovery.done());
+        recovery.finish().unwrap();
+        assert!(dir.join("b[tag].txt").exists());
+    }
+    #[test]
+    fn test_recover_torn_plan() {
+        let dir = scratch("torn-plan");
+        let path = dir.join("journal");
+        fs::write(&path, b"{\"plan\":[").unwrap();
+        assert!(Journal::recover(&path).unwrap().is_none());
+        assert!(!path.exists());
+    }
+    #[test]
+    fn test_recover_checkpoint() {
+        let dir = scratch("checkpoint");
+        let mut batch = Batch::new();
+        for name in &["a", "b", "c"] {
+            let from = dir.join(format!("{}.txt", name));
+            File::create(&from).unwrap();
+            batch.push(&from, dir.join(format!("{}[tag].txt", name)));
+        }
+        batch.set_chunk_size(2);
+        let journal = dir.join("journal");
+        batch.apply_journaled(&journal).unwrap();
+        assert!(!journal.exists());
+        // As though the machine died after the first chunk.
+        let mut journal = Journal::begin(&journal, &batch).unwrap();
+        journal.confirm(0).unwrap();
+        journal.confirm(1).unwrap();
+        journal.checkpoint(2).unwrap();
+        let recovery = Journal::recover(&dir.join("journal")).unwrap().unwrap();
+        assert_eq!(2, recovery.checkpoint());
+    }
+    #[test]
+    fn test_apply_checkpoints() {
+        let dir = scratch("apply-checkpoints");
+        let mut batch = Batch::new();
+        for name in &["a", "b", "c"] {
+            let from = dir.join(format!("{}.txt", name));
+            File::create(&from).unwrap();
+            batch.push(&from, dir.join(format!("{}[tag].txt", name)));
+        }
+        // Fails partway through the second chunk, into a missing directory.
+        let from = dir.join("d.txt");
+        File::create(&from).unwrap();
+        batch.push(&from, dir.join("missing").join("d[tag].txt"));
+        batch.set_chunk_size(2);
+        let journal = dir.join("journal");
+        assert!(batch.apply_journaled(&journal).is_err());
+        let recovery = Journal::recover(&journal).unwrap().unwrap();
+        assert_eq!(2, recovery.checkpoint());
+        assert_eq!(3, recovery.done());
+    }
+    #[test]
+    fn test_resume_unconfirmed_rename() {
+        let dir = scratch("unconfirmed");
+        let batch = interrupted(&dir);
+        let second = &batch.renames()[1];
+        fs::rename(&second.from, &second.to).unwrap();
+        let recovery = Journal::recover(&dir.join("journal")).unwrap().unwrap();
+        assert_eq!(2, recovery.done());
+        recovery.finish().unwrap();
+    }
+}
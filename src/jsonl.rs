@@ -0,0 +1,118 @@
+// Append only files of json records, one per line, each followed by a
+// checksum of the record. eg {"done":0}<tab>1b7a6e0f2c5d9e31
+//
+// A crash mid write leaves a torn final line, and a failing disk may garble
+// any line. Reading stops at the first record that is incomplete or does not
+// match its checksum, and repairing cuts the file back to the records before
+// it, so later appends are not lost behind it. Lines written before
+// checksums were added are read without one.
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+fn checksum(data: &[u8]) -> String {
+    Sha256::digest(data)[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A record as a line, ending with its checksum and a newline.
+pub fn encode<T: Serialize>(record: &T) -> io::Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(record)?;
+    let sum = checksum(&line);
+    line.push(b'\t');
+    line.extend_from_slice(sum.as_bytes());
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// The intact records at the start of some data, and the number of bytes
+/// they take up.
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> (Vec<T>, usize) {
+    let mut records = Vec::new();
+    let mut intact = 0;
+    while let Some(end) = data[intact..].iter().position(|byte| *byte == b'\n') {
+        let line = &data[intact..intact + end];
+        let json = match line.iter().rposition(|byte| *byte == b'\t') {
+            Some(tab) if checksum(&line[..tab]).as_bytes() == &line[tab + 1..] => &line[..tab],
+            Some(_) => break,
+            None => line,
+        };
+        match serde_json::from_slice(json) {
+            Ok(record) => records.push(record),
+            Err(_) => break,
+        }
+        intact += end + 1;
+    }
+    (records, intact)
+}
+
+/// Read every intact record of a file. A missing file has none.
+pub fn read<T: DeserializeOwned>(path: &Path) -> io::Result<Vec<T>> {
+    match fs::read(path) {
+        Ok(data) => Ok(decode(&data).0),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Cut a file back to its intact records, returning the number of bytes
+/// removed. Records must be of a single type for them to be recognised.
+pub fn repair<T: DeserializeOwned>(path: &Path) -> io::Result<usize> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    };
+    let intact = decode::<T>(&data).1;
+    if intact < data.len() {
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(intact as u64)?;
+        file.sync_all()?;
+    }
+    Ok(data.len() - intact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_round_trip() {
+        let mut data = encode(&1).unwrap();
+        data.extend(encode(&2).unwrap());
+        data.extend(b"3\n");
+        assert_eq!((vec![1, 2, 3], data.len()), decode::<u32>(&data));
+    }
+    #[test]
+    fn test_decode_stops_at_damage() {
+        let first = encode(&"a").unwrap();
+        let mut data = first.clone();
+        data.extend(encode(&"b").unwrap());
+        // A flipped byte within the second record
+        data[first.len() + 1] = b'c';
+        data.extend(encode(&"d").unwrap());
+        assert_eq!((vec!["a".to_owned()], first.len()), decode::<String>(&data));
+        // A torn write
+        let mut data = first.clone();
+        data.extend(&encode(&"b").unwrap()[..3]);
+        assert_eq!((vec!["a".to_owned()], first.len()), decode::<String>(&data));
+    }
+    #[test]
+    fn test_repair() {
+        let path = env::temp_dir().join("nametag-jsonl-repair");
+        let mut data = encode(&1).unwrap();
+        let intact = data.len();
+        data.extend(b"{\"to");
+        fs::write(&path, &data).unwrap();
+        assert_eq!(4, repair::<u32>(&path).unwrap());
+        assert_eq!(intact as u64, fs::metadata(&path).unwrap().len());
+        assert_eq!(0, repair::<u32>(&path).unwrap());
+    }
+}
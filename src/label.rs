@@ -0,0 +1,232 @@
+// Printable labels for the boxes of a physical archive, each carrying a
+// short code that leads back to the tagged directory. eg 7KQ2MD
+//
+// Codes are recorded in the state directory as labels are printed, so
+// "nametag locate" can resolve them. eg ~/.local/state/nametag/labels.json
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::state_dir;
+
+// Crockford's base32, which leaves out letters easily misread on paper
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const CODE_LEN: usize = 6;
+
+/// A short code for a directory, stable for the same path.
+pub fn code_for(path: &Path) -> String {
+    let hash = Sha256::digest(path.as_os_str().as_encoded_bytes());
+    let bits = hash[..8]
+        .iter()
+        .fold(0u64, |bits, byte| (bits << 8) | u64::from(*byte));
+    (0..CODE_LEN)
+        .map(|index| ALPHABET[(bits >> (59 - index * 5)) as usize & 31] as char)
+        .collect()
+}
+
+/// Codes printed so far, with the directory each stands for.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Registry {
+    pub codes: BTreeMap<String, PathBuf>,
+}
+
+impl Registry {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("labels.json"))
+    }
+
+    /// Load the registry. A missing file has no codes.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Directory a code was printed for. Codes are read case insensitively,
+    /// and with the letters O, I and L read as the digits they resemble.
+    pub fn locate(&self, code: &str) -> Option<&Path> {
+        let code = code
+            .trim()
+            .to_ascii_uppercase()
+            .replace('O', "0")
+            .replace(['I', 'L'], "1");
+        self.codes.get(&code).map(PathBuf::as_path)
+    }
+}
+
+/// The text of one label.
+pub struct Label {
+    pub code: String,
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+// Page layout in points, for A4 paper
+const PAGE_WIDTH: usize = 595;
+const PAGE_HEIGHT: usize = 842;
+const MARGIN: usize = 40;
+const LABEL_HEIGHT: usize = 150;
+const LINE_HEIGHT: usize = 14;
+const MAX_LINES: usize = 7;
+
+/// A pdf document holding labels, stacked down the page.
+pub fn pdf(labels: &[Label]) -> Vec<u8> {
+    let per_page = (PAGE_HEIGHT - 2 * MARGIN) / LABEL_HEIGHT;
+    let pages = labels.chunks(per_page.max(1)).collect::<Vec<_>>();
+    // Objects 1 and 2 are the catalog and page tree, 3 the font, then a
+    // page and its content for each page.
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        String::new(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_owned(),
+    ];
+    let mut kids = Vec::new();
+    for page in &pages {
+        let content = page_content(page);
+        let page_id = objects.len() + 1;
+        kids.push(format!("{} 0 R", page_id));
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            page_id + 1
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.len(),
+            content
+        ));
+    }
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        kids.join(" "),
+        kids.len()
+    );
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).bytes());
+    }
+    let xref = out.len();
+    out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+    for offset in offsets {
+        out.extend(format!("{:010} 00000 n \n", offset).bytes());
+    }
+    out.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .bytes(),
+    );
+    out
+}
+
+// Drawing operators for the labels on one page
+fn page_content(labels: &[Label]) -> String {
+    let mut content = String::new();
+    for (index, label) in labels.iter().enumerate() {
+        let top = PAGE_HEIGHT - MARGIN - index * LABEL_HEIGHT;
+        let bottom = top - LABEL_HEIGHT + 10;
+        // Cut line around the label
+        content.push_str(&format!(
+            "{} {} {} {} re S\n",
+            MARGIN,
+            bottom,
+            PAGE_WIDTH - 2 * MARGIN,
+            LABEL_HEIGHT - 10
+        ));
+        let mut line = |size: usize, x: usize, y: usize, text: &str| {
+            content.push_str(&format!(
+                "BT /F1 {} Tf {} {} Td ({}) Tj ET\n",
+                size,
+                x,
+                y,
+                pdf_text(text)
+            ));
+        };
+        line(28, MARGIN + 12, top - 40, &label.code);
+        line(14, MARGIN + 12, top - 62, &label.title);
+        for (row, text) in label.lines.iter().take(MAX_LINES).enumerate() {
+            line(10, MARGIN + 12, top - 80 - row * LINE_HEIGHT, text);
+        }
+    }
+    content
+}
+
+// Text as a pdf string. Characters outside latin 1 cannot be shown by the
+// standard fonts, so they become "?".
+fn pdf_text(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            '\u{a0}'..='\u{ff}' => out.push_str(&format!("\\{:03o}", c as u32)),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_for() {
+        let code = code_for(Path::new("/archive/box1"));
+        assert_eq!(CODE_LEN, code.len());
+        assert_eq!(code, code_for(Path::new("/archive/box1")));
+        assert_ne!(code, code_for(Path::new("/archive/box2")));
+        assert!(code.bytes().all(|c| ALPHABET.contains(&c)));
+    }
+    #[test]
+    fn test_locate() {
+        let mut registry = Registry::default();
+        registry
+            .codes
+            .insert("1K0ABC".to_owned(), PathBuf::from("/archive/box1"));
+        assert_eq!(Some(Path::new("/archive/box1")), registry.locate("ik0abc"));
+        assert_eq!(Some(Path::new("/archive/box1")), registry.locate("1kOabc"));
+        assert_eq!(None, registry.locate("ZZZZZZ"));
+    }
+    #[test]
+    fn test_pdf() {
+        let labels = (0..6)
+            .map(|index| Label {
+                code: format!("CODE{}", index),
+                title: "box (one)".to_owned(),
+                lines: vec!["café".to_owned()],
+            })
+            .collect::<Vec<_>>();
+        let pdf = String::from_utf8_lossy(&pdf(&labels)).into_owned();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("(CODE5) Tj"));
+        assert!(pdf.contains("(box \\(one\\)) Tj"));
+        assert!(pdf.contains("(caf\\351) Tj"));
+        assert!(pdf.contains("/Count 2"));
+    }
+}
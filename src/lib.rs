@@ -2,38 +2,221 @@
 // filename[tag tag tag].ext
 use std::collections::BTreeSet;
 use std::convert::{From, TryFrom};
-use std::ffi::OsString;
-use std::path::PathBuf;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use serde::Deserialize;
+
+use format::{Position, TagFormat};
+
+pub mod abbreviate;
+pub mod alias;
+pub mod audit;
+pub mod backup;
+pub mod batch;
+pub mod bundle;
+pub mod cache;
+pub mod cancel;
+pub mod cloud;
+pub mod collate;
+pub mod color;
+pub mod complete;
+pub mod config;
+pub mod conflict;
+pub mod derive;
+pub mod doctor;
+pub mod escape;
+pub mod event;
+pub mod fixture;
+pub mod format;
+pub mod fuzz;
+pub mod graph;
+pub mod grep;
+pub mod guard;
+pub mod i18n;
+pub mod index;
+pub mod journal;
+pub mod jsonl;
+pub mod label;
+pub mod limits;
+pub mod literal;
+pub mod lock;
+pub mod log;
+pub mod macros;
+pub mod manifest;
+#[cfg(feature = "metadata")]
+pub mod metadata;
+pub mod obfuscate;
+pub mod ontology;
+pub mod open;
+pub mod paths;
+pub mod pattern;
+pub mod policy;
+pub mod query;
+pub mod readme;
+pub mod redact;
+pub mod resolve;
+pub mod risk;
+pub mod roots;
+pub mod search;
+pub mod series;
+pub mod simulate;
+pub mod slug;
+pub mod snapshot;
+pub mod summary;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transaction;
+pub mod trash;
+pub mod triage;
+pub mod walk;
+pub mod workspace;
+
 type Tag = OsString;
 
-#[derive(Debug)]
+// Separates the values of a multi-valued tag. eg authors=alice+bob
+pub(crate) const VALUE_SEPARATOR: char = '+';
+
+/// Where the extension of a name begins, which is where tags are inserted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Extension {
+    /// eg archive[tag].tar.gz
+    #[default]
+    FirstDot,
+    /// eg archive.tar[tag].gz
+    LastDot,
+}
+
+#[derive(Debug, Clone)]
 pub struct NameTag {
     start: usize,
     stop: usize,
     tags: BTreeSet<Tag>,
     name: OsString,
+    // Parent directory, when made from a path
+    dir: Option<PathBuf>,
+    format: TagFormat,
+    // Whether a space comes before the tags, and pads them within their
+    // delimiters. eg name [ a b ].txt
+    spaced: bool,
+    padded: bool,
+    // Block of machine tags following this one, when the format has one.
+    machine: Option<Box<NameTag>>,
+    // Made from bytes the platform cannot hold in a name, carried as one
+    // char per byte. Never so where names are bytes, as on unix.
+    carried: bool,
 }
 
 // Interface into tag naming scheme. eg filename[tag1 tag2].ext
 impl NameTag {
     pub fn new<T: Into<OsString>>(name: T) -> Self {
-        let data = name.into();
+        Self::with_format(name, TagFormat::default())
+    }
+
+    /// Tags written in another syntax. eg NameTag::with_format("a{x;y}.txt", braces)
+    pub fn with_format<T: Into<OsString>>(name: T, format: TagFormat) -> Self {
+        Self::parse(name.into(), Extension::FirstDot, format)
+    }
+
+    /// Tags of the final component of a path, so periods and brackets in parent
+    /// directories are left alone. Converting back gives the full path.
+    /// eg NameTag::from_path("my.backups/archive.tar.gz", Extension::LastDot)
+    pub fn from_path<P: AsRef<Path>>(path: P, extension: Extension) -> Self {
+        Self::from_path_with_format(path, extension, TagFormat::default())
+    }
+
+    /// As from_path, with tags written in another syntax.
+    pub fn from_path_with_format<P: AsRef<Path>>(
+        path: P,
+        extension: Extension,
+        format: TagFormat,
+    ) -> Self {
+        let path = path.as_ref();
+        let mut nametag = Self::parse(
+            path.file_name().unwrap_or_default().into(),
+            extension,
+            format,
+        );
+        nametag.dir = path.parent().map(Path::to_path_buf);
+        nametag
+    }
+
+    fn parse(data: OsString, extension: Extension, format: TagFormat) -> Self {
+        let machine_format = match &format.machine {
+            Some(machine_format) => TagFormat {
+                machine: None,
+                reserved: format.delimiters(),
+                ..(**machine_format).clone()
+            },
+            None => return Self::parse_block(data, extension, format, None),
+        };
+        // The name splits where the machine tags start, or would be inserted
+        // after the other tags. eg file[photo] | {sha=abc}.jpg
+        let bytes = data.as_encoded_bytes();
+        let human_format = TagFormat {
+            machine: None,
+            reserved: machine_format.delimiters(),
+            ..format.clone()
+        };
+        // Machine tags are looked for after any other tags, so delimiters
+        // within those are left alone.
+        let human = Self::parse_block(data.clone(), extension, human_format.clone(), None);
+        let after = if human.start < human.stop {
+            human.stop
+        } else {
+            0
+        };
+        let cut = match Self::get_tag_bounds(&bytes[after..], extension, &machine_format) {
+            Some((start, _)) => after + start,
+            None => human.stop,
+        };
+        let (head, tail) = bytes.split_at(cut);
+        let (head, tail) = unsafe {
+            (
+                OsString::from_encoded_bytes_unchecked(head.to_vec()),
+                OsString::from_encoded_bytes_unchecked(tail.to_vec()),
+            )
+        };
+        let mut nametag = Self::parse_block(head, extension, human_format, Some(cut));
+        let machine = Self::parse_block(tail, extension, machine_format, None);
+        nametag.machine = Some(Box::new(machine));
+        nametag
+    }
+
+    // Parse one block of tags, inserting new tags at a given spot if there
+    // are none yet.
+    fn parse_block(
+        data: OsString,
+        extension: Extension,
+        format: TagFormat,
+        insert: Option<usize>,
+    ) -> Self {
         let bytes = data.as_encoded_bytes();
         let mut tags = BTreeSet::new();
-        let (start, stop) = match Self::get_tag_bounds(&bytes) {
+        let (start, stop, spaced, padded) = match Self::get_tag_bounds(bytes, extension, &format) {
             Some((upper, lower)) => {
-                let blah =
-                    unsafe { OsString::from_encoded_bytes_unchecked(bytes[upper..lower].to_vec()) };
-                Self::parse_tags(&mut tags, &bytes[upper + 1..lower - 1]);
-                (upper, lower)
+                let inner = &bytes[upper + format.open.len()..lower - format.close.len()];
+                tags.extend(Self::parse_tag_names(inner, &format));
+                // Follow the style of existing tags. A space before them is
+                // taken in with the tags, so it goes if they do.
+                let spaced = upper > 0 && bytes[upper - 1] == b' ';
+                let padded = Self::is_padded(inner);
+                (upper - usize::from(spaced), lower, spaced, padded)
             }
             _ => {
                 // No existing tags. Pick a spot for potential tag insertion.
-                // Before the first period, or at the end of the name entirely.
-                let split = Self::get_ext_bound(&bytes);
-                (split, split)
+                let split = match (insert, format.position) {
+                    (Some(split), _) => split,
+                    (None, Position::BeforeExtension) => Self::get_ext_bound(bytes, extension),
+                    (None, Position::End) => bytes.len(),
+                    (None, Position::Prefix) => 0,
+                };
+                let spaced = format.space_before && split > 0;
+                (split, split, spaced, format.pad)
             }
         };
         Self {
@@ -41,9 +224,81 @@ impl NameTag {
             stop,
             tags,
             name: data,
+            dir: None,
+            format,
+            spaced,
+            padded,
+            machine: None,
+            carried: false,
+        }
+    }
+
+    /// Tags of a name given as bytes in any encoding, as read from an archive
+    /// or a network listing, rather than as the platform encodes names. Tags
+    /// are found by their ascii delimiters and the bytes kept as they are,
+    /// so to_bytes gives them back unchanged.
+    /// eg NameTag::from_bytes_lossless(b"caf\xe9[x].txt", TagFormat::default())
+    pub fn from_bytes_lossless<B: Into<Vec<u8>>>(bytes: B, format: TagFormat) -> Self {
+        let (name, carried) = carry(bytes.into());
+        let mut nametag = Self::parse(name, Extension::FirstDot, format);
+        nametag.carried = carried;
+        if let Some(machine) = &mut nametag.machine {
+            machine.carried = carried;
+        }
+        nametag
+    }
+
+    /// Tags of a name in Latin-1, as older archives and listings write them,
+    /// decoded so the tags read as text.
+    pub fn from_latin1(bytes: &[u8], format: TagFormat) -> Self {
+        let name = bytes
+            .iter()
+            .map(|byte| char::from(*byte))
+            .collect::<String>();
+        Self::with_format(name, format)
+    }
+
+    /// The name as bytes, in the encoding it was read in: as given to
+    /// from_bytes_lossless, else as the platform encodes names. Tags added
+    /// are written as utf8.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let carried = self.carried;
+        let bytes = Vec::<u8>::from(self.clone());
+        if carried {
+            uncarry(&bytes)
+        } else {
+            bytes
         }
     }
 
+    /// The name encoded as Latin-1, or None if it has characters Latin-1
+    /// cannot hold.
+    pub fn to_latin1(&self) -> Option<Vec<u8>> {
+        let name = String::try_from(self.clone()).ok()?;
+        name.chars().map(|c| u8::try_from(c).ok()).collect()
+    }
+
+    /// Tags as bytes, in the encoding the name was read in.
+    pub fn get_tag_bytes(&self) -> Vec<Vec<u8>> {
+        self.tags
+            .iter()
+            .map(|tag| match self.carried {
+                true => uncarry(tag.as_encoded_bytes()),
+                false => tag.as_encoded_bytes().to_vec(),
+            })
+            .collect()
+    }
+
+    /// The block of machine tags, when the format has one. eg {sha=abc} in file[photo]{sha=abc}.jpg
+    pub fn machine(&self) -> Option<&NameTag> {
+        self.machine.as_deref()
+    }
+
+    /// The block of machine tags, to edit independently of the others.
+    pub fn machine_mut(&mut self) -> Option<&mut NameTag> {
+        self.machine.as_deref_mut()
+    }
+
     /// Add a new tag. eg tags.add_tag("john")
     pub fn add_tag<T: Into<Tag>>(&mut self, tag: T) {
         self.tags.insert(tag.into());
@@ -54,11 +309,118 @@ impl NameTag {
         self.tags.remove(&tag.into());
     }
 
+    /// Add a tag of any content, escaped as %XX where needed. eg tags.add_tag_escaped("New York")
+    pub fn add_tag_escaped<T: AsRef<OsStr>>(&mut self, tag: T) {
+        self.add_tag(escape::escape(tag));
+    }
+
+    /// Tags present, with any %XX escapes decoded.
+    pub fn get_tags_unescaped(&self) -> Vec<OsString> {
+        self.tags.iter().map(escape::unescape).collect()
+    }
+
+    /// Check if a tag is present. eg tags.has_tag("john")
+    pub fn has_tag<T: AsRef<OsStr>>(&self, tag: T) -> bool {
+        self.tags.contains(tag.as_ref())
+    }
+
+    /// Values of a multi-valued tag. eg authors=alice+bob -> ["alice", "bob"]
+    /// Values in a block of machine tags are included.
+    pub fn get_values(&self, key: &str) -> Vec<String> {
+        let mut values = Vec::new();
+        let machine = self
+            .machine
+            .iter()
+            .flat_map(|machine| machine.value_tags(key));
+        for tag in self.value_tags(key).into_iter().chain(machine) {
+            let value = &tag.to_str().unwrap_or_default()[key.len() + 1..];
+            for value in value.split(VALUE_SEPARATOR).filter(|v| !v.is_empty()) {
+                if !values.iter().any(|existing| existing == value) {
+                    values.push(value.to_owned());
+                }
+            }
+        }
+        values
+    }
+
+    /// Check if a multi-valued tag holds a value. eg tags.has_value("authors", "alice")
+    pub fn has_value(&self, key: &str, value: &str) -> bool {
+        self.get_values(key)
+            .iter()
+            .any(|existing| existing == value)
+    }
+
+    /// Add a value to a multi-valued tag. eg tags.add_value("authors", "bob")
+    pub fn add_value(&mut self, key: &str, value: &str) {
+        let mut values = self.get_values(key);
+        if !values.iter().any(|existing| existing == value) {
+            values.push(value.to_owned());
+        }
+        self.set_values(key, &values);
+    }
+
+    /// Remove a value from a multi-valued tag, dropping the tag once empty.
+    pub fn remove_value(&mut self, key: &str, value: &str) {
+        let mut values = self.get_values(key);
+        values.retain(|existing| existing != value);
+        self.set_values(key, &values);
+    }
+
+    /// Replace the values of a multi-valued tag. eg tags.set_values("ep", &["3".to_owned()])
+    /// Values are kept in the block of machine tags, when there is one.
+    pub fn set_values(&mut self, key: &str, values: &[String]) {
+        for tag in self.value_tags(key) {
+            self.tags.remove(&tag);
+        }
+        if let Some(machine) = &mut self.machine {
+            machine.set_values(key, values);
+        } else if !values.is_empty() {
+            let joined = values.join(&VALUE_SEPARATOR.to_string());
+            self.add_tag(format!("{}={}", key, joined));
+        }
+    }
+
+    // Tags of the form key=...
+    fn value_tags(&self, key: &str) -> Vec<Tag> {
+        self.tags
+            .iter()
+            .filter(|tag| {
+                tag.to_str()
+                    .and_then(|tag| tag.strip_prefix(key))
+                    .is_some_and(|rest| rest.starts_with('='))
+            })
+            .cloned()
+            .collect()
+    }
+
     /// Grab all tags present
-    pub fn get_tags(&self) -> std::collections::btree_set::Iter<Tag> {
+    pub fn get_tags(&self) -> std::collections::btree_set::Iter<'_, Tag> {
         self.tags.iter()
     }
 
+    /// Add every tag of another name. eg a[x].txt + b[y].txt -> a[x y].txt
+    pub fn union_tags(&mut self, other: &NameTag) {
+        self.tags.extend(other.tags.iter().cloned());
+    }
+
+    /// Keep only tags also on another name.
+    pub fn intersect_tags(&mut self, other: &NameTag) {
+        self.tags.retain(|tag| other.tags.contains(tag));
+    }
+
+    /// Remove tags that are on another name.
+    pub fn subtract_tags(&mut self, other: &NameTag) {
+        self.tags.retain(|tag| !other.tags.contains(tag));
+    }
+
+    /// The name without its tags or directory. eg report[x y].pdf -> report.pdf
+    pub fn untagged(&self) -> OsString {
+        let mut nametag = self.clone();
+        nametag.clear_tags();
+        nametag.dir = None;
+        nametag.into()
+    }
+
     /// Remove all tags.
     pub fn clear_tags(&mut self) {
         self.tags.clear();
@@ -68,39 +430,136 @@ impl NameTag {
         self.name = unsafe {
             OsString::from_encoded_bytes_unchecked(prefix.chain(suffix).copied().collect())
         };
+        self.stop = self.start;
+    }
+
+    /// The same name and tags, written in another format. None if the name
+    /// without its tags would already read as having tags in that format.
+    /// eg a[x y].txt -> a{x;y}.txt
+    pub fn reformat(&self, format: TagFormat, extension: Extension) -> Option<NameTag> {
+        let mut untagged = self.clone();
+        untagged.clear_tags();
+        let dir = untagged.dir.take();
+        let mut nametag = Self::parse(untagged.into(), extension, format);
+        if !nametag.tags.is_empty() {
+            return None;
+        }
+        nametag.tags = self.tags.clone();
+        nametag.dir = dir;
+        Some(nametag)
+    }
+
+    /// Rename a file to carry these tags, returning its new path. Refuses to
+    /// overwrite another file. eg tags.apply(Path::new("photos/a.jpg"))
+    pub fn apply(&self, original: &Path) -> io::Result<PathBuf> {
+        let mut nametag = self.clone();
+        nametag.dir = None;
+        let path = original.with_file_name(OsString::from(nametag));
+        if path == original {
+            return Ok(path);
+        }
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        fs::rename(original, &path)?;
+        Ok(path)
     }
 
     // Get the in and out of the tag space. eg [ and ]
-    fn get_tag_bounds(data: &[u8]) -> Option<(usize, usize)> {
-        if let Some(start) = data.iter().position(|x| *x == b'[') {
-            if let Some(rstop) = data.iter().rev().position(|x| *x == b']') {
-                let stop = data.len() - rstop;
-                if start < stop {
-                    return Some((start, stop));
-                }
-            }
+    fn get_tag_bounds(
+        data: &[u8],
+        extension: Extension,
+        format: &TagFormat,
+    ) -> Option<(usize, usize)> {
+        let (open, close) = (format.open.as_bytes(), format.close.as_bytes());
+        if open.is_empty() {
+            return None;
+        }
+        let start = data.windows(open.len()).position(|x| x == open)?;
+        let inner = start + open.len();
+        if close.is_empty() {
+            // Without a closing delimiter, tags run to the extension.
+            let rest = &data[inner..];
+            let stop = match format.position {
+                Position::End => rest.len(),
+                _ => Self::get_ext_bound(rest, extension),
+            };
+            return Some((start, inner + stop));
+        }
+        let stop = data.windows(close.len()).rposition(|x| x == close)? + close.len();
+        if inner <= stop - close.len() {
+            return Some((start, stop));
         }
         None
     }
 
-    // Find first period, else end of name
-    fn get_ext_bound(data: &[u8]) -> usize {
-        if let Some(index) = data.iter().position(|x| *x == b'.') {
-            return index;
-        }
-        data.len()
+    // Tags with exactly one space inside each delimiter. eg [ a b ]
+    fn is_padded(inner: &[u8]) -> bool {
+        inner.len() > 2
+            && inner.starts_with(b" ")
+            && !inner.starts_with(b"  ")
+            && inner.ends_with(b" ")
+            && !inner.ends_with(b"  ")
+    }
+
+    // Find the first or last period, else end of name
+    fn get_ext_bound(data: &[u8], extension: Extension) -> usize {
+        let index = match extension {
+            Extension::FirstDot => data.iter().position(|x| *x == b'.'),
+            Extension::LastDot => data.iter().rposition(|x| *x == b'.'),
+        };
+        index.unwrap_or(data.len())
     }
 
     // Extract tags from name
-    fn parse_tags(tags: &mut BTreeSet<Tag>, data: &[u8]) {
-        let names = data
-            .split(|x| x.is_ascii_whitespace() || *x == b',' || *x == b'[' || *x == b']')
-            .filter(|x| x.len() != 0)
-            .map(|x| unsafe { OsString::from_encoded_bytes_unchecked(x.to_vec()) });
-        tags.extend(names);
+    fn parse_tag_names<'a>(
+        data: &'a [u8],
+        format: &'a TagFormat,
+    ) -> impl Iterator<Item = Tag> + 'a {
+        data.split(move |x| format.is_separator(*x))
+            .filter(|x| !x.is_empty())
+            .map(|x| unsafe { OsString::from_encoded_bytes_unchecked(x.to_vec()) })
     }
 }
 
+// Bytes of a name in any encoding as an OsString, and whether they had to be
+// carried one char per byte as the platform cannot hold them as they are.
+#[cfg(unix)]
+fn carry(bytes: Vec<u8>) -> (OsString, bool) {
+    use std::os::unix::ffi::OsStringExt;
+    (OsString::from_vec(bytes), false)
+}
+
+#[cfg(not(unix))]
+fn carry(bytes: Vec<u8>) -> (OsString, bool) {
+    match String::from_utf8(bytes) {
+        Ok(name) => (name.into(), false),
+        Err(err) => {
+            let name = err
+                .into_bytes()
+                .into_iter()
+                .map(char::from)
+                .collect::<String>();
+            (name.into(), true)
+        }
+    }
+}
+
+// The bytes carried as chars, with chars beyond a byte written as utf8.
+fn uncarry(carried: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for c in String::from_utf8_lossy(carried).chars() {
+        match u8::try_from(c) {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => bytes.extend(c.to_string().as_bytes()),
+        }
+    }
+    bytes
+}
+
 impl FromStr for NameTag {
     type Err = &'static String;
 
@@ -110,24 +569,50 @@ impl FromStr for NameTag {
 }
 
 impl From<NameTag> for Vec<u8> {
-    fn from(nametag: NameTag) -> Self {
+    fn from(mut nametag: NameTag) -> Self {
+        if let Some(dir) = nametag.dir.take() {
+            let name = OsString::from(nametag);
+            return dir.join(name).into_os_string().into_encoded_bytes();
+        }
+        if let Some(machine) = nametag.machine.take() {
+            let mut bytes = Vec::<u8>::from(nametag);
+            bytes.extend(Vec::<u8>::from(*machine));
+            return bytes;
+        }
         let tag_len = nametag.tags.len();
+        let format = &nametag.format;
+        let bytes = nametag.name.as_encoded_bytes();
+        let prefix = bytes[..nametag.start].iter();
+        let suffix = bytes[nametag.stop..].iter();
         if tag_len == 0 {
-            nametag.name.as_encoded_bytes().to_vec()
+            // Keep a name that never had tags as it was, but drop brackets
+            // whose tags were all removed.
+            let open = nametag.start + usize::from(nametag.spaced) + format.open.len();
+            let inner = bytes
+                .get(open..nametag.stop.saturating_sub(format.close.len()))
+                .unwrap_or_default();
+            if NameTag::parse_tag_names(inner, format).count() == 0 {
+                bytes.to_vec()
+            } else {
+                prefix.chain(suffix).copied().collect()
+            }
         } else {
-            let bytes = nametag.name.as_encoded_bytes();
-            let prefix = bytes[..nametag.start].iter();
-            let suffix = bytes[nametag.stop..].iter();
             let tags = nametag
                 .tags
-                .into_iter()
+                .iter()
+                .cloned()
                 .collect::<Vec<_>>()
-                .join(&OsString::from(" "));
+                .join(OsStr::new(&format.separator));
 
+            let space: &[u8] = b" ";
+            let pad = if nametag.padded { space } else { b"" };
             prefix
-                .chain(b"[".into_iter())
-                .chain(tags.as_encoded_bytes().iter())
-                .chain(b"]".into_iter())
+                .chain(if nametag.spaced { space } else { b"" })
+                .chain(format.open.as_bytes())
+                .chain(pad)
+                .chain(tags.as_encoded_bytes())
+                .chain(pad)
+                .chain(format.close.as_bytes())
                 .chain(suffix)
                 .copied()
                 .collect()
@@ -135,9 +620,22 @@ impl From<NameTag> for Vec<u8> {
     }
 }
 
+impl From<NameTag> for OsString {
+    fn from(nametag: NameTag) -> Self {
+        let bytes: Vec<u8> = nametag.into();
+        unsafe { OsString::from_encoded_bytes_unchecked(bytes) }
+    }
+}
+
+impl From<NameTag> for PathBuf {
+    fn from(nametag: NameTag) -> Self {
+        PathBuf::from(OsString::from(nametag))
+    }
+}
+
 impl From<PathBuf> for NameTag {
     fn from(path: PathBuf) -> NameTag {
-        NameTag::new(path.as_os_str())
+        NameTag::from_path(path, Extension::FirstDot)
     }
 }
 
@@ -176,6 +674,14 @@ mod tests {
         );
     }
     #[test]
+    fn test_round_trip_os_string() {
+        let name_tag: NameTag = "somefile[tagB tagA].txt".parse().unwrap();
+        assert_eq!(
+            OsString::from("somefile[tagA tagB].txt"),
+            OsString::from(name_tag)
+        );
+    }
+    #[test]
     fn test_round_trip_empty_tags() {
         let name_tag: NameTag = "somefile[].txt".parse().unwrap();
         assert_eq!("somefile[].txt", &String::try_from(name_tag).unwrap());
@@ -191,6 +697,12 @@ mod tests {
         );
     }
     #[test]
+    fn test_has_tag() {
+        let name_tag: NameTag = "somefile[tagB tagA].txt".parse().unwrap();
+        assert!(name_tag.has_tag("tagA"));
+        assert!(!name_tag.has_tag("tagC"));
+    }
+    #[test]
     fn test_add_tags() {
         let mut name_tag = NameTag::new("somefile.txt");
         name_tag.add_tag("tagB");
@@ -225,12 +737,253 @@ mod tests {
         );
     }
     #[test]
+    fn test_round_trip_escaped_tag() {
+        let mut name_tag = NameTag::new("somefile.txt");
+        name_tag.add_tag_escaped("New York");
+        assert_eq!(
+            vec![OsString::from("New York")],
+            name_tag.get_tags_unescaped()
+        );
+        let name: String = String::try_from(name_tag).unwrap();
+        assert_eq!("somefile[New%20York].txt", &name);
+        assert_eq!(
+            vec![OsString::from("New York")],
+            NameTag::new(name).get_tags_unescaped()
+        );
+    }
+    #[test]
+    fn test_set_operations() {
+        let other = NameTag::new("other[tagB tagC].txt");
+        let mut name_tag = NameTag::new("somefile[tagA tagB].txt");
+        name_tag.union_tags(&other);
+        assert_eq!(
+            vec!["tagA", "tagB", "tagC"],
+            name_tag.get_tags().collect::<Vec<_>>()
+        );
+        let mut name_tag = NameTag::new("somefile[tagA tagB].txt");
+        name_tag.intersect_tags(&other);
+        assert_eq!(vec!["tagB"], name_tag.get_tags().collect::<Vec<_>>());
+        let mut name_tag = NameTag::new("somefile[tagA tagB].txt");
+        name_tag.subtract_tags(&other);
+        assert_eq!("somefile[tagA].txt", &String::try_from(name_tag).unwrap());
+    }
+    #[test]
+    fn test_round_trip_remove_last_tag() {
+        let mut name_tag = NameTag::new("somefile[tagA].txt");
+        name_tag.remove_tag("tagA");
+        assert_eq!("somefile.txt", &String::try_from(name_tag).unwrap());
+    }
+    #[test]
     fn test_round_trip_clear_tags() {
         let mut name_tag = NameTag::new("somefile[tagB tagA].txt");
         name_tag.clear_tags();
         assert_eq!("somefile.txt", &String::try_from(name_tag).unwrap());
     }
 
+    #[test]
+    fn test_get_values() {
+        let name_tag = NameTag::new("somefile[authors=alice+bob tagA].txt");
+        assert_eq!(vec!["alice", "bob"], name_tag.get_values("authors"));
+        assert!(name_tag.has_value("authors", "bob"));
+        assert!(!name_tag.has_value("authors", "carol"));
+        assert!(name_tag.get_values("author").is_empty());
+    }
+    #[test]
+    fn test_round_trip_add_value() {
+        let mut name_tag = NameTag::new("somefile[authors=alice].txt");
+        name_tag.add_value("authors", "bob");
+        name_tag.add_value("authors", "alice");
+        name_tag.add_value("editors", "carol");
+        assert_eq!(
+            "somefile[authors=alice+bob editors=carol].txt",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+    #[test]
+    fn test_round_trip_remove_value() {
+        let mut name_tag = NameTag::new("somefile[authors=alice+bob].txt");
+        name_tag.remove_value("authors", "alice");
+        assert_eq!(
+            "somefile[authors=bob].txt",
+            &String::try_from(name_tag.clone()).unwrap()
+        );
+        name_tag.remove_value("authors", "bob");
+        assert_eq!("somefile.txt", &String::try_from(name_tag).unwrap());
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = std::env::temp_dir().join("nametag-apply");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::File::create(dir.join("a.txt")).unwrap();
+        fs::File::create(dir.join("b[x].txt")).unwrap();
+        fs::File::create(dir.join("b.txt")).unwrap();
+        let mut name_tag = NameTag::new("a.txt");
+        name_tag.add_tag("x");
+        assert_eq!(
+            dir.join("a[x].txt"),
+            name_tag.apply(&dir.join("a.txt")).unwrap()
+        );
+        assert!(dir.join("a[x].txt").exists());
+        let mut name_tag = NameTag::new("b.txt");
+        name_tag.add_tag("x");
+        assert_eq!(
+            io::ErrorKind::AlreadyExists,
+            name_tag.apply(&dir.join("b.txt")).unwrap_err().kind()
+        );
+        assert!(dir.join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_round_trip_from_path() {
+        let mut name_tag = NameTag::from_path("my.backups/archive.tar.gz", Extension::FirstDot);
+        name_tag.add_tag("tagA");
+        assert_eq!(
+            PathBuf::from("my.backups/archive[tagA].tar.gz"),
+            PathBuf::from(name_tag)
+        );
+        let mut name_tag = NameTag::from_path("my.backups/archive.tar.gz", Extension::LastDot);
+        name_tag.add_tag("tagA");
+        assert_eq!(
+            PathBuf::from("my.backups/archive.tar[tagA].gz"),
+            PathBuf::from(name_tag)
+        );
+    }
+    #[test]
+    fn test_from_path_ignores_parent_brackets() {
+        let name_tag = NameTag::from(PathBuf::from("/a[b c]/file[tagA].txt"));
+        assert_eq!(vec!["tagA"], name_tag.get_tags().collect::<Vec<_>>());
+        assert_eq!(
+            "/a[b c]/file[tagA].txt",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip_with_format() {
+        let braces = format::TagFormat::named("braces").unwrap();
+        let mut name_tag = NameTag::with_format("somefile{tagB;tagA}.txt", braces.clone());
+        name_tag.add_tag("tagC");
+        assert_eq!(
+            "somefile{tagA;tagB;tagC}.txt",
+            &String::try_from(name_tag).unwrap()
+        );
+        let mut name_tag = NameTag::with_format("somefile.txt", braces);
+        name_tag.add_tag("tagA");
+        assert_eq!("somefile{tagA}.txt", &String::try_from(name_tag).unwrap());
+    }
+    #[test]
+    fn test_round_trip_suffix_format() {
+        let suffix = format::TagFormat::named("suffix").unwrap();
+        let mut name_tag = NameTag::with_format("2019 holiday -- tagB tag-a.jpg", suffix.clone());
+        assert_eq!(
+            vec!["tag-a", "tagB"],
+            name_tag.get_tags().collect::<Vec<_>>()
+        );
+        name_tag.remove_tag("tagB");
+        assert_eq!(
+            "2019 holiday -- tag-a.jpg",
+            &String::try_from(name_tag.clone()).unwrap()
+        );
+        name_tag.remove_tag("tag-a");
+        assert_eq!("2019 holiday.jpg", &String::try_from(name_tag).unwrap());
+        let mut name_tag = NameTag::with_format("2019 holiday.jpg", suffix);
+        name_tag.add_tag("tagA");
+        assert_eq!(
+            "2019 holiday -- tagA.jpg",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+    #[test]
+    fn test_format_position() {
+        let mut format = format::TagFormat::default();
+        format.position = format::Position::Prefix;
+        let mut name_tag = NameTag::with_format("somefile.txt", format.clone());
+        name_tag.add_tag("tagA");
+        assert_eq!("[tagA]somefile.txt", &String::try_from(name_tag).unwrap());
+        format.position = format::Position::End;
+        let mut name_tag = NameTag::with_format("somefile.txt", format);
+        name_tag.add_tag("tagA");
+        assert_eq!("somefile.txt[tagA]", &String::try_from(name_tag).unwrap());
+    }
+
+    #[test]
+    fn test_reformat() {
+        let braces = format::TagFormat::named("braces").unwrap();
+        let name_tag = NameTag::from_path("dir[1]/somefile[tagB tagA].txt", Extension::FirstDot);
+        assert_eq!(
+            PathBuf::from("dir[1]/somefile{tagA;tagB}.txt"),
+            PathBuf::from(
+                name_tag
+                    .reformat(braces.clone(), Extension::FirstDot)
+                    .unwrap()
+            )
+        );
+        let name_tag = NameTag::new("some{file}[tagA].txt");
+        assert!(name_tag.reformat(braces, Extension::FirstDot).is_none());
+    }
+
+    #[test]
+    fn test_round_trip_whitespace_style() {
+        let mut name_tag = NameTag::new("somefile [ tagB ].txt");
+        name_tag.add_tag("tagA");
+        assert_eq!(
+            "somefile [ tagA tagB ].txt",
+            &String::try_from(name_tag.clone()).unwrap()
+        );
+        name_tag.clear_tags();
+        assert_eq!("somefile.txt", &String::try_from(name_tag).unwrap());
+        let format = format::TagFormat {
+            pad: true,
+            space_before: true,
+            ..Default::default()
+        };
+        let mut name_tag = NameTag::with_format("somefile.txt", format.clone());
+        name_tag.add_tag("tagA");
+        assert_eq!(
+            "somefile [ tagA ].txt",
+            &String::try_from(name_tag).unwrap()
+        );
+        // Existing names keep their style, whatever the format prefers.
+        let mut name_tag = NameTag::with_format("somefile[tagB].txt", format);
+        name_tag.add_tag("tagA");
+        assert_eq!(
+            "somefile[tagA tagB].txt",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_round_trip_machine_block() {
+        let dual = format::TagFormat::named("dual").unwrap();
+        let mut name_tag =
+            NameTag::with_format("file[travel photo]{sha=abc,seq=004}.jpg", dual.clone());
+        assert_eq!(
+            vec!["photo", "travel"],
+            name_tag.get_tags().collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["004"], name_tag.get_values("seq"));
+        name_tag.set_values("seq", &["005".to_owned()]);
+        name_tag.machine_mut().unwrap().remove_tag("sha=abc");
+        name_tag.remove_tag("photo");
+        assert_eq!(
+            "file[travel]{seq=005}.jpg",
+            &String::try_from(name_tag).unwrap()
+        );
+        let mut name_tag = NameTag::with_format("file.tar.gz", dual);
+        name_tag.set_values("sha", &["abc".to_owned()]);
+        assert_eq!(
+            "file{sha=abc}.tar.gz",
+            &String::try_from(name_tag.clone()).unwrap()
+        );
+        name_tag.add_tag("photo");
+        assert_eq!(
+            "file[photo]{sha=abc}.tar.gz",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+
     // Edgy Cases
     #[test]
     fn test_round_trip_nested_braces() {
@@ -264,4 +1017,30 @@ mod tests {
             &String::try_from(name_tag).unwrap()
         );
     }
+
+    // Foreign encodings
+    #[test]
+    fn test_from_bytes_lossless() {
+        let mut name_tag =
+            NameTag::from_bytes_lossless(&b"caf\xe9[x \xff].txt"[..], TagFormat::default());
+        assert_eq!(
+            vec![b"x".to_vec(), b"\xff".to_vec()],
+            name_tag.get_tag_bytes()
+        );
+        assert_eq!(b"caf\xe9[x \xff].txt".to_vec(), name_tag.to_bytes());
+        name_tag.add_tag("y");
+        assert_eq!(b"caf\xe9[x y \xff].txt".to_vec(), name_tag.to_bytes());
+    }
+    #[test]
+    fn test_from_latin1() {
+        let name_tag = NameTag::from_latin1(b"caf\xe9[\xe9t\xe9].txt", TagFormat::default());
+        assert!(name_tag.has_tag("été"));
+        assert_eq!(
+            Some(b"caf\xe9[\xe9t\xe9].txt".to_vec()),
+            name_tag.to_latin1()
+        );
+        let mut name_tag = name_tag;
+        name_tag.add_tag("⭐");
+        assert_eq!(None, name_tag.to_latin1());
+    }
 }
@@ -0,0 +1,170 @@
+// Bounds on the length and number of tags, keeping names usable and
+// catching a long string pasted in as a tag by mistake.
+// eg [limits]
+//    min_tag_length = 2
+//    max_tag_length = 32
+//    max_tags = 12
+//
+// Emoji tags are measured by their ascii name from config, so a single
+// emoji paired with a name is not too short.
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::alias::Aliases;
+use crate::NameTag;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Fewest characters in a tag.
+    pub min_tag_length: usize,
+    /// Most characters in a tag.
+    pub max_tag_length: usize,
+    /// Most tags on a file.
+    pub max_tags: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            min_tag_length: 2,
+            max_tag_length: 32,
+            max_tags: 12,
+        }
+    }
+}
+
+/// A way tags fall outside the limits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breach {
+    TooShort(String),
+    TooLong(String),
+    TooMany(usize),
+}
+
+impl fmt::Display for Breach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breach::TooShort(tag) => write!(f, "tag \"{}\" is too short", tag),
+            Breach::TooLong(tag) => write!(f, "tag \"{}\" is too long", tag),
+            Breach::TooMany(count) => write!(f, "{} tags are too many", count),
+        }
+    }
+}
+
+/// An edit refused as it would take the tags of a file outside the limits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Refused {
+    pub path: PathBuf,
+    pub breach: Breach,
+}
+
+impl fmt::Display for Refused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: Refusing as {}", self.path.display(), self.breach)
+    }
+}
+
+impl Error for Refused {}
+
+impl Limits {
+    /// Ways the tags of a file fall outside the limits.
+    pub fn breaches<S: AsRef<str>>(&self, tags: &[S], aliases: &Aliases) -> Vec<Breach> {
+        let mut breaches = Vec::new();
+        for tag in tags {
+            let tag = tag.as_ref();
+            let length = aliases.ascii(tag).chars().count();
+            if length < self.min_tag_length {
+                breaches.push(Breach::TooShort(tag.to_owned()));
+            } else if length > self.max_tag_length {
+                breaches.push(Breach::TooLong(tag.to_owned()));
+            }
+        }
+        if tags.len() > self.max_tags {
+            breaches.push(Breach::TooMany(tags.len()));
+        }
+        breaches
+    }
+
+    /// Breaches after an edit that were not present before, so files
+    /// already outside the limits may still be edited.
+    pub fn introduced(&self, before: &NameTag, after: &NameTag, aliases: &Aliases) -> Vec<Breach> {
+        let existing = self.breaches(&tags(before), aliases);
+        self.breaches(&tags(after), aliases)
+            .into_iter()
+            .filter(|breach| match breach {
+                Breach::TooMany(count) => !existing
+                    .iter()
+                    .any(|old| matches!(old, Breach::TooMany(old) if old >= count)),
+                _ => !existing.contains(breach),
+            })
+            .collect()
+    }
+}
+
+fn tags(nametag: &NameTag) -> Vec<String> {
+    nametag
+        .get_tags()
+        .map(|tag| tag.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn aliases() -> Aliases {
+        let mut emoji = BTreeMap::new();
+        emoji.insert("star".to_owned(), "⭐".to_owned());
+        Aliases::new(emoji)
+    }
+
+    #[test]
+    fn test_breaches() {
+        let limits = Limits {
+            max_tags: 2,
+            ..Limits::default()
+        };
+        let aliases = Aliases::default();
+        assert!(limits.breaches(&["work", "done"], &aliases).is_empty());
+        assert_eq!(
+            vec![
+                Breach::TooShort("x".to_owned()),
+                Breach::TooLong("a".repeat(33)),
+                Breach::TooMany(3),
+            ],
+            limits.breaches(&["x", &"a".repeat(33), "work"], &aliases)
+        );
+        assert!(limits.breaches(&["café"], &aliases).is_empty());
+    }
+    #[test]
+    fn test_emoji() {
+        let limits = Limits::default();
+        assert!(limits.breaches(&["⭐"], &aliases()).is_empty());
+        assert_eq!(
+            vec![Breach::TooShort("🎉".to_owned())],
+            limits.breaches(&["🎉"], &aliases())
+        );
+        let before = NameTag::new("a.txt");
+        let mut after = NameTag::new("a.txt");
+        after.add_tag("⭐");
+        assert!(limits.introduced(&before, &after, &aliases()).is_empty());
+    }
+    #[test]
+    fn test_introduced() {
+        let (limits, aliases) = (Limits::default(), Aliases::default());
+        let before = NameTag::new("a[x].txt");
+        let mut after = NameTag::new("a[x].txt");
+        after.add_tag("work");
+        assert!(limits.introduced(&before, &after, &aliases).is_empty());
+        after.add_tag("y");
+        assert_eq!(
+            vec![Breach::TooShort("y".to_owned())],
+            limits.introduced(&before, &after, &aliases)
+        );
+    }
+}
@@ -0,0 +1,109 @@
+// Telling tag blocks from bracketed text that is part of a title, so bulk
+// operations leave such names alone. Each word of a block is scored by how
+// much it reads as a tag, and the block by the mean of its words.
+// eg "Best of [Live at Wembley].mp3" scores 0.2, "report[draft work].pdf" 0.7
+use std::collections::BTreeSet;
+
+use crate::NameTag;
+
+// Small words found in titles, but rarely given as tags.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "at", "by", "for", "from", "in", "of", "on", "or", "the", "to", "with",
+];
+
+/// How much the words of a block read as tags, from 0 to 1. Words in the
+/// vocabulary are surely tags, stopwords surely not, and capitalized words
+/// likely part of a title.
+pub fn confidence<S: AsRef<str>>(tags: &[S], vocabulary: &BTreeSet<String>) -> f64 {
+    if tags.is_empty() {
+        return 1.0;
+    }
+    let total: f64 = tags
+        .iter()
+        .map(|tag| {
+            let tag = tag.as_ref();
+            let key = tag.split('=').next().unwrap_or(tag).to_lowercase();
+            if vocabulary.contains(&key) {
+                1.0
+            } else if STOPWORDS.contains(&key.as_str()) {
+                0.0
+            } else if tag.contains('=') {
+                0.9
+            } else if tag.starts_with(char::is_uppercase) {
+                0.3
+            } else {
+                0.7
+            }
+        })
+        .sum();
+    total / tags.len() as f64
+}
+
+/// Names whose tags score below a confidence, taken as plain text.
+#[derive(Debug, Default, Clone)]
+pub struct Literal {
+    below: f64,
+    vocabulary: BTreeSet<String>,
+}
+
+impl Literal {
+    /// Tags in the vocabulary are matched ignoring case.
+    pub fn new<I: IntoIterator<Item = String>>(below: f64, vocabulary: I) -> Self {
+        Self {
+            below,
+            vocabulary: vocabulary
+                .into_iter()
+                .map(|tag| tag.to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Whether the tags of a name are more likely part of its title.
+    pub fn is_literal(&self, nametag: &NameTag) -> bool {
+        let tags = nametag
+            .get_tags()
+            .map(|tag| tag.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        confidence(&tags, &self.vocabulary) < self.below
+    }
+}
+
+/// A confidence given on the command line, from 0 to 1.
+pub fn parse_confidence(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(confidence) if (0.0..=1.0).contains(&confidence) => Ok(confidence),
+        _ => Err(format!(
+            "Expected a confidence from 0 to 1, not \"{}\".",
+            text
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence() {
+        let vocabulary = vec!["live".to_owned()].into_iter().collect();
+        let none = BTreeSet::new();
+        assert!((confidence(&["Live", "at", "Wembley"], &none) - 0.2).abs() < 1e-9);
+        assert!((confidence(&["draft", "work"], &none) - 0.7).abs() < 1e-9);
+        assert_eq!(1.0, confidence(&["Live"], &vocabulary));
+        assert_eq!(0.9, confidence(&["year=2012"], &none));
+    }
+    #[test]
+    fn test_is_literal() {
+        let literal = Literal::new(0.5, vec!["Draft".to_owned()]);
+        assert!(literal.is_literal(&NameTag::new("Best of [Live at Wembley].mp3")));
+        assert!(!literal.is_literal(&NameTag::new("report[draft work].pdf")));
+        assert!(!literal.is_literal(&NameTag::new("report[Draft].pdf")));
+        assert!(!literal.is_literal(&NameTag::new("report.pdf")));
+    }
+    #[test]
+    fn test_parse_confidence() {
+        assert_eq!(Ok(0.5), parse_confidence("0.5"));
+        assert!(parse_confidence("2").is_err());
+        assert!(parse_confidence("high").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+// Advisory lock held while a command changes files, so two nametag processes
+// never interleave their renames or plan from names the other is changing.
+// eg ~/.local/state/nametag/lock
+//
+// The lock is shared by every tree, as the batch journal is. It is released
+// when the process exits, however it exits.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::config::state_dir;
+
+#[derive(Debug)]
+pub struct Lock {
+    // Held open for as long as the lock is
+    file: File,
+}
+
+impl Lock {
+    /// Where the lock lives when not given explicitly.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("lock"))
+    }
+
+    /// Take the lock, waiting for another process to release it or failing
+    /// straight away.
+    pub fn acquire(path: &Path, wait: bool) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if let Err(err) = lock(&file, wait) {
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err);
+            }
+            // Where locks are mandatory, as on Windows, the holder cannot be read.
+            let mut holder = String::new();
+            if file.read_to_string(&mut holder).is_err() {
+                holder.clear();
+            }
+            let holder = match holder.trim() {
+                "" => String::new(),
+                pid => format!(" (pid {})", pid),
+            };
+            return Err(io::Error::new(
+                err.kind(),
+                format!(
+                    "Another nametag process{} is changing files. Try again when it \
+                     finishes, or use --wait.",
+                    holder
+                ),
+            ));
+        }
+        // Note who holds it, for the message above.
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", process::id())?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[cfg(unix)]
+fn lock(file: &File, wait: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let operation = if wait {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_EX | libc::LOCK_NB
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn lock(file: &File, wait: bool) -> io::Result<()> {
+    if wait {
+        return file.lock();
+    }
+    file.try_lock().map_err(|err| match err {
+        fs::TryLockError::WouldBlock => io::Error::from(io::ErrorKind::WouldBlock),
+        fs::TryLockError::Error(err) => err,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_acquire() {
+        let path = env::temp_dir().join("nametag-lock").join("lock");
+        let held = Lock::acquire(&path, false).unwrap();
+        let err = Lock::acquire(&path, false).unwrap_err();
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+        assert!(err.to_string().contains(&process::id().to_string()));
+        drop(held);
+        Lock::acquire(&path, false).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+// Warnings and errors as written to stderr, apart from the results on
+// stdout so those can be piped. Each is tagged with its level and kind.
+// eg nametag: warning: skipped: a.txt: a[x].txt already exists
+//
+// As json, each is a line of its own.
+// eg {"level":"warning","kind":"skipped","path":"a.txt","message":"a[x].txt already exists"}
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("Expected one of text or json."),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A file left as it was.
+    Skipped,
+    /// A directory that could not be reached.
+    Offline,
+    /// A problem during an unattended run, also kept in the incident log.
+    Incident,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Skipped => "skipped",
+            Kind::Offline => "offline",
+            Kind::Incident => "incident",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record {
+    pub level: Level,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Kind>,
+    /// The file concerned, if there is one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Record {
+    pub fn warning<M: Into<String>>(kind: Kind, path: Option<&Path>, message: M) -> Self {
+        Self {
+            level: Level::Warning,
+            kind: Some(kind),
+            path: path.map(Path::to_path_buf),
+            message: message.into(),
+        }
+    }
+
+    /// An error ending the run.
+    pub fn error<M: Into<String>>(message: M) -> Self {
+        Self {
+            level: Level::Error,
+            kind: None,
+            path: None,
+            message: message.into(),
+        }
+    }
+
+    /// The record as a line for stderr.
+    pub fn render(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Json => serde_json::to_string(self).expect("record serializes"),
+            LogFormat::Text => {
+                let mut line = "nametag: ".to_owned();
+                if self.level == Level::Warning {
+                    line += "warning: ";
+                }
+                if let Some(kind) = self.kind {
+                    line += kind.name();
+                    line += ": ";
+                }
+                if let Some(path) = &self.path {
+                    line += &format!("{}: ", path.display());
+                }
+                line + &self.message
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let record = Record::warning(
+            Kind::Skipped,
+            Some(Path::new("a.txt")),
+            "a[x].txt already exists",
+        );
+        assert_eq!(
+            "nametag: warning: skipped: a.txt: a[x].txt already exists",
+            record.render(LogFormat::Text)
+        );
+        assert_eq!(
+            r#"{"level":"warning","kind":"skipped","path":"a.txt","message":"a[x].txt already exists"}"#,
+            record.render(LogFormat::Json)
+        );
+        let record = Record::error("No transaction is open.");
+        assert_eq!(
+            "nametag: No transaction is open.",
+            record.render(LogFormat::Text)
+        );
+        assert_eq!(
+            r#"{"level":"error","message":"No transaction is open."}"#,
+            record.render(LogFormat::Json)
+        );
+    }
+}
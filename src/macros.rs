@@ -0,0 +1,72 @@
+// Commands under short names, so a team can share invocations taking
+// several flags. Arguments after the name fill $1, $2 and so on, or $@ for
+// every one left, else follow the command.
+// eg alias.done = "add -t done"
+//    alias.file = "add -t $1 -- $@"
+//
+// Names of built in commands always mean those commands.
+use std::ffi::OsString;
+
+use crate::simulate::split_args;
+
+/// The command an alias stands for, given the arguments following it.
+pub fn expand(name: &str, command: &str, args: &[OsString]) -> Result<Vec<OsString>, String> {
+    let words = split_args(command).map_err(|err| format!("Alias \"{}\": {}", name, err))?;
+    let mut used = 0;
+    // Where $@ stands, filled once the numbered arguments are known.
+    let mut rest = None;
+    let mut expanded = Vec::new();
+    for word in words {
+        if word == "$@" {
+            rest = Some(expanded.len());
+            continue;
+        }
+        match word.strip_prefix('$').map(str::parse::<usize>) {
+            Some(Ok(number)) if number > 0 => {
+                let arg = args.get(number - 1).ok_or_else(|| {
+                    format!(
+                        "Alias \"{}\" expects at least {} argument(s).",
+                        name, number
+                    )
+                })?;
+                used = used.max(number);
+                expanded.push(arg.clone());
+            }
+            _ => expanded.push(OsString::from(word)),
+        }
+    }
+    match (rest, used) {
+        (Some(at), _) => {
+            expanded.splice(at..at, args[used..].iter().cloned());
+        }
+        (None, 0) => expanded.extend(args.iter().cloned()),
+        (None, _) => {}
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(
+            Ok(args(&["add", "-t", "done", "--where", "NOT done", "a.txt"])),
+            expand("done", "add -t done --where 'NOT done'", &args(&["a.txt"]))
+        );
+        assert_eq!(
+            Ok(args(&["add", "-t", "x", "--", "a.txt", "b.txt"])),
+            expand("file", "add -t $1 -- $@", &args(&["x", "a.txt", "b.txt"]))
+        );
+        assert_eq!(
+            Ok(args(&["add", "a.txt", "-t", "x"])),
+            expand("tag", "add $@ -t $1", &args(&["x", "a.txt"]))
+        );
+        assert!(expand("file", "add -t $2", &args(&["x"])).is_err());
+    }
+}
@@ -1,35 +1,3287 @@
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use structopt::clap::ErrorKind;
 use structopt::StructOpt;
 
-use nametag::NameTag;
+use nametag::abbreviate::Abbreviations;
+use nametag::alias::Aliases;
+use nametag::audit;
+use nametag::backup;
+use nametag::batch::{self, Batch, Rename, Skipped};
+use nametag::bundle::Bundles;
+use nametag::cache::QueryCache;
+use nametag::cancel::Cancel;
+use nametag::cloud;
+use nametag::collate::Collation;
+use nametag::complete::Completions;
+use nametag::config::Config;
+use nametag::derive::DerivedRules;
+use nametag::doctor;
+use nametag::event::{Event, Output};
+use nametag::fixture::{self, Distribution, Fixture};
+use nametag::format::TagFormat;
+use nametag::fuzz;
+use nametag::graph::{Graph, GraphFormat};
+use nametag::grep;
+use nametag::guard::RootGuard;
+use nametag::i18n::{self, Catalog};
+use nametag::index::Index;
+use nametag::journal::Journal;
+use nametag::label::{self, Label, Registry};
+use nametag::limits::{Limits, Refused};
+use nametag::literal::{self, Literal};
+use nametag::lock::Lock;
+use nametag::log::{Kind, LogFormat, Record};
+use nametag::macros;
+use nametag::manifest::{self, Manifest};
+#[cfg(feature = "metadata")]
+use nametag::metadata;
+use nametag::obfuscate::Obfuscator;
+use nametag::ontology::Ontology;
+use nametag::open;
+use nametag::pattern;
+use nametag::policy::{Forbidden, Policy};
+use nametag::query::Query;
+use nametag::readme::{self, Listed};
+use nametag::redact::Redaction;
+use nametag::resolve::{Problem, Resolution, Resolutions};
+use nametag::risk;
+use nametag::roots::{self, Roots};
+use nametag::series::Series;
+use nametag::simulate;
+use nametag::snapshot::{Change, Snapshot};
+use nametag::summary::{Summary, SummaryFormat};
+use nametag::transaction::Transaction;
+use nametag::trash;
+use nametag::triage::{self, Candidate};
+use nametag::walk::{walk, WalkOptions};
+use nametag::workspace;
+use nametag::{Extension, NameTag};
 
 #[derive(Debug, StructOpt)]
 #[structopt(about = "Work with tags on files, using a defined format.")]
-enum Cli {
+struct Cli {
+    /// Config file to use in place of the default location.
+    #[structopt(long, global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Colorize tags: auto, always or never.
+    #[structopt(long, global = true, default_value = "auto")]
+    color: ColorChoice,
+    /// Take bracketed text in names as part of the title, leaving the file
+    /// alone, when it reads as tags with less than this confidence, from 0
+    /// to 1. Words in the ontology or presets read as tags, capitalized
+    /// words and stopwords as a title. eg "Best of [Live at Wembley].mp3"
+    #[structopt(long, global = true, parse(try_from_str = literal::parse_confidence))]
+    assume_literal_below: Option<f64>,
+    /// Plain lines of text without color or aligned columns, for screen readers and dumb terminals.
+    #[structopt(long, global = true)]
+    plain: bool,
+    /// Refuse to run any command that would modify files.
+    #[structopt(long, global = true)]
+    read_only: bool,
+    /// Mask tags and shorten paths in output, using a redaction profile from config.
+    #[structopt(long, global = true)]
+    redact: Option<String>,
+    /// Limit renames to this many per second, to share a busy disk.
+    #[structopt(long, global = true)]
+    throttle: Option<f64>,
+    /// Run at low CPU and IO priority.
+    #[structopt(long, global = true)]
+    nice: bool,
+    /// Syntax of tags in names: brackets, braces, suffix or a format from config.
+    #[structopt(long, global = true)]
+    format: Option<String>,
+    /// Write emoji tags in names by their ascii alias from config.
+    #[structopt(long, global = true)]
+    ascii: bool,
+    /// Wait for another nametag process changing files to finish, in place of failing.
+    #[structopt(long, global = true)]
+    wait: bool,
+    /// Go ahead with batches scored as high risk, such as clearing tags across many directories.
+    #[structopt(long, global = true)]
+    yes_i_mean_it: bool,
+    /// Change files recursively even under a filesystem root, home or a shallow mount.
+    #[structopt(long, global = true)]
+    allow_root: bool,
+    /// Ask how to settle each rename that would overwrite another file or
+    /// combine forbidden tags, in place of skipping or refusing it. Answers
+    /// are kept for --replay.
+    #[structopt(long, global = true)]
+    interactive: bool,
+    /// Settle renames that would overwrite another file or combine forbidden
+    /// tags as they were settled before, for the same file and problem.
+    #[structopt(long, global = true)]
+    replay: bool,
+    /// Report time taken and files scanned, changed, skipped and failed: text or json.
+    /// Commands changing files report as text by default.
+    #[structopt(long, global = true)]
+    summary: Option<SummaryFormat>,
+    /// Write warnings and errors to stderr as text or json.
+    #[structopt(long, global = true, default_value = "text")]
+    log_format: LogFormat,
+    /// Write results as text or json-lines, an event per line for programs,
+    /// with warnings on stdout among them.
+    #[structopt(long, global = true, default_value = "text")]
+    output: Output,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("Expected one of auto, always or never."),
+        }
+    }
+}
+
+// An edit to the tags of a name. eg add:x
+#[derive(Debug, Clone)]
+enum Op {
+    Add(String),
+    Remove(String),
+}
+
+// Edits separated by commas. eg add:x,remove:y
+#[derive(Debug)]
+struct Ops(Vec<Op>);
+
+impl FromStr for Ops {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut ops = Vec::new();
+        for op in text.split(',').filter(|op| !op.trim().is_empty()) {
+            ops.push(match op.trim().split_once(':') {
+                Some(("add", tag)) if !tag.is_empty() => Op::Add(tag.to_owned()),
+                Some(("remove", tag)) if !tag.is_empty() => Op::Remove(tag.to_owned()),
+                _ => {
+                    return Err(format!(
+                        "Expected add:<tag> or remove:<tag>, got \"{}\"",
+                        op
+                    ))
+                }
+            });
+        }
+        Ok(Ops(ops))
+    }
+}
+
+impl ColorChoice {
+    // Auto colors only a terminal that is not dumb, and honors https://no-color.org
+    fn enabled(self) -> bool {
+        if json_lines() {
+            return false;
+        }
+        match self {
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none()
+                    && std::env::var_os("TERM").is_none_or(|term| term != "dumb")
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Add tags to files.
     Add {
-        #[structopt(short)]
+        #[structopt(short, long = "tag", number_of_values = 1)]
         tags: Vec<String>,
+        /// Add the tags of a preset defined in config.
+        #[structopt(long, number_of_values = 1)]
+        preset: Vec<String>,
+        /// Show the renames without performing them.
+        #[structopt(long)]
+        dry_run: bool,
+        /// With --dry-run, show the changes as a unified diff of a manifest
+        /// of the files with their tags, rather than as pairs of names.
+        #[structopt(long, requires = "dry-run")]
+        diff: bool,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Remove tags from files.
+    Remove {
+        #[structopt(short, long = "tag", number_of_values = 1, required = true)]
+        tags: Vec<String>,
+        /// Show the renames without performing them.
+        #[structopt(long)]
+        dry_run: bool,
+        /// With --dry-run, show the changes as a unified diff of a manifest
+        /// of the files with their tags, rather than as pairs of names.
+        #[structopt(long, requires = "dry-run")]
+        diff: bool,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Print files whose tags satisfy an expression. eg "work AND (urgent OR NOT done)"
+    Query {
+        /// Tags combined with AND, OR, NOT and parentheses. Tags may use * and ? wildcards,
+        /// and name:~text matches names holding the text.
+        expression: Query,
+        /// Files, or directories whose files are searched. Defaults to the
+        /// root of the workspace, else the current directory.
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+        /// Search directories recursively.
+        #[structopt(short, long)]
+        recursive: bool,
+        /// Descend at most this many directories when searching recursively.
+        #[structopt(long)]
+        max_depth: Option<usize>,
+        /// Print the tags of each file alongside its path.
+        #[structopt(long)]
+        tags: bool,
+        /// Answer from the index covering each path, searching it recursively.
+        #[structopt(long, conflicts_with = "max-depth")]
+        use_index: bool,
+        /// Search again rather than reuse the result of the same query, when
+        /// no directory has changed since.
+        #[structopt(long)]
+        no_cache: bool,
+        /// Answer from a saved snapshot, as files were named when it was
+        /// taken, searching each path recursively.
+        #[structopt(long, conflicts_with_all = &["use-index", "max-depth"])]
+        as_of: Option<String>,
+        /// Print absolute paths, rather than paths relative to the path each
+        /// file was found under.
+        #[structopt(long)]
+        absolute: bool,
+        /// Include trashed files, which are left out unless the expression
+        /// names them.
+        #[structopt(long)]
+        trashed: bool,
+        /// Open each file found, with the opener the ontology gives its
+        /// tags, else the system's default.
+        #[structopt(long, conflicts_with = "as-of")]
+        open: bool,
+        /// Print how each term of the expression evaluated for each file
+        /// named, rather than searching.
+        #[structopt(long, conflicts_with_all = &["use-index", "as-of", "open"])]
+        explain: bool,
+        /// Check the expression rather than searching, printing it in its
+        /// canonical form along with any part that can never match.
+        #[structopt(long, conflicts_with = "explain")]
+        check: bool,
+    },
+    /// Tag files as trashed, with today's date, in place of deleting them.
+    /// They are left out of queries until swept.
+    Trash {
+        /// Show the renames without performing them.
+        #[structopt(long)]
+        dry_run: bool,
+        #[structopt(parse(from_os_str), required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Delete files under a directory trashed long enough ago, or move them
+    /// into an archive directory. Deleted files cannot be restored.
+    Sweep {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+        /// Age at which trashed files are swept. eg 30d or 2w
+        #[structopt(long, parse(try_from_str = trash::parse_days))]
+        older_than: i64,
+        /// Move files into this directory, under their paths relative to
+        /// the one swept, instead of deleting them.
+        #[structopt(long, parse(from_os_str))]
+        archive: Option<PathBuf>,
+        /// Show the files that would be swept without touching them.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Suggest tidying the tags of every file under directories: merging
+    /// tags spelled alike, reviewing tags used once and shortening long
+    /// names. Each suggestion comes with a command to run.
+    Doctor {
+        /// Files, or directories searched recursively. Defaults to the current directory.
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+        /// Report names longer than this many bytes. Defaults to max_length in config, else 255.
+        #[structopt(long)]
+        max_length: Option<usize>,
+    },
+    /// Find tagged file names in text read from stdin, eg logs or old
+    /// manifests, and print each with its tags as a line of json.
+    GrepTags {},
+    /// Edit the tags of names read from stdin, one per line, writing the new
+    /// names to stdout. No files are read or renamed.
+    Filter {
+        /// Edits to make, in order. eg 'add:x,remove:y'
+        #[structopt(long, number_of_values = 1, required = true)]
+        op: Vec<Ops>,
+    },
+    /// Check random, awkward names keep their tags when rewritten in the tag
+    /// format in use, printing the smallest names that do not.
+    FuzzCheck {
+        #[structopt(long, default_value = "10000")]
+        iterations: usize,
+        /// Seed for the names generated, to repeat a run. Defaults to the time.
+        #[structopt(long)]
+        seed: Option<u64>,
+    },
+    /// Draw a cloud of the tags of files under a directory, the most used
+    /// in bold and the least dimmed.
+    Cloud {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Complete a partly typed tag, from the tags of files under a directory
+    /// and the ontology, the most used first. For editors and file managers.
+    Complete {
+        /// Start of the tag, matched ignoring case.
+        #[structopt(default_value = "")]
+        prefix: String,
+        #[structopt(long, parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+        /// Most completions to give.
+        #[structopt(long, default_value = "10")]
+        limit: usize,
+    },
+    /// Export the network of tags used together on files under a directory,
+    /// for Graphviz or Gephi to draw.
+    Graph {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+        /// File format: dot or gexf.
+        #[structopt(long, default_value = "dot")]
+        graph_format: GraphFormat,
+    },
+    /// Print files under a directory still without tags, most informative
+    /// first: large, recent, named by other files or of unusual types.
+    Triage {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        dir: PathBuf,
+        /// Print at most this many files.
+        #[structopt(long)]
+        limit: Option<usize>,
+    },
+    /// Rehearse commands on a copy of the names under a directory, each file
+    /// empty, and show how the names would end up. eg simulate photos -- "add -t trip a.jpg"
+    ///
+    /// Commands are given --config, --format, --ascii, --assume-literal-below,
+    /// --allow-root, --yes-i-mean-it, --output and --log-format as given to
+    /// simulate. Other global flags are dropped.
+    Simulate {
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+        /// Commands to run in turn from the copy, each quoted as one argument.
+        #[structopt(last = true, required = true)]
+        commands: Vec<String>,
+    },
+    /// Create a tree of synthetic tagged files, for benchmarking and trying out config.
+    GenFixture {
+        /// Files to create.
+        #[structopt(long, default_value = "1000")]
+        files: usize,
+        /// Distinct tags to draw from.
+        #[structopt(long, default_value = "20")]
+        tags: usize,
+        /// Most tags on a single file.
+        #[structopt(long, default_value = "3")]
+        tags_per_file: usize,
+        /// Share of names with broken tag blocks. eg 1%
+        #[structopt(long, default_value = "0%", parse(try_from_str = fixture::parse_ratio))]
+        malformed: f64,
+        /// How often each tag is used: uniform, or zipf where a few tags are far more common.
+        #[structopt(long, default_value = "zipf")]
+        distribution: Distribution,
+        /// Files in each directory of the tree.
+        #[structopt(long, default_value = "100")]
+        files_per_dir: usize,
+        /// Seed for the tree, to create it again. Defaults to the time.
+        #[structopt(long)]
+        seed: Option<u64>,
+        /// Directory to create the tree in, which must be empty or missing.
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+    },
+    /// Add tags to files automatically.
+    Autotag {
+        /// Add tags from the derived rules in config.
+        #[structopt(long)]
+        derived: bool,
         #[structopt(parse(from_os_str))]
         paths: Vec<PathBuf>,
     },
-    Remove {},
-    Query {},
+    /// Finish, or roll back, a batch that was interrupted.
+    Resume {
+        /// Undo the renames already performed instead of finishing the batch.
+        #[structopt(long)]
+        rollback: bool,
+    },
+    /// Show files with their tags.
+    Show {
+        /// Also show every file in the roots of a collection defined in config.
+        #[structopt(long)]
+        collection: Option<String>,
+        /// Recall files on unmounted collection roots from their latest snapshot.
+        #[structopt(long)]
+        catalog: bool,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// List the tags used by files, with their ontology descriptions.
+    List {
+        /// Also list every file in the roots of a collection defined in config.
+        #[structopt(long)]
+        collection: Option<String>,
+        /// Recall files on unmounted collection roots from their latest snapshot.
+        #[structopt(long)]
+        catalog: bool,
+        /// Files, or directories whose files are listed.
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+        /// List tags from a saved snapshot, as they were when it was taken,
+        /// of files at or below each path.
+        #[structopt(long, conflicts_with_all = &["collection", "catalog"])]
+        as_of: Option<String>,
+    },
+    /// Report problems with the tags on files, such as forbidden combinations.
+    Lint {
+        /// Warn about tags missing from the ontology.
+        #[structopt(long)]
+        strict: bool,
+        /// Files, or directories whose files are checked.
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Tidy a directory tree, suitable for running on a timer.
+    ///
+    /// Rewrites file names into their canonical form (sorted, single spaced
+    /// tags), sweeps trashed files when asked, refreshes any index covering
+    /// the tree and reports any unfinished batch.
+    Maintain {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+        /// Delete files trashed at least this long ago, as sweep does. eg 30d
+        #[structopt(long, parse(try_from_str = trash::parse_days))]
+        sweep_older_than: Option<i64>,
+    },
+    /// Write an INDEX.md into each directory, listing its files with their tags.
+    IndexFiles {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+    },
+    /// Rewrite the tags of every file under a directory in another format.
+    /// Not available while a transaction is open.
+    Rescheme {
+        /// Format names are written in now: brackets, braces, suffix, a format
+        /// from config, or delimiters around "..". eg '[..]'
+        #[structopt(long)]
+        from: String,
+        /// Format to write names in. eg '{..}'
+        #[structopt(long)]
+        to: String,
+        #[structopt(parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+    },
+    /// Keep an index of the tags under a directory, for query --use-index.
+    Index(IndexCommand),
+    /// Print labels for archive boxes, with a summary of each directory's tags
+    /// and a short code that locate resolves back to the directory.
+    Label {
+        #[structopt(parse(from_os_str), required = true)]
+        dirs: Vec<PathBuf>,
+        /// Pdf file to write.
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+    },
+    /// Find the directory a label code was printed for.
+    Locate { code: String },
+    /// Save and compare the tags of a directory over time.
+    Snapshot(SnapshotCommand),
+    /// Compare the tags under a directory to a recorded baseline, failing if
+    /// any protected file changed.
+    Check {
+        /// Baseline file, as recorded with --update.
+        #[structopt(long, parse(from_os_str))]
+        baseline: PathBuf,
+        /// Record the current state as the baseline instead of checking it.
+        #[structopt(long)]
+        update: bool,
+        /// Relative paths of protected files. Defaults to protected in config, else every file.
+        #[structopt(long, number_of_values = 1)]
+        protect: Vec<String>,
+        /// Directory to check. Defaults to the root the baseline was recorded from.
+        #[structopt(parse(from_os_str))]
+        root: Option<PathBuf>,
+    },
+    /// Tag a series of files from the numbering in their names. eg S01E03
+    Series {
+        /// Fields to read from names. eg 'S{season=}E{ep=}'
+        #[structopt(long)]
+        pattern: Series,
+        /// Number this field through the files in order, from --start.
+        #[structopt(long)]
+        renumber: Option<String>,
+        #[structopt(long, default_value = "1")]
+        start: u64,
+        /// Rewrite the numbering in names consistently, zero padded to --width.
+        #[structopt(long)]
+        render: bool,
+        #[structopt(long, default_value = "2")]
+        width: usize,
+        /// Files, or directories whose files are numbered in name order.
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Save named sets of tags, and add or remove them together.
+    Bundle(BundleCommand),
+    /// Stage tag changes to review, then apply with commit.
+    Stage(StageCommand),
+    /// Show the staged changes.
+    Status {},
+    /// Apply the staged changes, recording a message in the audit log.
+    Commit {
+        #[structopt(short, long)]
+        message: String,
+    },
+    /// Gather the renames of several commands, to review and apply as one batch.
+    Tx(TxCommand),
+    /// Show the batches of renames made, from the audit log.
+    History {
+        /// Print each batch as a patch to a manifest of the files under root
+        /// and their tags, for tracking in version control with "git am".
+        #[structopt(long)]
+        as_patch: bool,
+        /// Directory the manifest covers. Defaults to the current directory.
+        #[structopt(long, parse(from_os_str))]
+        root: Option<PathBuf>,
+    },
+    /// Apply the tags of an exemplar file to other files, eg new files joining a series.
+    CopyFrom {
+        #[structopt(parse(from_os_str))]
+        exemplar: PathBuf,
+        #[structopt(parse(from_os_str), required = true)]
+        targets: Vec<PathBuf>,
+        /// Add the exemplar's tags alongside existing tags. The default.
+        // Only spelled out for clarity, as merging is what happens without --replace.
+        #[allow(dead_code)]
+        #[structopt(long, conflicts_with = "replace")]
+        merge: bool,
+        /// Replace existing tags with the exemplar's tags.
+        #[structopt(long)]
+        replace: bool,
+    },
+    /// Copy the tags of a file to the clipboard.
+    #[cfg(feature = "clipboard")]
+    CopyTags {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Add the tags held in the clipboard to files.
+    #[cfg(feature = "clipboard")]
+    PasteTags {
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Keep tags in names and in file system metadata consistent, so file
+    /// managers can show them.
+    #[cfg(feature = "metadata")]
+    Sync {
+        /// name-to-xattr, xattr-to-name, or merge to give both every tag.
+        #[structopt(long)]
+        direction: Direction,
+        /// Files, or directories whose files are synced recursively.
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[cfg(feature = "metadata")]
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    NameToXattr,
+    XattrToName,
+    Merge,
+}
+
+#[cfg(feature = "metadata")]
+impl FromStr for Direction {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "name-to-xattr" => Ok(Direction::NameToXattr),
+            "xattr-to-name" => Ok(Direction::XattrToName),
+            "merge" => Ok(Direction::Merge),
+            _ => Err("Expected one of name-to-xattr, xattr-to-name or merge."),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum SnapshotCommand {
+    /// Record the tags of every file under a directory.
+    Save {
+        name: String,
+        #[structopt(parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+    },
+    /// Report files whose tags changed, appeared or disappeared between two snapshots.
+    Diff { before: String, after: String },
+}
+
+#[derive(Debug, StructOpt)]
+enum IndexCommand {
+    /// Index every file under a directory, replacing any index of it. A
+    /// build interrupted part way resumes where it stopped.
+    Build {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+    },
+    /// Refresh an index, reading only directories changed since it was built.
+    Update {
+        #[structopt(parse(from_os_str), default_value = ".")]
+        root: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum BundleCommand {
+    /// Save tags under a name, replacing any bundle of that name.
+    Save {
+        name: String,
+        #[structopt(required = true)]
+        tags: Vec<String>,
+    },
+    /// Add the tags of a bundle to files.
+    Apply {
+        name: String,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Remove the tags of a bundle from files.
+    Remove {
+        name: String,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// List saved bundles with their tags.
+    List {},
+}
+
+#[derive(Debug, StructOpt)]
+enum StageCommand {
+    /// Stage adding tags to files.
+    Add {
+        #[structopt(short, long = "tag", number_of_values = 1, required = true)]
+        tags: Vec<String>,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+    /// Stage removing tags from files.
+    Remove {
+        #[structopt(short, long = "tag", number_of_values = 1, required = true)]
+        tags: Vec<String>,
+        #[structopt(parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum TxCommand {
+    /// Start recording renames instead of performing them.
+    Begin {},
+    /// Show the renames recorded so far.
+    Status {},
+    /// Perform every recorded rename as one batch.
+    Commit {},
+    /// Discard the recorded renames.
+    Abort {},
+}
+
+impl Command {
+    // Whether the command renames or otherwise changes files
+    fn mutates(&self) -> bool {
+        match self {
+            Command::Add { dry_run, .. }
+            | Command::Remove { dry_run, .. }
+            | Command::Trash { dry_run, .. }
+            | Command::Sweep { dry_run, .. } => !dry_run,
+            Command::Autotag { .. }
+            | Command::Resume { .. }
+            | Command::Maintain { .. }
+            | Command::Series { .. }
+            | Command::Bundle(BundleCommand::Apply { .. })
+            | Command::Bundle(BundleCommand::Remove { .. })
+            | Command::Tx(TxCommand::Commit {})
+            | Command::Commit { .. }
+            | Command::IndexFiles { .. }
+            | Command::Rescheme { .. }
+            | Command::CopyFrom { .. }
+            | Command::GenFixture { .. } => true,
+            #[cfg(feature = "clipboard")]
+            Command::PasteTags { .. } => true,
+            #[cfg(feature = "clipboard")]
+            Command::CopyTags { .. } => false,
+            #[cfg(feature = "metadata")]
+            Command::Sync { .. } => true,
+            Command::Query { .. }
+            | Command::Show { .. }
+            | Command::List { .. }
+            | Command::Lint { .. }
+            | Command::Snapshot(_)
+            | Command::Bundle(BundleCommand::Save { .. })
+            | Command::Bundle(BundleCommand::List {})
+            | Command::Stage(_)
+            | Command::Status {}
+            | Command::Label { .. }
+            | Command::Index(_)
+            | Command::Locate { .. }
+            | Command::GrepTags {}
+            | Command::Doctor { .. }
+            | Command::History { .. }
+            | Command::Filter { .. }
+            | Command::FuzzCheck { .. }
+            | Command::Simulate { .. }
+            | Command::Triage { .. }
+            | Command::Cloud { .. }
+            | Command::Complete { .. }
+            | Command::Graph { .. }
+            | Command::Tx(TxCommand::Begin {})
+            | Command::Tx(TxCommand::Status {})
+            | Command::Tx(TxCommand::Abort {})
+            | Command::Check { .. } => false,
+        }
+    }
 }
 
 fn main() {
-    match Cli::from_args() {
-        Cli::Add { tags, paths } => {
-            let nametags = paths
-                .iter()
-                .map(|path| NameTag::new(path))
+    let started = Instant::now();
+    let cli = match parse_args(env::args_os().collect()) {
+        Ok(cli) => cli,
+        Err(err) => {
+            warn(Record::error(err.to_string()));
+            std::process::exit(1);
+        }
+    };
+    let report = match cli.summary {
+        Some(format) => Some(format),
+        None if cli.command.mutates() => Some(SummaryFormat::Text),
+        None => None,
+    };
+    let _ = LOG_FORMAT.set(cli.log_format);
+    let _ = OUTPUT.set(cli.output);
+    if let Some(language) = i18n::language() {
+        let dir = cli.config.clone().or_else(Config::default_path);
+        match dir.map(|config| Catalog::load(&config.with_file_name("locale"), &language)) {
+            Some(Ok(catalog)) => {
+                let _ = CATALOG.set(catalog);
+            }
+            Some(Err(err)) => warn(Record::warning(Kind::Incident, None, err.to_string())),
+            None => {}
+        }
+    }
+    let summary = RefCell::new(Summary::default());
+    let result = run(cli, &summary);
+    if let Some(format) = report {
+        eprintln!("{}", summary.borrow().render(started.elapsed(), format));
+    }
+    if let Err(err) = result {
+        warn(Record::error(err.to_string()));
+        std::process::exit(1);
+    }
+}
+
+// Parse arguments, expanding an alias from config given in place of a command.
+fn parse_args(mut args: Vec<OsString>) -> Result<Cli, Box<dyn Error>> {
+    let mut expanded = BTreeSet::new();
+    loop {
+        let err = match Cli::from_iter_safe(&args) {
+            Ok(cli) => return Ok(cli),
+            Err(err) => err,
+        };
+        // Clap reports a word where a command goes as any of these,
+        // depending on whether it resembles a command.
+        let name = match (&err.kind, &err.info) {
+            (
+                ErrorKind::InvalidSubcommand
+                | ErrorKind::UnrecognizedSubcommand
+                | ErrorKind::UnknownArgument,
+                Some(info),
+            ) if info.first().is_some_and(|word| !word.starts_with('-')) => info[0].clone(),
+            _ => err.exit(),
+        };
+        let at = match args.iter().position(|arg| *arg == *name) {
+            Some(at) => at,
+            None => err.exit(),
+        };
+        // Config named before the alias, as it is needed to read it.
+        let config_path = args[..at].iter().enumerate().find_map(|(index, arg)| {
+            match arg.to_str()?.strip_prefix("--config") {
+                Some("") => args.get(index + 1).map(PathBuf::from),
+                Some(rest) => Some(PathBuf::from(rest.strip_prefix('=')?)),
+                None => None,
+            }
+        });
+        let config = Config::load(config_path.as_deref())?;
+        let command = match config.alias.get(&name) {
+            Some(command) => command,
+            None => err.exit(),
+        };
+        if !expanded.insert(name.clone()) {
+            return Err(format!("Alias \"{}\" expands to itself.", name).into());
+        }
+        let rest = args.split_off(at + 1);
+        args.pop();
+        args.extend(macros::expand(&name, command, &rest)?);
+    }
+}
+
+fn run(cli: Cli, summary: &RefCell<Summary>) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::load(cli.config.as_deref())?;
+    if cli.format.is_some() {
+        config.format = cli.format.clone();
+    }
+    config.ascii |= cli.ascii;
+    config.walk.cancel = handle_interrupt();
+    if (cli.read_only || config.read_only) && cli.command.mutates() {
+        return Err("Refusing to modify files in read-only mode.".into());
+    }
+    if cli.nice {
+        batch::lower_priority()?;
+    }
+    // Held until the command completes.
+    let lock = match (cli.command.mutates(), Lock::default_path()) {
+        (true, Some(path)) => Some(Lock::acquire(&path, cli.wait)?),
+        _ => None,
+    };
+    if lock.is_some() {
+        repair_logs()?;
+    }
+    let mut tagger = Tagger::new(&config)?;
+    if let Some(below) = cli.assume_literal_below {
+        let ontology = load_ontology(cli.config.as_deref())?;
+        let presets = config.preset.values().flatten().cloned();
+        tagger.literal = Some(Literal::new(
+            below,
+            ontology.tag.keys().cloned().chain(presets),
+        ));
+    }
+    let apply = Apply {
+        throttle: cli.throttle,
+        chunk_size: config.chunk_size,
+        cancel: config.walk.cancel.clone(),
+        confirmed: cli.yes_i_mean_it,
+        summary,
+        format: tagger.format.clone(),
+        extension: config.extension,
+        ontology: if cli.command.mutates() {
+            load_ontology(cli.config.as_deref())?
+        } else {
+            Ontology::default()
+        },
+        backup_dir: config.backup_dir.clone().or_else(backup::default_dir),
+    };
+    let resolver = match (cli.interactive || cli.replay, Resolutions::default_path()) {
+        (false, _) => None,
+        (true, None) => return Err("No state directory available.".into()),
+        (true, Some(path)) => {
+            if cli.interactive && !io::stdin().is_terminal() {
+                return Err("Answers for --interactive must come from a terminal.".into());
+            }
+            Some(Resolver {
+                resolutions: RefCell::new(Resolutions::load(&path)?),
+                path,
+                interactive: cli.interactive,
+                replay: cli.replay,
+            })
+        }
+    };
+    let guard = match cli.allow_root {
+        true => None,
+        false => Some(config.root_guard()),
+    };
+    let redaction = match &cli.redact {
+        Some(profile) => config.redaction(profile)?,
+        None => Redaction::default(),
+    };
+    if cli.command.mutates() && !config.sensitive.is_empty() && tagger.obfuscator.is_none() {
+        return Err(
+            "Sensitive tags are configured, but no key is available to hide them. \
+             Set NAMETAG_KEY or key_file in config."
+                .into(),
+        );
+    }
+    match cli.command {
+        Command::Add {
+            mut tags,
+            preset,
+            dry_run,
+            diff,
+            paths,
+        } => {
+            for name in preset {
+                tags.extend(config.expand_preset(&name)?);
+            }
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), paths, |nametag| {
+                for tag in &tags {
+                    nametag.add_tag(tag);
+                }
+            })?;
+            apply_or_show(&mut batch, dry_run, diff, &tagger, &apply)?;
+            if dry_run {
+                return Ok(());
+            }
+        }
+        Command::Remove {
+            tags,
+            dry_run,
+            diff,
+            paths,
+        } => {
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), paths, |nametag| {
+                for tag in &tags {
+                    nametag.remove_tag(tag);
+                }
+            })?;
+            apply_or_show(&mut batch, dry_run, diff, &tagger, &apply)?;
+            if dry_run {
+                return Ok(());
+            }
+        }
+        Command::Trash { dry_run, paths } => {
+            let tags = trash::tags();
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), paths, |nametag| {
+                for tag in &tags {
+                    nametag.add_tag(tag.as_str());
+                }
+            })?;
+            apply_or_show(&mut batch, dry_run, false, &tagger, &apply)?;
+            if dry_run {
+                return Ok(());
+            }
+        }
+        Command::Sweep {
+            dir,
+            older_than,
+            archive,
+            dry_run,
+        } => {
+            check_root(guard.as_ref(), &dir)?;
+            // Files already archived beneath the directory stay where they are.
+            let archived_within = archive
+                .as_ref()
+                .and_then(|archive| archive.canonicalize().ok());
+            // Archiving is a batch of renames like any other, while deletions
+            // are only recorded in the audit log.
+            let mut batch = Batch::new();
+            let mut removing = Vec::new();
+            for path in walk(&dir, &config.walk)?.files {
+                config.walk.cancel.check()?;
+                if !trash::expired(&tagger.file_tags(&path)?, older_than) {
+                    continue;
+                }
+                if let Some(within) = &archived_within {
+                    if path.canonicalize()?.starts_with(within) {
+                        continue;
+                    }
+                }
+                let archive = match &archive {
+                    Some(archive) => archive,
+                    None => {
+                        emit(redaction.path(&path).display());
+                        removing.push(path);
+                        continue;
+                    }
+                };
+                let to = archive.join(path.strip_prefix(&dir).unwrap_or(&path));
+                emit(format!(
+                    "{} -> {}",
+                    redaction.path(&path).display(),
+                    redaction.path(&to).display()
+                ));
+                if to.exists() {
+                    warn(Record::warning(
+                        Kind::Skipped,
+                        Some(&path),
+                        tr("Already in the archive: {}", &[&to.display()]),
+                    ));
+                    batch.skip();
+                    continue;
+                }
+                batch.push(path, to);
+            }
+            if !dry_run {
+                for rename in batch.renames() {
+                    if let Some(parent) = rename.to.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                }
+                apply_batch(&mut batch, &apply)?;
+                remove_logged(&removing)?;
+            }
+        }
+        Command::Query {
+            expression,
+            mut paths,
+            recursive,
+            max_depth,
+            tags,
+            use_index,
+            no_cache,
+            as_of,
+            absolute,
+            trashed,
+            open,
+            explain,
+            check,
+        } => {
+            if check {
+                emit(expression.normalize());
+                let contradictions = expression.contradictions();
+                for contradiction in &contradictions {
+                    emit(format!("Never matches: {}", contradiction));
+                }
+                if !contradictions.is_empty() {
+                    return Err(format!("{} problem(s) found", contradictions.len()).into());
+                }
+                return Ok(());
+            }
+            let ontology = match open || tags {
+                true => load_ontology(cli.config.as_deref())?,
+                false => Ontology::default(),
+            };
+            let color = cli.color.enabled() && !cli.plain;
+            let mut expression = tagger.aliases.widen(&expression);
+            if !trashed {
+                expression = trash::hide(expression);
+            }
+            if explain {
+                if paths.is_empty() {
+                    return Err("Name the files to explain the query for.".into());
+                }
+                for path in expand_paths(&paths, &config.walk)? {
+                    let nametag = tagger.parse(&path)?;
+                    let explanation = match tagger.is_literal(&nametag) {
+                        true => {
+                            warn(Record::warning(
+                                Kind::Skipped,
+                                Some(&path),
+                                tr("Tags read as part of the title, so none are matched", &[]),
+                            ));
+                            expression.explain::<&str>(&[], &file_name(&path)?.to_string_lossy())
+                        }
+                        false => expression.explain_name(&nametag),
+                    };
+                    emit(redaction.path(&path).display());
+                    emit_block(explanation);
+                }
+                return Ok(());
+            }
+            if paths.is_empty() {
+                paths.push(workspace::current().unwrap_or_else(|| PathBuf::from(".")));
+            }
+            let roots = Roots::new(&paths, absolute)?;
+            if let Some(name) = as_of {
+                for found in snapshot_files(&name, &paths, &tagger)? {
+                    let file_tags = tagger.recorded_tags(&found)?;
+                    let name = tagger.parse_name(&found)?.untagged();
+                    if !expression.matches_named(&file_tags, &name.to_string_lossy()) {
+                        continue;
+                    }
+                    let shown = file_tags.iter().map(|tag| redaction.tag(tag));
+                    emit_found(
+                        &redaction.path(&roots.show(&found)),
+                        tags.then(|| shown.collect::<Vec<_>>()).as_deref(),
+                        &ontology,
+                        color,
+                    );
+                }
+                return Ok(());
+            }
+            if use_index {
+                for path in &paths {
+                    let index = Index::find(path)?.ok_or_else(|| {
+                        format!(
+                            "{}: Not indexed, run \"nametag index build\"",
+                            path.display()
+                        )
+                    })?;
+                    let path = fs::canonicalize(path)?;
+                    for found in index.search(&expression, |tag| tagger.reveal_tag(tag)) {
+                        if !found.starts_with(&path) {
+                            continue;
+                        }
+                        let shown = match tags {
+                            true => Some(
+                                tagger
+                                    .file_tags(&found)?
+                                    .iter()
+                                    .map(|tag| redaction.tag(tag))
+                                    .collect::<Vec<_>>(),
+                            ),
+                            false => None,
+                        };
+                        emit_found(
+                            &redaction.path(&roots.show(&found)),
+                            shown.as_deref(),
+                            &ontology,
+                            color,
+                        );
+                        if open {
+                            open_file(&ontology, &tagger.file_tags(&found)?, &found)?;
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            // Results are cached for directories only, as named files are
+            // not looked at again.
+            let cache = match (no_cache, QueryCache::default_path()) {
+                (false, Some(dir)) if paths.iter().all(|path| path.is_dir()) => {
+                    Some(QueryCache::new(dir))
+                }
+                _ => None,
+            };
+            let key = QueryCache::key(&[
+                &env::current_dir()?.to_string_lossy(),
+                &format!("{:?}", paths),
+                &format!("{:?}", expression),
+                &format!("{:?} {:?} {:?}", recursive, max_depth, config.walk),
+                &format!("{:?} {:?}", tagger.format, config.extension),
+                &format!("{:?}", cli.assume_literal_below),
+                &tagger
+                    .obfuscator
+                    .as_ref()
+                    .map(Obfuscator::fingerprint)
+                    .unwrap_or_default(),
+            ]);
+            let cached = cache.as_ref().and_then(|cache| cache.get(&key));
+            let matched = match cached {
+                Some(matched) => matched,
+                None => {
+                    let (files, dirs) = if recursive || max_depth.is_some() {
+                        let (mut files, mut dirs) = (Vec::new(), Vec::new());
+                        for path in &paths {
+                            if path.is_dir() {
+                                let options = WalkOptions {
+                                    max_depth: max_depth.or(config.walk.max_depth),
+                                    ..config.walk.clone()
+                                };
+                                let found = walk(path, &options)?;
+                                files.extend(found.files);
+                                dirs.extend(found.dirs);
+                            } else {
+                                files.push(path.clone());
+                            }
+                        }
+                        (files, dirs)
+                    } else {
+                        (expand_paths(&paths, &config.walk)?, paths.clone())
+                    };
+                    let mut matched = Vec::new();
+                    for path in files {
+                        if tagger.satisfies(&expression, &tagger.parse(&path)?) {
+                            matched.push(path);
+                        }
+                    }
+                    if let Some(cache) = &cache {
+                        if let Err(err) = cache.put(&key, &dirs, &matched) {
+                            warn(Record::warning(
+                                Kind::Incident,
+                                None,
+                                format!("Could not cache query: {}", err),
+                            ));
+                        }
+                    }
+                    matched
+                }
+            };
+            for path in matched {
+                let shown = match tags {
+                    true => Some(
+                        tagger
+                            .file_tags(&path)?
+                            .iter()
+                            .map(|tag| redaction.tag(tag))
+                            .collect::<Vec<_>>(),
+                    ),
+                    false => None,
+                };
+                emit_found(
+                    &redaction.path(&roots.show(&path)),
+                    shown.as_deref(),
+                    &ontology,
+                    color,
+                );
+                if open {
+                    open_file(&ontology, &tagger.file_tags(&path)?, &path)?;
+                }
+            }
+        }
+        Command::Doctor {
+            mut paths,
+            max_length,
+        } => {
+            if paths.is_empty() {
+                paths.push(PathBuf::from("."));
+            }
+            let mut files = Vec::new();
+            for path in &paths {
+                let found = if path.is_dir() {
+                    walk(path, &config.walk)?.files
+                } else {
+                    vec![path.clone()]
+                };
+                for path in found {
+                    let tags = tagger.file_tags(&path)?;
+                    files.push((path, tags));
+                }
+            }
+            let max_length = max_length.or(config.max_length).unwrap_or(255);
+            let suggestions = doctor::diagnose(&files, max_length, config.max_length.is_some());
+            for suggestion in &suggestions {
+                emit(&suggestion.problem);
+                if let Some(command) = &suggestion.command {
+                    emit(format!("  {}", command));
+                }
+            }
+            if suggestions.is_empty() {
+                emit("No suggestions.");
+            }
+        }
+        Command::GrepTags {} => {
+            for line in io::stdin().lock().lines() {
+                let line = line?;
+                for name in grep::names(&line) {
+                    let mut nametag = tagger.parse_name(Path::new(name))?;
+                    tagger.reveal(&mut nametag);
+                    let tags = nametag
+                        .get_tags()
+                        .map(|tag| redaction.tag(&tag.to_string_lossy()))
+                        .collect::<Vec<_>>();
+                    let found = serde_json::json!({
+                        "path": redaction.path(Path::new(name)).display().to_string(),
+                        "tags": tags,
+                    });
+                    emit(found);
+                }
+            }
+        }
+        Command::Filter { op } => {
+            let ops = op.into_iter().flat_map(|ops| ops.0).collect::<Vec<_>>();
+            let stdout = io::stdout();
+            let mut out = stdout.lock();
+            for line in io::stdin().lock().lines() {
+                let line = line?;
+                if line.is_empty() {
+                    writeln!(out)?;
+                    continue;
+                }
+                let name = tagger.rename(Path::new(&line), |nametag| {
+                    for op in &ops {
+                        match op {
+                            Op::Add(tag) => nametag.add_tag(tag),
+                            Op::Remove(tag) => nametag.remove_tag(tag),
+                        }
+                    }
+                })?;
+                writeln!(out, "{}", name.display())?;
+            }
+        }
+        Command::GenFixture {
+            files,
+            tags,
+            tags_per_file,
+            malformed,
+            distribution,
+            files_per_dir,
+            seed,
+            dir,
+        } => {
+            if dir.exists() && fs::read_dir(&dir)?.next().is_some() {
+                return Err(format!("{} is not empty", dir.display()).into());
+            }
+            let seed = match seed {
+                Some(seed) => seed,
+                None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+            };
+            let fixture = Fixture {
+                files,
+                tags,
+                tags_per_file,
+                malformed,
+                distribution,
+                files_per_dir,
+            };
+            let generated = fixture.generate(&mut fuzz::Rng::new(seed), &tagger.format);
+            for file in &generated {
+                let path = dir.join(&file.path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::File::create(&path)?;
+            }
+            emit(format!(
+                "Created {} files, {} malformed, with seed {}.",
+                generated.len(),
+                generated.iter().filter(|file| file.malformed).count(),
+                seed
+            ));
+        }
+        Command::Cloud { dir } => {
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let mut counts = BTreeMap::new();
+            for path in walk(&dir, &config.walk)?.files {
+                for tag in tagger.file_tags(&path)? {
+                    *counts.entry(redaction.tag(&tag)).or_insert(0) += 1;
+                }
+            }
+            let mut counts = counts.into_iter().collect::<Vec<_>>();
+            counts.sort_by(|(a, _), (b, _)| config.collation.compare(a, b));
+            let width = env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.parse().ok())
+                .unwrap_or(80);
+            let color = cli.color.enabled() && !cli.plain;
+            emit_block(cloud::render(&counts, width, color.then_some(&ontology)));
+        }
+        Command::Complete { prefix, dir, limit } => {
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let mut completions = Completions::default();
+            for path in walk(&dir, &config.walk)?.files {
+                completions.add(&tagger.file_tags(&path)?);
+            }
+            for tag in ontology.tag.keys() {
+                completions.know(tag);
+            }
+            for (tag, _) in completions.complete(&prefix, limit) {
+                emit(redaction.tag(tag));
+            }
+        }
+        Command::Graph { dir, graph_format } => {
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let mut graph = Graph::default();
+            for path in walk(&dir, &config.walk)?.files {
+                let tags = tagger.file_tags(&path)?;
+                graph.add_file(
+                    &tags
+                        .iter()
+                        .map(|tag| redaction.tag(tag))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            emit_block(graph.render(graph_format, &ontology));
+        }
+        Command::Triage { dir, limit } => {
+            let files = walk(&dir, &config.walk)?.files;
+            let mut candidates = Vec::new();
+            for path in &files {
+                if tagger.file_tags(path)?.is_empty() {
+                    candidates.push(Candidate::read(path)?);
+                }
+            }
+            triage::count_references(&mut candidates, &files)?;
+            let ranked = triage::rank(candidates);
+            for candidate in ranked.iter().take(limit.unwrap_or(usize::MAX)) {
+                emit(redaction.path(&candidate.path).display());
+            }
+        }
+        Command::Simulate { dir, commands } => {
+            let sandbox = env::temp_dir().join(format!("nametag-simulate-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&sandbox);
+            let mut flags = Vec::new();
+            if let Some(format) = &cli.format {
+                flags.extend(["--format".to_owned(), format.clone()]);
+            }
+            if let Some(below) = cli.assume_literal_below {
+                flags.extend(["--assume-literal-below".to_owned(), below.to_string()]);
+            }
+            for (flag, given) in [
+                ("--ascii", cli.ascii),
+                ("--allow-root", cli.allow_root),
+                ("--yes-i-mean-it", cli.yes_i_mean_it),
+            ] {
+                if given {
+                    flags.push(flag.to_owned());
+                }
+            }
+            if json_lines() {
+                flags.extend(["--output".to_owned(), "json-lines".to_owned()]);
+            }
+            if let LogFormat::Json = cli.log_format {
+                flags.extend(["--log-format".to_owned(), "json".to_owned()]);
+            }
+            let result = simulate(
+                &dir,
+                &sandbox,
+                &commands,
+                &flags,
+                cli.config
+                    .clone()
+                    .or_else(|| workspace::config_path().filter(|path| path.exists()))
+                    .as_deref(),
+                &config,
+                &tagger,
+            );
+            // The sandbox may not exist if copying names failed early.
+            let _ = fs::remove_dir_all(&sandbox);
+            result?;
+        }
+        Command::FuzzCheck { iterations, seed } => {
+            let seed = match seed {
+                Some(seed) => seed,
+                None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+            };
+            let mut rng = fuzz::Rng::new(seed);
+            let mut failures = BTreeMap::new();
+            for _ in 0..iterations {
+                let name = fuzz::generate(&mut rng, &tagger.format);
+                if let Some(failure) = fuzz::check(&name, &tagger.format) {
+                    let failure = fuzz::minimize(failure, &tagger.format);
+                    failures.entry(failure.name.clone()).or_insert(failure);
+                }
+            }
+            for failure in failures.values() {
+                // Bytes that are not printable ascii are shown escaped. eg \xff
+                emit(format!(
+                    "\"{}\" -> \"{}\" -> \"{}\"",
+                    failure.name.escape_ascii(),
+                    failure.rendered.escape_ascii(),
+                    failure.rerendered.escape_ascii()
+                ));
+            }
+            if !failures.is_empty() {
+                return Err(format!(
+                    "{} unstable name(s) found, with seed {}",
+                    failures.len(),
+                    seed
+                )
+                .into());
+            }
+            emit(format!("Checked {} names, with seed {}.", iterations, seed));
+        }
+        Command::Autotag { derived, paths } => {
+            if !derived {
+                return Err("No tag source given. eg --derived".into());
+            }
+            let rules = DerivedRules::new(&config.derived)?;
+            let mut batch = Batch::new();
+            for path in paths {
+                let new_path = tagger.retag(&path, |nametag| {
+                    rules.apply(nametag);
+                })?;
+                batch.push(path, new_path);
+            }
+            // Autotag often runs unattended, so problems are logged and
+            // announced rather than only printed.
+            for conflict in take_conflicts(&mut batch, &config) {
+                report_incident(&format!(
+                    "Skipped {}: {} already exists",
+                    conflict.from.display(),
+                    conflict.to.display()
+                ));
+            }
+            if let Err(err) = apply_batch(&mut batch, &apply) {
+                report_incident(&format!("Rename failed: {}", err));
+                return Err(err.into());
+            }
+            for rename in batch.renames() {
+                emit(rename.to.display());
+            }
+        }
+        Command::Resume { rollback } => {
+            let path = Journal::default_path().ok_or("No state directory available.")?;
+            let recovery = Journal::recover(&path)?.ok_or("No unfinished batch to resume.")?;
+            let (total, done) = (recovery.renames().len(), recovery.done());
+            if rollback {
+                let skipped = recovery.rollback()?;
+                for Skipped { rename, reason } in &skipped {
+                    warn(Record::warning(
+                        Kind::Skipped,
+                        Some(&rename.from),
+                        tr(&reason.to_string(), &[]),
+                    ));
+                }
+                emit(format!(
+                    "Rolled back {} of {} renames.",
+                    done - skipped.len(),
+                    total
+                ));
+            } else {
+                if recovery.checkpoint() > 0 {
+                    emit(format!(
+                        "Resuming from a checkpoint after {} of {} renames.",
+                        recovery.checkpoint(),
+                        total
+                    ));
+                }
+                let skipped = recovery.finish()?;
+                for Skipped { rename, reason } in &skipped {
+                    warn(Record::warning(
+                        Kind::Skipped,
+                        Some(&rename.from),
+                        tr(&reason.to_string(), &[]),
+                    ));
+                }
+                emit(format!(
+                    "Finished {} remaining of {} renames.",
+                    total - done - skipped.len(),
+                    total
+                ));
+            }
+        }
+        Command::Show {
+            collection,
+            catalog,
+            paths,
+        } => {
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let color = cli.color.enabled() && !cli.plain;
+            let mut found = gather_files(&config, &paths, collection.as_deref(), catalog)?;
+            // Files keep the order they were given in unless collated.
+            if config.collation != Collation::Bytes {
+                found.sort_by(|a, b| {
+                    config
+                        .collation
+                        .compare(&a.path.to_string_lossy(), &b.path.to_string_lossy())
+                });
+            }
+            for Found {
+                root,
+                path,
+                offline,
+            } in found
+            {
+                let mut tags = tagger.file_tags(&path)?;
+                config.collation.sort(&mut tags);
+                let tags = tags
+                    .iter()
+                    .map(|tag| redaction.tag(tag))
+                    .collect::<Vec<_>>();
+                match root {
+                    // Collection files are labelled with the root they came from.
+                    Some(root) => emit(format!(
+                        "{}\t{}: {}{}",
+                        redaction.path(&root).display(),
+                        redaction
+                            .path(path.strip_prefix(&root).unwrap_or(&path))
+                            .display(),
+                        tags.iter()
+                            .map(|tag| paint_tag(tag, &ontology, color))
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        if offline { " (offline)" } else { "" }
+                    )),
+                    None => emit_found(&redaction.path(&path), Some(&tags), &ontology, color),
+                }
+            }
+        }
+        Command::List {
+            collection,
+            catalog,
+            paths,
+            as_of,
+        } => {
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let color = cli.color.enabled() && !cli.plain;
+            let mut counts = BTreeMap::new();
+            let mut count = |tags: Vec<String>| {
+                for tag in tags {
+                    *counts.entry(redaction.tag(&tag)).or_insert(0) += 1;
+                }
+            };
+            match as_of {
+                Some(name) => {
+                    for path in snapshot_files(&name, &paths, &tagger)? {
+                        count(tagger.recorded_tags(&path)?);
+                    }
+                }
+                None => {
+                    for Found { path, .. } in
+                        gather_files(&config, &paths, collection.as_deref(), catalog)?
+                    {
+                        count(tagger.file_tags(&path)?);
+                    }
+                }
+            }
+            let width = counts.keys().map(String::len).max().unwrap_or(0);
+            let mut counts = counts.into_iter().collect::<Vec<_>>();
+            counts.sort_by(|(a, _), (b, _)| config.collation.compare(a, b));
+            for (tag, count) in counts {
+                let info = ontology.get(&tag);
+                let category = info.and_then(|info| info.category.as_deref());
+                let description = info.and_then(|info| info.description.as_deref());
+                // Plain columns are separated by tabs rather than lined up, and are
+                // never left empty.
+                let line = if cli.plain {
+                    format!(
+                        "{}\t{}\t{}\t{}",
+                        tag,
+                        count,
+                        category.unwrap_or("-"),
+                        description.unwrap_or("-"),
+                    )
+                } else {
+                    format!(
+                        "{}{} {:>5}  {:10} {}",
+                        paint_tag(&tag, &ontology, color),
+                        " ".repeat(width - tag.len()),
+                        count,
+                        category.unwrap_or("-"),
+                        description.unwrap_or(""),
+                    )
+                };
+                emit(line.trim_end());
+            }
+        }
+        Command::Lint { strict, paths } => {
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let color = cli.color.enabled() && !cli.plain;
+            let strict = strict || ontology.strict;
+            let mut problems = 0;
+            for path in expand_paths(&paths, &config.walk)? {
+                let tags = tagger.file_tags(&path)?;
+                for breach in tagger.limits.breaches(&tags, &tagger.aliases) {
+                    emit(format!("{}: {}", path.display(), breach));
+                    problems += 1;
+                }
+                for combination in tagger.policy.violations(&tagger.parse(&path)?) {
+                    emit(format!(
+                        "{}: forbidden combination of tags: {}",
+                        path.display(),
+                        combination.join(" + ")
+                    ));
+                    problems += 1;
+                }
+                for tag in tagger.file_tags(&path)? {
+                    if strict && !ontology.contains(&tag) {
+                        emit(format!(
+                            "{}: tag \"{}\" is not in the ontology",
+                            path.display(),
+                            paint_tag(&tag, &ontology, color)
+                        ));
+                        problems += 1;
+                    }
+                }
+            }
+            if problems > 0 {
+                return Err(format!("{} problem(s) found", problems).into());
+            }
+        }
+        Command::Maintain {
+            root,
+            sweep_older_than,
+        } => {
+            check_root(guard.as_ref(), &root)?;
+            let files = walk(&root, &config.walk)?.files;
+            let checked = files.len();
+            let mut expired = Vec::new();
+            if let Some(days) = sweep_older_than {
+                for path in &files {
+                    if trash::expired(&tagger.file_tags(path)?, days) {
+                        expired.push(path.clone());
+                    }
+                }
+            }
+            let files = files
+                .into_iter()
+                .filter(|path| !expired.contains(path))
+                .collect();
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), files, |_| {})?;
+            apply_batch(&mut batch, &apply)?;
+            remove_logged(&expired)?;
+            // Refreshed last, so the index sees the names as they now are.
+            let refreshed = match Index::find(&root)? {
+                Some(mut index) => {
+                    let path =
+                        Index::default_path(&index.root).ok_or("No state directory available.")?;
+                    let read =
+                        index.update(&tagger.format, config.extension, &config.walk.cancel)?;
+                    index.save(&path)?;
+                    Some(read)
+                }
+                None => None,
+            };
+            let unfinished = match Journal::default_path() {
+                Some(path) => Journal::recover(&path)?.is_some(),
+                None => false,
+            };
+            emit(format!("Files checked: {}", checked));
+            emit(format!("Names canonicalized: {}", batch.len()));
+            if sweep_older_than.is_some() {
+                emit(format!("Trashed files swept: {}", expired.len()));
+            }
+            match refreshed {
+                Some(read) => emit(format!("Index refreshed: {} changed directories", read)),
+                None => emit("Index refreshed: no index"),
+            }
+            if unfinished {
+                emit("Unfinished batch: yes, run \"nametag resume\"");
+            } else {
+                emit("Unfinished batch: no");
+            }
+        }
+        Command::Check {
+            baseline,
+            update,
+            mut protect,
+            root,
+        } => {
+            if update {
+                let root = root
+                    .or_else(workspace::current)
+                    .unwrap_or_else(|| PathBuf::from("."));
+                Snapshot::scan(&root, &tagger.format, &config.walk)?.save(&baseline)?;
+                return Ok(());
+            }
+            let recorded = Snapshot::load(&baseline)?;
+            let current = Snapshot::scan(
+                root.as_deref().unwrap_or(&recorded.root),
+                &tagger.format,
+                &config.walk,
+            )?;
+            if protect.is_empty() {
+                protect = config.protected.clone();
+            }
+            let protected = |path: &Path| {
+                protect.is_empty()
+                    || protect
+                        .iter()
+                        .any(|pattern| pattern::matches(pattern, &path.to_string_lossy()))
+            };
+            let mut changed = 0;
+            for change in recorded.diff(&current) {
+                match change {
+                    Change::Retagged {
+                        path,
+                        before,
+                        after,
+                    } if protected(&path) => {
+                        emit(format!(
+                            "{}: tags changed from [{}] to [{}]",
+                            redaction.path(&path).display(),
+                            join_tags(before.iter().map(|tag| redaction.tag(tag))),
+                            join_tags(after.iter().map(|tag| redaction.tag(tag)))
+                        ));
+                        changed += 1;
+                    }
+                    Change::Disappeared(path, _) if protected(&path) => {
+                        emit(format!("{}: missing", redaction.path(&path).display()));
+                        changed += 1;
+                    }
+                    _ => {}
+                }
+            }
+            if changed > 0 {
+                return Err(format!("{} protected file(s) changed", changed).into());
+            }
+        }
+        Command::IndexFiles { root } => {
+            check_root(guard.as_ref(), &root)?;
+            let ontology = load_ontology(cli.config.as_deref())?;
+            let written = write_indexes(&root, &config, &tagger.format, &ontology, &redaction)?;
+            emit(format!("Wrote {} index files.", written));
+        }
+        Command::Rescheme { from, to, root } => {
+            check_root(guard.as_ref(), &root)?;
+            // Recorded renames are read in the configured format, so would
+            // be taken apart differently once reschemed.
+            if tagger.plan.is_some() {
+                return Err("A transaction is open, run \"nametag tx commit\" or \"nametag tx abort\" first".into());
+            }
+            let from = tag_format(&config, &from)?;
+            let to = tag_format(&config, &to)?;
+            let mut batch = Batch::new();
+            for path in walk(&root, &config.walk)?.files {
+                let nametag = NameTag::from_path_with_format(&path, config.extension, from.clone());
+                if nametag.get_tags().next().is_none() || tagger.is_literal(&nametag) {
+                    continue;
+                }
+                match nametag.reformat(to.clone(), config.extension) {
+                    Some(nametag) => batch.push(path, PathBuf::from(nametag)),
+                    None => {
+                        warn(Record::warning(
+                            Kind::Skipped,
+                            Some(&path),
+                            "Name already reads as tagged in the new format",
+                        ));
+                        batch.skip();
+                    }
+                }
+            }
+            settle_conflicts(&mut batch, &config, resolver.as_ref())?;
+            // Names are read in the new format, where they keep their tags.
+            let apply = Apply {
+                format: to,
+                ..apply
+            };
+            apply_batch(&mut batch, &apply)?;
+            emit(format!("Renamed {} files.", batch.len()));
+        }
+        Command::Index(IndexCommand::Build { root }) => {
+            let root = fs::canonicalize(&root)?;
+            let path = Index::default_path(&root).ok_or("No state directory available.")?;
+            let checkpoint =
+                Index::checkpoint_path(&root).ok_or("No state directory available.")?;
+            if checkpoint.exists() {
+                emit("Resuming from a checkpoint.");
+            }
+            let index = Index::build_resumable(
+                &root,
+                &tagger.format,
+                config.extension,
+                &config.walk.cancel,
+                &checkpoint,
+            )?;
+            index.save(&path)?;
+            emit(format!("Indexed {} files.", index.len()));
+        }
+        Command::Index(IndexCommand::Update { root }) => {
+            let root = fs::canonicalize(&root)?;
+            let path = Index::default_path(&root).ok_or("No state directory available.")?;
+            if !path.exists() {
+                return Err(format!(
+                    "{}: Not indexed, run \"nametag index build\"",
+                    root.display()
+                )
+                .into());
+            }
+            let mut index = Index::load(&path)?;
+            let read = index.update(&tagger.format, config.extension, &config.walk.cancel)?;
+            index.save(&path)?;
+            emit(format!(
+                "Indexed {} files, reading {} changed directories.",
+                index.len(),
+                read
+            ));
+        }
+        Command::Label { dirs, out } => {
+            let registry_path = Registry::default_path().ok_or("No state directory available.")?;
+            let mut registry = Registry::load(&registry_path)?;
+            let mut labels = Vec::new();
+            for dir in dirs {
+                let dir = fs::canonicalize(&dir)?;
+                let files = walk(&dir, &config.walk)?.files;
+                // Tags are shown as named, so sensitive tags stay hidden.
+                let mut counts = BTreeMap::new();
+                for file in &files {
+                    for tag in tagger.parse_name(file)?.get_tags() {
+                        *counts
+                            .entry(redaction.tag(&tag.to_string_lossy()))
+                            .or_insert(0) += 1;
+                    }
+                }
+                let mut counts = counts.into_iter().collect::<Vec<_>>();
+                counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+                let mut lines = vec![format!("{} files", files.len())];
+                for chunk in counts.chunks(4) {
+                    let line = chunk
+                        .iter()
+                        .map(|(tag, count)| format!("{} ({})", tag, count))
+                        .collect::<Vec<_>>();
+                    lines.push(line.join("    "));
+                }
+                let code = label::code_for(&dir);
+                registry.codes.insert(code.clone(), dir.clone());
+                labels.push(Label {
+                    code,
+                    title: redaction.path(&dir).display().to_string(),
+                    lines,
+                });
+            }
+            fs::write(&out, label::pdf(&labels))?;
+            registry.save(&registry_path)?;
+        }
+        Command::Locate { code } => {
+            let registry_path = Registry::default_path().ok_or("No state directory available.")?;
+            let registry = Registry::load(&registry_path)?;
+            let dir = registry
+                .locate(&code)
+                .ok_or_else(|| format!("No label was printed with code {}", code))?;
+            emit(redaction.path(dir).display());
+        }
+        Command::Snapshot(SnapshotCommand::Save { name, root }) => {
+            let snapshot = Snapshot::scan(&root, &tagger.format, &config.walk)?;
+            snapshot.save(&snapshot_path(&name)?)?;
+            emit(format!(
+                "Saved {} files as \"{}\".",
+                snapshot.files.len(),
+                name
+            ));
+        }
+        Command::Snapshot(SnapshotCommand::Diff { before, after }) => {
+            let before = Snapshot::load(&snapshot_path(&before)?)?;
+            let after = Snapshot::load(&snapshot_path(&after)?)?;
+            for change in before.diff_matching(&after, config.path_matching) {
+                match change {
+                    Change::Appeared(path, tags) => emit(format!(
+                        "+ {} [{}]",
+                        redaction.path(&path).display(),
+                        join_tags(tags.iter().map(|tag| redaction.tag(tag)))
+                    )),
+                    Change::Disappeared(path, tags) => emit(format!(
+                        "- {} [{}]",
+                        redaction.path(&path).display(),
+                        join_tags(tags.iter().map(|tag| redaction.tag(tag)))
+                    )),
+                    Change::Retagged {
+                        path,
+                        before,
+                        after,
+                    } => {
+                        let removed = before
+                            .difference(&after)
+                            .map(|tag| format!("-{}", redaction.tag(tag)));
+                        let added = after
+                            .difference(&before)
+                            .map(|tag| format!("+{}", redaction.tag(tag)));
+                        emit(format!(
+                            "~ {} {}",
+                            redaction.path(&path).display(),
+                            removed.chain(added).collect::<Vec<_>>().join(" ")
+                        ));
+                    }
+                }
+            }
+        }
+        Command::Series {
+            pattern,
+            renumber,
+            start,
+            render,
+            width,
+            paths,
+        } => {
+            if let Some(key) = &renumber {
+                if !pattern.keys().any(|field| field == key) {
+                    return Err(format!("Series pattern has no field \"{}\".", key).into());
+                }
+            }
+            let mut batch = Batch::new();
+            let mut number = start;
+            for path in expand_paths(&paths, &config.walk)? {
+                let current = tagger.planned(&path)?;
+                let name = file_name(&current)?
+                    .to_str()
+                    .ok_or_else(|| format!("{}: Name is not valid unicode", path.display()))?;
+                let mut found = match pattern.find(name) {
+                    Some(found) => found,
+                    None => {
+                        warn(Record::warning(
+                            Kind::Skipped,
+                            Some(&path),
+                            "Does not match the series",
+                        ));
+                        continue;
+                    }
+                };
+                if let Some(key) = &renumber {
+                    found.values.insert(key.clone(), number);
+                    number += 1;
+                }
+                // Renumbering always rewrites names, so they agree with the tags.
+                let renamed = if render || renumber.is_some() {
+                    current.with_file_name(format!(
+                        "{}{}{}",
+                        &name[..found.start],
+                        pattern.render(&found.values, width),
+                        &name[found.end..]
+                    ))
+                } else {
+                    current.clone()
+                };
+                let new_path = tagger.retag_as(&path, &renamed, |nametag| {
+                    for (key, value) in &found.values {
+                        nametag.set_values(key, &[value.to_string()]);
+                    }
+                })?;
+                batch.push(path, new_path);
+            }
+            settle_conflicts(&mut batch, &config, resolver.as_ref())?;
+            apply_batch(&mut batch, &apply)?;
+        }
+        Command::Bundle(BundleCommand::Save { name, tags }) => {
+            let path = bundles_path(cli.config.as_deref())?;
+            let mut bundles = Bundles::load(&path)?;
+            bundles.bundle.insert(name, tags);
+            bundles.save(&path)?;
+        }
+        Command::Bundle(BundleCommand::List {}) => {
+            let bundles = Bundles::load(&bundles_path(cli.config.as_deref())?)?;
+            for (name, tags) in &bundles.bundle {
+                emit(format!("{}: {}", name, tags.join(" ")));
+            }
+        }
+        Command::Bundle(BundleCommand::Apply { name, paths }) => {
+            let bundles = Bundles::load(&bundles_path(cli.config.as_deref())?)?;
+            let tags = bundles.get(&name)?;
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), paths, |nametag| {
+                for tag in tags {
+                    nametag.add_tag(tag);
+                }
+            })?;
+            apply_batch(&mut batch, &apply)?;
+        }
+        Command::Bundle(BundleCommand::Remove { name, paths }) => {
+            let bundles = Bundles::load(&bundles_path(cli.config.as_deref())?)?;
+            let tags = bundles.get(&name)?;
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), paths, |nametag| {
+                for tag in tags {
+                    nametag.remove_tag(tag);
+                }
+            })?;
+            apply_batch(&mut batch, &apply)?;
+        }
+        Command::Stage(StageCommand::Add { tags, paths }) => {
+            tagger.record_into(stage_path()?)?;
+            for path in paths {
+                tagger.retag(&path, |nametag| {
+                    for tag in &tags {
+                        nametag.add_tag(tag);
+                    }
+                })?;
+            }
+        }
+        Command::Stage(StageCommand::Remove { tags, paths }) => {
+            tagger.record_into(stage_path()?)?;
+            for path in paths {
+                tagger.retag(&path, |nametag| {
+                    for tag in &tags {
+                        nametag.remove_tag(tag);
+                    }
+                })?;
+            }
+        }
+        Command::Status {} => {
+            let stage = Transaction::load(&stage_path()?)?.unwrap_or_default();
+            if stage.renames().is_empty() {
+                emit("Nothing staged.");
+            }
+            for rename in stage.renames() {
+                emit(format!(
+                    "{} -> {}",
+                    redaction.path(&rename.from).display(),
+                    redaction.path(&rename.to).display()
+                ));
+            }
+        }
+        Command::Commit { message } => {
+            let path = stage_path()?;
+            let stage = Transaction::load(&path)?.unwrap_or_default();
+            let mut batch = stage.batch();
+            if batch.is_empty() {
+                return Err("Nothing staged to commit.".into());
+            }
+            settle_conflicts(&mut batch, &config, resolver.as_ref())?;
+            apply_logged(&mut batch, &apply, &message)?;
+            fs::remove_file(&path)?;
+            emit(format!("Committed {} renames.", batch.len()));
+            return Ok(());
+        }
+        Command::History { as_patch, root } => {
+            let log = audit::default_path().ok_or("No state directory available.")?;
+            let entries = audit::entries(&log)?;
+            if !as_patch {
+                for entry in &entries {
+                    emit(format!(
+                        "{}  {} ({} renames{})",
+                        entry.date(),
+                        entry.message,
+                        entry.renames.len(),
+                        match entry.removed.len() {
+                            0 => String::new(),
+                            removed => format!(", {} removed", removed),
+                        }
+                    ));
+                }
+                return Ok(());
+            }
+            let root = fs::canonicalize(root.unwrap_or_else(|| PathBuf::from(".")))?;
+            let tags_of = |path: &Path| written_tags(&tagger, path);
+            let mut manifest = Manifest::default();
+            for path in walk(&root, &config.walk)?.files {
+                let relative = path.strip_prefix(&root)?;
+                manifest.insert(relative, tags_of(relative));
+            }
+            // Work back from the files as they are to before the first batch.
+            let mut manifests = vec![manifest];
+            for entry in entries.iter().rev() {
+                let mut before = manifests[manifests.len() - 1].clone();
+                before.undo(&root, &entry.renames, tags_of);
+                for removed in &entry.removed {
+                    if let Ok(relative) = removed.strip_prefix(&root) {
+                        before.insert(relative, tags_of(relative));
+                    }
+                }
+                manifests.push(before);
+            }
+            manifests.reverse();
+            let start = entries.first().map_or(0, |entry| entry.time);
+            emit_block(manifest::patch(
+                None,
+                &manifests[0],
+                "Start tracking tags",
+                start,
+            ));
+            for (entry, pair) in entries.iter().zip(manifests.windows(2)) {
+                let patch = manifest::patch(Some(&pair[0]), &pair[1], &entry.message, entry.time);
+                emit_block(patch);
+            }
+        }
+        Command::Tx(TxCommand::Begin {}) => {
+            Transaction::begin(&transaction_path()?)?;
+        }
+        Command::Tx(TxCommand::Status {}) => {
+            let transaction =
+                Transaction::load(&transaction_path()?)?.ok_or("No transaction is open.")?;
+            for rename in transaction.renames() {
+                emit(format!(
+                    "{} -> {}",
+                    redaction.path(&rename.from).display(),
+                    redaction.path(&rename.to).display()
+                ));
+            }
+        }
+        Command::Tx(TxCommand::Commit {}) => {
+            let path = transaction_path()?;
+            let transaction = Transaction::load(&path)?.ok_or("No transaction is open.")?;
+            let mut batch = transaction.batch();
+            settle_conflicts(&mut batch, &config, resolver.as_ref())?;
+            apply_batch(&mut batch, &apply)?;
+            fs::remove_file(&path)?;
+            emit(format!("Committed {} renames.", batch.len()));
+            return Ok(());
+        }
+        Command::Tx(TxCommand::Abort {}) => {
+            let path = transaction_path()?;
+            Transaction::load(&path)?.ok_or("No transaction is open.")?;
+            fs::remove_file(&path)?;
+            return Ok(());
+        }
+        Command::CopyFrom {
+            exemplar,
+            targets,
+            merge: _,
+            replace,
+        } => {
+            let exemplar = tagger.parse(&exemplar)?;
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), targets, |nametag| {
+                if replace {
+                    nametag.clear_tags();
+                }
+                nametag.union_tags(&exemplar);
+            })?;
+            apply_batch(&mut batch, &apply)?;
+        }
+        #[cfg(feature = "metadata")]
+        Command::Sync { direction, paths } => {
+            let mut files = Vec::new();
+            for path in &paths {
+                if path.is_dir() {
+                    check_root(guard.as_ref(), path)?;
+                    files.extend(walk(path, &config.walk)?.files);
+                } else {
+                    files.push(path.clone());
+                }
+            }
+            let mut batch = Batch::new();
+            for path in files {
+                // Tags are synced as named, so sensitive tags stay hidden.
+                let mut nametag = tagger.parse_name(&path)?;
+                let named = nametag
+                    .get_tags()
+                    .map(|tag| tag.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>();
+                if let Direction::NameToXattr = direction {
+                    if metadata::read_tags(&path)? != named {
+                        metadata::write_tags(&path, &named)?;
+                    }
+                    continue;
+                }
+                let stored = metadata::read_safe_tags(&path, config.slug)?;
+                if let Direction::Merge = direction {
+                    metadata::import(&path, &mut nametag, config.slug)?;
+                    metadata::export(&nametag, &path)?;
+                }
+                let new_path = tagger.retag(&path, |nametag| {
+                    if let Direction::XattrToName = direction {
+                        nametag.clear_tags();
+                    }
+                    for tag in &stored {
+                        nametag.add_tag(tag);
+                    }
+                })?;
+                batch.push(path, new_path);
+            }
+            settle_conflicts(&mut batch, &config, resolver.as_ref())?;
+            apply_batch(&mut batch, &apply)?;
+        }
+        #[cfg(feature = "clipboard")]
+        Command::CopyTags { file } => {
+            let tags = tagger.file_tags(&file)?;
+            let text = tags.join(" ");
+            clipboard()?.set_text(text).map_err(clipboard_error)?;
+        }
+        #[cfg(feature = "clipboard")]
+        Command::PasteTags { paths } => {
+            let text = clipboard()?.get_text().map_err(clipboard_error)?;
+            let tags = text
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|tag| !tag.is_empty())
                 .collect::<Vec<_>>();
+            if tags.is_empty() {
+                return Err("Clipboard does not contain any tags.".into());
+            }
+            let mut batch = plan_paths(&tagger, &config, resolver.as_ref(), paths, |nametag| {
+                for tag in &tags {
+                    nametag.add_tag(*tag);
+                }
+            })?;
+            apply_batch(&mut batch, &apply)?;
+        }
+    }
+    tagger.save_transaction()?;
+    Ok(())
+}
+
+// Reads and edits the tags in file names, applying config wide behavior.
+// While a transaction is open, edits are recorded in it rather than applied.
+struct Tagger {
+    obfuscator: Option<Obfuscator>,
+    abbreviations: Abbreviations,
+    aliases: Aliases,
+    ascii: bool,
+    policy: Policy,
+    limits: Limits,
+    plan: Option<Plan>,
+    extension: Extension,
+    format: TagFormat,
+    /// Names whose bracketed text is taken as part of the title.
+    literal: Option<Literal>,
+}
+
+// Renames recorded in place of being performed, with the file keeping them.
+struct Plan {
+    path: PathBuf,
+    transaction: RefCell<Transaction>,
+}
+
+impl Tagger {
+    fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let plan = match Transaction::default_path() {
+            Some(path) => Transaction::load(&path)?.map(|transaction| Plan {
+                path,
+                transaction: RefCell::new(transaction),
+            }),
+            None => None,
+        };
+        Ok(Self {
+            obfuscator: config.obfuscator()?,
+            abbreviations: config.abbreviations(),
+            aliases: config.aliases(),
+            ascii: config.ascii,
+            policy: config.policy(),
+            limits: config.limits,
+            plan,
+            extension: config.extension,
+            format: config.tag_format()?,
+            literal: None,
+        })
+    }
+
+    // Record edits into the transaction at a path, starting it if needed.
+    fn record_into(&mut self, path: PathBuf) -> io::Result<()> {
+        let transaction = Transaction::load(&path)?.unwrap_or_default();
+        self.plan = Some(Plan {
+            path,
+            transaction: RefCell::new(transaction),
+        });
+        Ok(())
+    }
+
+    // Name a file goes by, which is its planned name in an open transaction.
+    fn planned(&self, path: &Path) -> io::Result<PathBuf> {
+        match &self.plan {
+            Some(plan) => plan.transaction.borrow().planned(path),
+            None => Ok(path.to_path_buf()),
+        }
+    }
+
+    // Tags of a file name. Checks the path names a file, eg is not "..".
+    fn parse_name(&self, path: &Path) -> io::Result<NameTag> {
+        file_name(path)?;
+        Ok(NameTag::from_path_with_format(
+            path,
+            self.extension,
+            self.format.clone(),
+        ))
+    }
+
+    fn parse(&self, path: &Path) -> io::Result<NameTag> {
+        let mut nametag = self.parse_name(&self.planned(path)?)?;
+        self.reveal(&mut nametag);
+        Ok(nametag)
+    }
+
+    // Whether the tags of a name are taken as part of its title.
+    fn is_literal(&self, nametag: &NameTag) -> bool {
+        self.literal
+            .as_ref()
+            .is_some_and(|literal| literal.is_literal(nametag))
+    }
+
+    // Whether the tags of a name satisfy a query, values in a block of
+    // machine tags included.
+    fn satisfies(&self, query: &Query, nametag: &NameTag) -> bool {
+        match self.is_literal(nametag) {
+            // The whole name is the title, so name terms search all of it.
+            true => {
+                let path = PathBuf::from(nametag.clone());
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                query.matches_named::<&str>(&[], &name)
+            }
+            false => query.matches_name(nametag),
+        }
+    }
+
+    // Tags on the file name of a path, in sorted order.
+    fn file_tags(&self, path: &Path) -> io::Result<Vec<String>> {
+        let nametag = self.parse(path)?;
+        if self.is_literal(&nametag) {
+            return Ok(Vec::new());
+        }
+        Ok(nametag
+            .get_tags()
+            .map(|tag| tag.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    // Tags on a name as written, regardless of staged edits. eg a name
+    // recorded in a snapshot
+    fn recorded_tags(&self, path: &Path) -> io::Result<Vec<String>> {
+        let mut nametag = self.parse_name(path)?;
+        self.reveal(&mut nametag);
+        if self.is_literal(&nametag) {
+            return Ok(Vec::new());
+        }
+        Ok(nametag
+            .get_tags()
+            .map(|tag| tag.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    // Path a file would take with edited tags. Only the file name is considered,
+    // so periods and braces in parent directories are left alone.
+    fn retag<F: FnOnce(&mut NameTag)>(&self, path: &Path, edit: F) -> io::Result<PathBuf> {
+        self.retag_as(path, &self.planned(path)?, edit)
+    }
+
+    // As retag, but starting from a different name for the file.
+    fn retag_as<F: FnOnce(&mut NameTag)>(
+        &self,
+        path: &Path,
+        name: &Path,
+        edit: F,
+    ) -> io::Result<PathBuf> {
+        let before = self.parse(path)?;
+        if self.is_literal(&before) {
+            warn(Record::warning(
+                Kind::Skipped,
+                Some(path),
+                "Bracketed text reads as part of the title",
+            ));
+            return Ok(path.to_path_buf());
+        }
+        let new_path = self.edit_name(&before, name, edit)?;
+        match &self.plan {
+            // Nothing is renamed until the transaction is committed.
+            Some(plan) => {
+                plan.transaction.borrow_mut().record(path, &new_path)?;
+                Ok(path.to_path_buf())
+            }
+            None => Ok(new_path),
+        }
+    }
+
+    // A name with edited tags, without touching the disk.
+    fn rename<F: FnOnce(&mut NameTag)>(&self, name: &Path, edit: F) -> io::Result<PathBuf> {
+        let mut before = self.parse_name(name)?;
+        self.reveal(&mut before);
+        self.edit_name(&before, name, edit)
+    }
+
+    // Edit the tags of a name, refusing to introduce forbidden combinations
+    // to the tags a file had before.
+    fn edit_name<F: FnOnce(&mut NameTag)>(
+        &self,
+        before: &NameTag,
+        name: &Path,
+        edit: F,
+    ) -> io::Result<PathBuf> {
+        let mut nametag = self.parse_name(name)?;
+        self.reveal(&mut nametag);
+        edit(&mut nametag);
+        if let Some(combination) = self.policy.introduced(before, &nametag).first() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                Forbidden {
+                    path: name.to_path_buf(),
+                    combination: combination.to_vec(),
+                },
+            ));
+        }
+        if let Some(breach) = self
+            .limits
+            .introduced(before, &nametag, &self.aliases)
+            .first()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                Refused {
+                    path: name.to_path_buf(),
+                    breach: breach.clone(),
+                },
+            ));
+        }
+        self.conceal(&mut nametag);
+        Ok(PathBuf::from(nametag))
+    }
+
+    // Tags as they are used, with obfuscated and abbreviated tags restored.
+    fn reveal(&self, nametag: &mut NameTag) {
+        if let Some(obfuscator) = &self.obfuscator {
+            obfuscator.reveal(nametag);
+        }
+        self.abbreviations.expand_tags(nametag);
+    }
+
+    // Tags as they are written, with sensitive tags obfuscated, emoji in
+    // ascii if asked for, and tags shortened to fit the length limit.
+    fn conceal(&self, nametag: &mut NameTag) {
+        if let Some(obfuscator) = &self.obfuscator {
+            obfuscator.conceal(nametag);
+        }
+        if self.ascii {
+            self.aliases.to_ascii(nametag);
+        }
+        self.abbreviations.shorten(nametag);
+    }
+
+    // A single tag as it is used.
+    fn reveal_tag(&self, tag: &str) -> Option<String> {
+        match self
+            .obfuscator
+            .as_ref()
+            .and_then(|obfuscator| obfuscator.decode(tag))
+        {
+            Some(plain) => Some(plain),
+            None => self.abbreviations.expand(tag).map(str::to_owned),
+        }
+    }
+
+    // Keep the edits recorded in an open transaction.
+    fn save_transaction(&self) -> io::Result<()> {
+        match &self.plan {
+            Some(plan) => plan.transaction.borrow().save(&plan.path),
+            None => Ok(()),
+        }
+    }
+}
+
+// How batches are applied, from the global options and config.
+struct Apply<'a> {
+    throttle: Option<f64>,
+    chunk_size: Option<usize>,
+    cancel: Cancel,
+    /// Whether high risk batches go ahead.
+    confirmed: bool,
+    /// Counts of the batches applied, reported when the run ends.
+    summary: &'a RefCell<Summary>,
+    format: TagFormat,
+    extension: Extension,
+    ontology: Ontology,
+    /// Where the tags of files are backed up before batches taking tags.
+    backup_dir: Option<PathBuf>,
+}
+
+// Apply a batch, recording it in the audit log under the command line.
+fn apply_batch(batch: &mut Batch, apply: &Apply) -> io::Result<()> {
+    apply_logged(batch, apply, &command_line())
+}
+
+// The command being run, for the audit log.
+fn command_line() -> String {
+    let mut command = vec!["nametag".to_owned()];
+    command.extend(
+        env::args_os()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned()),
+    );
+    command.join(" ")
+}
+
+// Delete files, recording those deleted in the audit log even when one
+// fails, so history shows them.
+fn remove_logged(paths: &[PathBuf]) -> io::Result<()> {
+    let cwd = env::current_dir()?;
+    let mut removed = Vec::new();
+    let result = paths.iter().try_for_each(|path| {
+        fs::remove_file(path)?;
+        removed.push(cwd.join(path));
+        Ok(())
+    });
+    if let (Some(log), false) = (audit::default_path(), removed.is_empty()) {
+        audit::append(&log, &audit::Entry::removing(&command_line(), &removed))?;
+    }
+    result
+}
+
+// As apply_batch, with a message for the audit log in place of the command.
+fn apply_logged(batch: &mut Batch, apply: &Apply, message: &str) -> io::Result<()> {
+    let result = apply_audited(batch, apply, message);
+    apply.summary.borrow_mut().record(batch, result.is_ok());
+    result
+}
+
+fn apply_audited(batch: &mut Batch, apply: &Apply, message: &str) -> io::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let risk = risk::assess(
+        batch.renames(),
+        &apply.format,
+        apply.extension,
+        &apply.ontology,
+    );
+    if risk.is_high() && !apply.confirmed {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr(
+                "Refusing a high risk batch, which {}. Review it, with --dry-run where \
+                 available, then pass --yes-i-mean-it.",
+                &[&risk.reasons.join(", ")],
+            ),
+        ));
+    }
+    if let Some(ops_per_sec) = apply.throttle {
+        batch.set_throttle(ops_per_sec);
+    }
+    if let Some(renames) = apply.chunk_size {
+        batch.set_chunk_size(renames);
+    }
+    batch.set_cancel(apply.cancel.clone());
+    // Check the directories involved allow renames before changing anything.
+    batch.probe()?;
+    if let Some(dir) = &apply.backup_dir {
+        let tags_of = |path: &Path| {
+            NameTag::from_path_with_format(path, apply.extension, apply.format.clone())
+                .get_tags()
+                .map(|tag| tag.to_string_lossy().into_owned())
+                .collect()
+        };
+        let manifest = backup::removing(&env::current_dir()?, batch.renames(), tags_of);
+        if !manifest.is_empty() {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            backup::write(dir, &manifest, time)?;
+        }
+    }
+    let skipped = match Journal::default_path() {
+        Some(journal) => batch.apply_journaled(&journal)?,
+        None => batch.apply()?,
+    };
+    for Skipped { rename, reason } in skipped {
+        warn(Record::warning(
+            Kind::Skipped,
+            Some(&rename.from),
+            tr(&reason.to_string(), &[]),
+        ));
+    }
+    if json_lines() {
+        for rename in batch.renames() {
             println!(
-                ">tags: {:?}, paths {:?}, nametags {:?}",
-                tags, paths, nametags
+                "{}",
+                Event::Apply {
+                    from: &rename.from,
+                    to: &rename.to
+                }
+                .line()
             );
         }
-        Cli::Remove {} => {}
-        Cli::Query {} => {}
     }
+    // Paths are made absolute, so history can place them.
+    if let Some(log) = audit::default_path() {
+        let cwd = env::current_dir()?;
+        let renames = batch
+            .renames()
+            .iter()
+            .map(|rename| Rename {
+                from: cwd.join(&rename.from),
+                to: cwd.join(&rename.to),
+            })
+            .collect::<Vec<_>>();
+        audit::append(&log, &audit::Entry::new(message, &renames))?;
+    }
+    Ok(())
+}
+
+// Plan the renames editing the tags of files, skipping any that would
+// overwrite another file. Edits combining forbidden tags are refused,
+// unless a resolver settles them.
+fn plan_paths<F: Fn(&mut NameTag)>(
+    tagger: &Tagger,
+    config: &Config,
+    resolver: Option<&Resolver>,
+    paths: Vec<PathBuf>,
+    edit: F,
+) -> io::Result<Batch> {
+    let mut batch = Batch::new();
+    for path in paths {
+        let retagged = tagger.retag(&path, &edit);
+        let refused = retagged
+            .as_ref()
+            .err()
+            .and_then(|err| err.get_ref()?.downcast_ref::<Refused>());
+        if let Some(Refused { breach, .. }) = refused {
+            warn(Record::warning(
+                Kind::Skipped,
+                Some(&path),
+                tr("Refusing as {}", &[breach]),
+            ));
+            batch.skip();
+            continue;
+        }
+        let new_path = match (retagged, resolver) {
+            (Err(err), Some(resolver)) => {
+                let forbidden = match err
+                    .get_ref()
+                    .and_then(|err| err.downcast_ref::<Forbidden>())
+                {
+                    Some(forbidden) => forbidden,
+                    None => return Err(err),
+                };
+                match resolver.resolve(Problem::Forbidden, &path, &forbidden.to_string())? {
+                    Resolution::Edit { tags } => tagger.retag(&path, |nametag| {
+                        nametag.clear_tags();
+                        for tag in &tags {
+                            nametag.add_tag(tag.as_str());
+                        }
+                    })?,
+                    _ => {
+                        warn(Record::warning(
+                            Kind::Skipped,
+                            Some(&path),
+                            tr(
+                                "Refusing to combine forbidden tags: {}",
+                                &[&forbidden.combination.join(" + ")],
+                            ),
+                        ));
+                        batch.skip();
+                        continue;
+                    }
+                }
+            }
+            (new_path, _) => new_path?,
+        };
+        batch.push(path, new_path);
+    }
+    settle_conflicts(&mut batch, config, resolver)?;
+    Ok(batch)
+}
+
+// Take out renames that would overwrite another file, warning of each,
+// unless a resolver settles them otherwise.
+fn settle_conflicts(
+    batch: &mut Batch,
+    config: &Config,
+    resolver: Option<&Resolver>,
+) -> io::Result<()> {
+    for conflict in take_conflicts(batch, config) {
+        let exists = tr("{} already exists", &[&conflict.to.display()]);
+        let resolution = match resolver {
+            Some(resolver) => resolver.resolve(
+                Problem::Collision,
+                &conflict.from,
+                &format!("{}: {}", conflict.from.display(), exists),
+            )?,
+            None => Resolution::Skip,
+        };
+        match resolution {
+            Resolution::Rename { name } => {
+                let to = conflict.to.with_file_name(name);
+                if to.exists() || batch.renames().iter().any(|rename| rename.to == to) {
+                    warn(Record::warning(
+                        Kind::Skipped,
+                        Some(&conflict.from),
+                        tr("{} already exists", &[&to.display()]),
+                    ));
+                } else {
+                    batch.readmit(
+                        Rename {
+                            from: conflict.from,
+                            to,
+                        },
+                        false,
+                    );
+                }
+            }
+            Resolution::Overwrite => batch.readmit(conflict, true),
+            _ => warn(Record::warning(Kind::Skipped, Some(&conflict.from), exists)),
+        }
+    }
+    Ok(())
+}
+
+// Settles renames that cannot go ahead as planned, asking at the terminal
+// with --interactive, and answering as before with --replay.
+struct Resolver {
+    path: PathBuf,
+    resolutions: RefCell<Resolutions>,
+    interactive: bool,
+    replay: bool,
+}
+
+impl Resolver {
+    // How to settle a problem with a file, described by a message. Without
+    // an answer, the file is skipped.
+    fn resolve(&self, problem: Problem, path: &Path, message: &str) -> io::Result<Resolution> {
+        if self.replay {
+            if let Some(resolution) = self.resolutions.borrow().get(problem, path) {
+                return Ok(resolution.clone());
+            }
+        }
+        if !self.interactive {
+            return Ok(Resolution::Skip);
+        }
+        let stdin = io::stdin();
+        loop {
+            eprint!("{}\n{}? ", message, tr(problem.choices(), &[]));
+            let mut answer = String::new();
+            if stdin.lock().read_line(&mut answer)? == 0 {
+                return Ok(Resolution::Skip);
+            }
+            if let Some(resolution) = Resolution::parse(problem, &answer) {
+                let mut resolutions = self.resolutions.borrow_mut();
+                resolutions.record(problem, path, resolution.clone());
+                resolutions.save(&self.path)?;
+                return Ok(resolution);
+            }
+        }
+    }
+}
+
+// Apply a batch, or with dry_run only show what it would do, as a diff of
+// the tags of its files when asked.
+fn apply_or_show(
+    batch: &mut Batch,
+    dry_run: bool,
+    diff: bool,
+    tagger: &Tagger,
+    apply: &Apply,
+) -> io::Result<()> {
+    if !dry_run {
+        return apply_batch(batch, apply);
+    }
+    if diff {
+        let (before, after) = Manifest::planned(batch.renames(), |path| written_tags(tagger, path));
+        emit_block(manifest::unified(&before, &after));
+        return Ok(());
+    }
+    for rename in batch.renames() {
+        match json_lines() {
+            true => println!(
+                "{}",
+                Event::Plan {
+                    from: &rename.from,
+                    to: &rename.to
+                }
+                .line()
+            ),
+            false => println!("{} -> {}", rename.from.display(), rename.to.display()),
+        }
+    }
+    Ok(())
+}
+
+// Renames that would overwrite another file, after renaming around them
+// with the strategy in config, if any.
+fn take_conflicts(batch: &mut Batch, config: &Config) -> Vec<Rename> {
+    match &config.conflict {
+        Some(naming) => batch.resolve_conflicts(naming.namer().as_ref()),
+        None => batch.remove_conflicts(),
+    }
+}
+
+// A format from config or built in, else delimiters around "..".
+fn tag_format(config: &Config, name: &str) -> Result<TagFormat, Box<dyn Error>> {
+    match config.named_format(name) {
+        Ok(format) => Ok(format),
+        Err(_) => Ok(name.parse::<TagFormat>()?),
+    }
+}
+
+fn stage_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(Transaction::stage_path().ok_or("No state directory available.")?)
+}
+
+fn transaction_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(Transaction::default_path().ok_or("No state directory available.")?)
+}
+
+fn snapshot_path(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(Snapshot::named_path(name).ok_or("No state directory available.")?)
+}
+
+// Files of a saved snapshot at or below any of the paths, named as they
+// were when it was taken. No paths means the current directory.
+fn snapshot_files(
+    name: &str,
+    paths: &[PathBuf],
+    tagger: &Tagger,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let snapshot = Snapshot::load(&snapshot_path(name)?)?;
+    let cwd = env::current_dir()?;
+    let within = match paths.is_empty() {
+        true => vec![cwd],
+        false => paths
+            .iter()
+            .map(|path| roots::normalize(&cwd.join(path)))
+            .collect(),
+    };
+    Ok(within
+        .iter()
+        .flat_map(|path| snapshot.tagged_paths_within(path, &tagger.format))
+        .collect())
+}
+
+// Run commands against a copy of the names under a directory, made within
+// a sandbox with its own state, then print how the names changed.
+fn simulate(
+    dir: &Path,
+    sandbox: &Path,
+    commands: &[String],
+    flags: &[String],
+    config_path: Option<&Path>,
+    config: &Config,
+    tagger: &Tagger,
+) -> Result<(), Box<dyn Error>> {
+    let tree = sandbox.join("tree");
+    simulate::copy_names(dir, &tree, &config.walk)?;
+    for command in commands {
+        let mut child = std::process::Command::new(env::current_exe()?);
+        if let Some(path) = config_path {
+            child.arg("--config").arg(fs::canonicalize(path)?);
+        }
+        let status = child
+            .args(flags)
+            .args(simulate::split_args(command)?)
+            .current_dir(&tree)
+            .env("NAMETAG_STATE_DIR", sandbox.join("state"))
+            .status()?;
+        if !status.success() {
+            return Err(format!("Stopped as \"{}\" failed.", command).into());
+        }
+    }
+    let manifest = |root: &Path| -> io::Result<Manifest> {
+        let mut manifest = Manifest::default();
+        for path in walk(root, &config.walk)?.files {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            manifest.insert(relative, written_tags(tagger, relative));
+        }
+        Ok(manifest)
+    };
+    let changes = manifest::unified(&manifest(dir)?, &manifest(&tree)?);
+    if changes.is_empty() {
+        emit("No changes.");
+    } else {
+        emit_block(changes);
+    }
+    Ok(())
+}
+
+// Tags of a name as written, so sensitive tags stay hidden.
+fn written_tags(tagger: &Tagger, path: &Path) -> Vec<String> {
+    tagger.parse_name(path).map_or_else(
+        |_| Vec::new(),
+        |nametag| {
+            nametag
+                .get_tags()
+                .map(|tag| tag.to_string_lossy().into_owned())
+                .collect()
+        },
+    )
+}
+
+fn join_tags<I: IntoIterator<Item = String>>(tags: I) -> String {
+    tags.into_iter().collect::<Vec<_>>().join(" ")
+}
+
+fn paint_tag(tag: &str, ontology: &Ontology, color: bool) -> String {
+    if color {
+        ontology.color(tag).paint(tag)
+    } else {
+        tag.to_owned()
+    }
+}
+
+// Files named directly, plus the files within named directories, as found
+// by a walk that goes no deeper.
+fn expand_paths(paths: &[PathBuf], options: &WalkOptions) -> io::Result<Vec<PathBuf>> {
+    let options = WalkOptions {
+        max_depth: Some(0),
+        ..options.clone()
+    };
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(walk(path, &options)?.files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+// Cut torn writes, left by a crash or power loss, from the end of the
+// journal and audit log, so they can be appended to again. Only done while
+// holding the lock, so no other process is partway through writing them.
+fn repair_logs() -> io::Result<()> {
+    let logs = [
+        (
+            Journal::default_path(),
+            Journal::repair as fn(&Path) -> io::Result<usize>,
+        ),
+        (audit::default_path(), audit::repair),
+    ];
+    for (path, repair) in logs {
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+        let removed = repair(&path)?;
+        if removed > 0 {
+            report_incident(&format!(
+                "Removed {} damaged bytes from the end of {}",
+                removed,
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// How warnings and errors are written, set once from the command line.
+static LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+// Translations of messages on stderr, for the language of the environment.
+// Results on stdout are left as they are for scripts reading them.
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+// A message translated, with its placeholders filled.
+fn tr(message: &str, args: &[&dyn fmt::Display]) -> String {
+    match CATALOG.get() {
+        Some(catalog) => catalog.format(message, args),
+        None => Catalog::default().format(message, args),
+    }
+}
+
+// How results are written, set once from the command line.
+static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+fn json_lines() -> bool {
+    OUTPUT.get() == Some(&Output::JsonLines)
+}
+
+// Write a line of results to stdout, or a result event holding it.
+fn emit<T: fmt::Display>(text: T) {
+    match json_lines() {
+        true => emit_result(None, None, Some(&text.to_string())),
+        false => println!("{}", text),
+    }
+}
+
+// Write results spanning lines, ending in a newline, as emit does each line.
+fn emit_block<T: fmt::Display>(text: T) {
+    match json_lines() {
+        true => text.to_string().lines().for_each(emit),
+        false => print!("{}", text),
+    }
+}
+
+// Write a file found by a command, with its tags when given, coloured
+// as in the ontology unless written as json-lines.
+fn emit_found(path: &Path, tags: Option<&[String]>, ontology: &Ontology, color: bool) {
+    match (json_lines(), tags) {
+        (true, _) => emit_result(Some(path), tags, None),
+        (false, Some(tags)) => println!(
+            "{}: {}",
+            path.display(),
+            tags.iter()
+                .map(|tag| paint_tag(tag, ontology, color))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+        (false, None) => println!("{}", path.display()),
+    }
+}
+
+fn emit_result(path: Option<&Path>, tags: Option<&[String]>, text: Option<&str>) {
+    println!("{}", Event::Result { path, tags, text }.line());
+}
+
+// Cancelled on Ctrl-C, once handled.
+static INTERRUPT: OnceLock<Cancel> = OnceLock::new();
+
+// Let Ctrl-C stop a long operation at a safe point rather than midway. A
+// second press ends the process at once.
+#[cfg(unix)]
+fn handle_interrupt() -> Cancel {
+    extern "C" fn interrupted(_: libc::c_int) {
+        if let Some(cancel) = INTERRUPT.get() {
+            cancel.cancel();
+        }
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+    let cancel = INTERRUPT.get_or_init(Cancel::default).clone();
+    let handler = interrupted as extern "C" fn(libc::c_int);
+    unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    cancel
+}
+
+#[cfg(not(unix))]
+fn handle_interrupt() -> Cancel {
+    Cancel::default()
+}
+
+// Write a warning or error to stderr, keeping stdout for results, or with
+// --output json-lines to stdout as an event among them. Messages without
+// placeholders are translated whole.
+fn warn(mut record: Record) {
+    if let Some(catalog) = CATALOG.get() {
+        record.message = catalog.translate(&record.message).to_owned();
+    }
+    if json_lines() {
+        println!("{}", Event::from_record(&record).line());
+        return;
+    }
+    eprintln!(
+        "{}",
+        record.render(LOG_FORMAT.get().copied().unwrap_or_default())
+    );
+}
+
+// Warn about a problem, append it to the incident log in the state directory
+// and, with the notify feature, raise a desktop notification.
+fn report_incident(message: &str) {
+    warn(Record::warning(Kind::Incident, None, message));
+    if let Some(dir) = nametag::config::state_dir() {
+        let logged = fs::create_dir_all(&dir).and_then(|_| {
+            let mut log = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join("incidents.log"))?;
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            writeln!(log, "{} {}", time, message)
+        });
+        if let Err(err) = logged {
+            warn(Record::warning(
+                Kind::Incident,
+                None,
+                format!("Could not log incident: {}", err),
+            ));
+        }
+    }
+    #[cfg(feature = "notify")]
+    {
+        let shown = notify_rust::Notification::new()
+            .summary("nametag")
+            .body(message)
+            .show();
+        if let Err(err) = shown {
+            warn(Record::warning(
+                Kind::Incident,
+                None,
+                format!("Could not show notification: {}", err),
+            ));
+        }
+    }
+}
+
+// A file to work on, with the collection root it was found under, if any.
+struct Found {
+    root: Option<PathBuf>,
+    path: PathBuf,
+    /// Recalled from a snapshot, as the root is not mounted.
+    offline: bool,
+}
+
+// Files named on the command line and within the roots of a collection.
+// Collection roots that are not mounted are skipped with a warning, or with
+// catalog set, recalled from their latest snapshot.
+fn gather_files(
+    config: &Config,
+    paths: &[PathBuf],
+    collection: Option<&str>,
+    catalog: bool,
+) -> Result<Vec<Found>, Box<dyn Error>> {
+    let mut files = expand_paths(paths, &config.walk)?
+        .into_iter()
+        .map(|path| Found {
+            root: None,
+            path,
+            offline: false,
+        })
+        .collect::<Vec<_>>();
+    if let Some(name) = collection {
+        for root in config.collection(name)? {
+            let (found, offline) = if root.is_dir() {
+                (walk(&root, &config.walk)?.files, false)
+            } else {
+                let snapshot = match catalog {
+                    true => Snapshot::latest_for(&std::env::current_dir()?.join(&root))?,
+                    false => None,
+                };
+                match snapshot {
+                    Some(snapshot) => (snapshot.tagged_paths(&config.tag_format()?), true),
+                    None => {
+                        warn(Record::warning(
+                            Kind::Offline,
+                            Some(&root),
+                            "Root is offline, skipping",
+                        ));
+                        continue;
+                    }
+                }
+            };
+            files.extend(found.into_iter().map(|path| Found {
+                root: Some(root.clone()),
+                path,
+                offline,
+            }));
+        }
+    }
+    Ok(files)
+}
+
+// Refuse to change a directory recursively if it is too broad, unless the
+// guard is lifted.
+fn check_root(guard: Option<&RootGuard>, dir: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(guard) = guard {
+        guard.check(&dir.canonicalize()?)?;
+    }
+    Ok(())
+}
+
+// Write a listing into each directory of a walk from the root, returning
+// how many were written. Tags are shown as named, so sensitive tags stay hidden.
+fn write_indexes(
+    root: &Path,
+    config: &Config,
+    format: &TagFormat,
+    ontology: &Ontology,
+    redaction: &Redaction,
+) -> io::Result<usize> {
+    let found = walk(root, &config.walk)?;
+    for dir in &found.dirs {
+        let dirs = found
+            .dirs
+            .iter()
+            .filter(|sub| sub.parent() == Some(dir))
+            .filter_map(|sub| sub.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let mut files = Vec::new();
+        for path in found.files.iter().filter(|path| path.parent() == Some(dir)) {
+            let name = file_name(path)?;
+            if name == readme::INDEX_NAME {
+                continue;
+            }
+            let tags = NameTag::from_path_with_format(path, config.extension, format.clone())
+                .get_tags()
+                .map(|tag| redaction.tag(&tag.to_string_lossy()))
+                .collect();
+            files.push(Listed {
+                name: name.to_string_lossy().into_owned(),
+                tags,
+            });
+        }
+        let title = fs::canonicalize(dir)?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "/".to_owned());
+        let listing = readme::render(&title, &dirs, &files, ontology);
+        fs::write(dir.join(readme::INDEX_NAME), listing)?;
+    }
+    Ok(found.dirs.len())
+}
+
+// Open a file with the opener for its tags, without waiting for it to close.
+fn open_file(ontology: &Ontology, tags: &[String], path: &Path) -> Result<(), Box<dyn Error>> {
+    open::command(ontology.opener(tags), path)?.spawn()?;
+    Ok(())
+}
+
+// The ontology sits beside whichever config file is in use.
+fn load_ontology(config: Option<&Path>) -> Result<Ontology, Box<dyn Error>> {
+    match config {
+        Some(config) => {
+            let path = config.with_file_name("ontology.toml");
+            if path.exists() {
+                Ok(Ontology::load(Some(&path))?)
+            } else {
+                Ok(Ontology::default())
+            }
+        }
+        None => Ok(Ontology::load(None)?),
+    }
+}
+
+// Bundles beside the config file in use.
+fn bundles_path(config: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+    match config {
+        Some(config) => Ok(config.with_file_name("bundles.toml")),
+        None => Ok(Bundles::default_path().ok_or("No config directory available.")?),
+    }
+}
+
+fn file_name(path: &Path) -> io::Result<&OsStr> {
+    path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No file name in path: {}", path.display()),
+        )
+    })
+}
+
+#[cfg(feature = "clipboard")]
+fn clipboard() -> io::Result<arboard::Clipboard> {
+    arboard::Clipboard::new().map_err(clipboard_error)
+}
+
+#[cfg(feature = "clipboard")]
+fn clipboard_error(err: arboard::Error) -> io::Error {
+    io::Error::other(format!("Clipboard: {}", err))
 }
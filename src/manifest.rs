@@ -0,0 +1,337 @@
+// A manifest of the files under a directory with their tags, one per line
+// as tab separated values, and each batch of renames as a patch to it.
+// eg MANIFEST.tsv
+// photos/a[beach].jpg	beach
+//
+// Patches are in the form git format-patch writes, so the history of the
+// tags can be replayed into a repository with git am, though the files
+// themselves are not kept there.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::batch::Rename;
+use crate::config::civil_from_days;
+
+/// Name of the manifest within patches.
+pub const FILE_NAME: &str = "MANIFEST.tsv";
+
+// Lines of context around each change
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Manifest {
+    files: BTreeMap<PathBuf, Vec<String>>,
+}
+
+impl Manifest {
+    pub fn insert<P: Into<PathBuf>>(&mut self, path: P, tags: Vec<String>) {
+        self.files.insert(path.into(), tags);
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.files.remove(path);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The manifest before a batch of renames, given the one after it.
+    /// Renames are of full paths, those outside root being left out.
+    /// tags_of gives the tags of a name.
+    pub fn undo<F: Fn(&Path) -> Vec<String>>(
+        &mut self,
+        root: &Path,
+        renames: &[Rename],
+        tags_of: F,
+    ) {
+        for rename in renames.iter().rev() {
+            if let Ok(to) = rename.to.strip_prefix(root) {
+                self.files.remove(to);
+            }
+            if let Ok(from) = rename.from.strip_prefix(root) {
+                self.files.insert(from.to_path_buf(), tags_of(from));
+            }
+        }
+    }
+
+    /// Manifests of only the files a batch of renames would change, before
+    /// and after it. tags_of gives the tags of a name.
+    pub fn planned<F: Fn(&Path) -> Vec<String>>(renames: &[Rename], tags_of: F) -> (Self, Self) {
+        let (mut before, mut after) = (Self::default(), Self::default());
+        for rename in renames {
+            before.insert(rename.from.clone(), tags_of(&rename.from));
+            after.insert(rename.to.clone(), tags_of(&rename.to));
+        }
+        (before, after)
+    }
+
+    // A line of the manifest, being the path alone for files without tags.
+    // Tabs and newlines in paths are escaped.
+    fn line(path: &Path, tags: &[String]) -> String {
+        let path = path
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n");
+        if tags.is_empty() {
+            path
+        } else {
+            format!("{}\t{}", path, tags.join(" "))
+        }
+    }
+
+    /// The manifest as it is written to a file.
+    pub fn render(&self) -> String {
+        self.files
+            .iter()
+            .map(|(path, tags)| Self::line(path, tags) + "\n")
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+// Lines of two manifests, matched up by path.
+fn compare(before: &Manifest, after: &Manifest) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut before = before.files.iter().peekable();
+    let mut after = after.files.iter().peekable();
+    loop {
+        let order = match (before.peek(), after.peek()) {
+            (Some((old, _)), Some((new, _))) => old.cmp(new),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => break,
+        };
+        match order {
+            std::cmp::Ordering::Less => {
+                let (path, tags) = before.next().unwrap();
+                lines.push(Line::Removed(Manifest::line(path, tags)));
+            }
+            std::cmp::Ordering::Greater => {
+                let (path, tags) = after.next().unwrap();
+                lines.push(Line::Added(Manifest::line(path, tags)));
+            }
+            std::cmp::Ordering::Equal => {
+                let (path, old) = before.next().unwrap();
+                let (_, new) = after.next().unwrap();
+                if old == new {
+                    lines.push(Line::Same(Manifest::line(path, old)));
+                } else {
+                    lines.push(Line::Removed(Manifest::line(path, old)));
+                    lines.push(Line::Added(Manifest::line(path, new)));
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// Hunks of a unified diff between two manifests, empty if they are the same.
+pub fn diff(before: &Manifest, after: &Manifest) -> String {
+    let lines = compare(before, after);
+    let changed = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    // Ranges of lines shown, joining changes whose context would overlap.
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    let mut out = String::new();
+    for (start, end) in ranges {
+        let counts = |lines: &[Line]| {
+            let old = lines
+                .iter()
+                .filter(|line| !matches!(line, Line::Added(_)))
+                .count();
+            let new = lines
+                .iter()
+                .filter(|line| !matches!(line, Line::Removed(_)))
+                .count();
+            (old, new)
+        };
+        let (old_before, new_before) = counts(&lines[..start]);
+        let (old_len, new_len) = counts(&lines[start..end]);
+        // An empty range is numbered by the line before it.
+        let first = |before: usize, len: usize| before + usize::from(len > 0);
+        out += &format!(
+            "@@ -{},{} +{},{} @@\n",
+            first(old_before, old_len),
+            old_len,
+            first(new_before, new_len),
+            new_len
+        );
+        for line in &lines[start..end] {
+            let (mark, text) = match line {
+                Line::Same(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            out += &format!("{}{}\n", mark, text);
+        }
+    }
+    out
+}
+
+/// A unified diff between two manifests, with file headers as diff -u
+/// writes them. Empty if they are the same.
+pub fn unified(before: &Manifest, after: &Manifest) -> String {
+    let hunks = diff(before, after);
+    if hunks.is_empty() {
+        return hunks;
+    }
+    format!("--- a/{0}\n+++ b/{0}\n{1}", FILE_NAME, hunks)
+}
+
+/// A patch in the form of git format-patch, taking the manifest from before
+/// to after, or creating it if there was none before. Empty if nothing changed.
+pub fn patch(before: Option<&Manifest>, after: &Manifest, message: &str, time: u64) -> String {
+    let hunks = diff(before.unwrap_or(&Manifest::default()), after);
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let (subject, body) = match message.trim().split_once('\n') {
+        Some((subject, body)) => (subject.trim(), format!("\n{}\n", body.trim())),
+        None => (message.trim(), String::new()),
+    };
+    let files = match before {
+        Some(_) => format!("--- a/{0}\n+++ b/{0}\n", FILE_NAME),
+        None => format!("new file mode 100644\n--- /dev/null\n+++ b/{}\n", FILE_NAME),
+    };
+    format!(
+        "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001\n\
+         From: nametag <nametag@localhost>\n\
+         Date: {date}\n\
+         Subject: [PATCH] {subject}\n\
+         {body}\n\
+         ---\n\
+         diff --git a/{name} b/{name}\n\
+         {files}{hunks}-- \n\
+         nametag\n\n",
+        date = rfc2822(time),
+        subject = subject,
+        body = body,
+        name = FILE_NAME,
+        files = files,
+        hunks = hunks,
+    )
+}
+
+// eg Thu, 1 Jan 1970 00:00:00 +0000
+fn rfc2822(time: u64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = (time / 86400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let seconds = time % 86400;
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} +0000",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(files: &[(&str, &str)]) -> Manifest {
+        let mut manifest = Manifest::default();
+        for (path, tags) in files {
+            let tags = tags.split_whitespace().map(str::to_owned).collect();
+            manifest.insert(*path, tags);
+        }
+        manifest
+    }
+
+    #[test]
+    fn test_render() {
+        let manifest = manifest(&[("b[y].txt", "y"), ("a\tb.txt", "")]);
+        assert_eq!("a\\tb.txt\nb[y].txt\ty\n", manifest.render());
+    }
+    #[test]
+    fn test_diff() {
+        let names = ["a", "b", "c", "d", "e", "f", "g", "h", "i"];
+        let before = manifest(&names.map(|name| (name, "")));
+        let mut after = before.clone();
+        after.insert("b", vec!["x".to_owned()]);
+        after.remove(Path::new("h"));
+        assert_eq!(
+            "@@ -1,9 +1,8 @@\n a\n-b\n+b\tx\n c\n d\n e\n f\n g\n-h\n i\n",
+            diff(&before, &after)
+        );
+        after.insert("0", Vec::new());
+        after.insert("b", Vec::new());
+        after.insert("h", Vec::new());
+        assert_eq!("@@ -1,3 +1,4 @@\n+0\n a\n b\n c\n", diff(&before, &after));
+        assert_eq!("", diff(&before, &before));
+    }
+    #[test]
+    fn test_planned() {
+        let renames = [
+            Rename {
+                from: PathBuf::from("a.txt"),
+                to: PathBuf::from("a[x].txt"),
+            },
+            Rename {
+                from: PathBuf::from("b[x].txt"),
+                to: PathBuf::from("b[x y].txt"),
+            },
+        ];
+        let tags_of = |path: &Path| match path.to_str() {
+            Some("a[x].txt") | Some("b[x].txt") => vec!["x".to_owned()],
+            Some("b[x y].txt") => vec!["x".to_owned(), "y".to_owned()],
+            _ => Vec::new(),
+        };
+        let (before, after) = Manifest::planned(&renames, tags_of);
+        assert_eq!(
+            "--- a/MANIFEST.tsv\n+++ b/MANIFEST.tsv\n\
+             @@ -1,2 +1,2 @@\n-a.txt\n+a[x].txt\tx\n+b[x y].txt\tx y\n-b[x].txt\tx\n",
+            unified(&before, &after)
+        );
+        assert_eq!("", unified(&before, &before));
+    }
+    #[test]
+    fn test_undo() {
+        let mut manifest = manifest(&[("a[x].txt", "x")]);
+        let renames = [Rename {
+            from: PathBuf::from("/root/a.txt"),
+            to: PathBuf::from("/root/a[x].txt"),
+        }];
+        manifest.undo(Path::new("/root"), &renames, |_| Vec::new());
+        assert_eq!("a.txt\n", manifest.render());
+    }
+    #[test]
+    fn test_patch() {
+        let after = manifest(&[("a[x].txt", "x")]);
+        let patch = patch(None, &after, "add x\n\nto a", 86400);
+        assert!(patch.contains("Date: Fri, 2 Jan 1970 00:00:00 +0000\nSubject: [PATCH] add x\n"));
+        assert!(patch.contains("\nto a\n\n---\n"));
+        assert!(
+            patch.contains("--- /dev/null\n+++ b/MANIFEST.tsv\n@@ -0,0 +1,1 @@\n+a[x].txt\tx\n")
+        );
+        assert_eq!("", super::patch(Some(&after), &after, "nothing", 0));
+    }
+}
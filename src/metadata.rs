@@ -0,0 +1,482 @@
+// Tags kept in file system metadata, where file managers can see them.
+// eg user.xdg.tags on Linux, or Finder tags on macOS
+//
+// Linux stores tags comma separated. macOS stores a binary property list of
+// strings, each optionally followed by a newline and a Finder color number.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::ffi::CString;
+use std::io;
+use std::path::Path;
+
+use crate::slug::{slug_tag, SlugMode};
+use crate::NameTag;
+
+#[cfg(target_os = "linux")]
+const ATTRIBUTE: &str = "user.xdg.tags";
+#[cfg(target_os = "macos")]
+const ATTRIBUTE: &str = "com.apple.metadata:_kMDItemUserTags";
+
+/// Tags held in the metadata of a file. None are held when the attribute is missing.
+pub fn read_tags(path: &Path) -> io::Result<Vec<String>> {
+    let data = match get_attribute(path)? {
+        Some(data) => data,
+        None => return Ok(Vec::new()),
+    };
+    decode(&data).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: Unreadable tags in metadata", path.display()),
+        )
+    })
+}
+
+/// Replace the tags held in the metadata of a file, removing the attribute
+/// when there are none.
+pub fn write_tags(path: &Path, tags: &[String]) -> io::Result<()> {
+    if tags.is_empty() {
+        return remove_attribute(path);
+    }
+    set_attribute(path, &encode(tags))
+}
+
+/// Write the tags of a name into the metadata of a file. eg metadata::export(&nametag, path)
+pub fn export(nametag: &NameTag, path: &Path) -> io::Result<()> {
+    let tags = nametag
+        .get_tags()
+        .map(|tag| tag.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    write_tags(path, &tags)
+}
+
+/// Tags held in the metadata of a file, made safe in names. File managers
+/// allow any text, eg a Finder tag "My Project", which would otherwise be
+/// read back from the name as two tags.
+pub fn read_safe_tags(path: &Path, mode: SlugMode) -> io::Result<Vec<String>> {
+    Ok(read_tags(path)?
+        .iter()
+        .map(|tag| slug_tag(tag, mode))
+        .filter(|tag| !tag.is_empty())
+        .collect())
+}
+
+/// Add the tags in the metadata of a file to a name, made safe as read_safe_tags.
+pub fn import(path: &Path, nametag: &mut NameTag, mode: SlugMode) -> io::Result<()> {
+    for tag in read_safe_tags(path, mode)? {
+        nametag.add_tag(tag);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn encode(tags: &[String]) -> Vec<u8> {
+    tags.join(",").into_bytes()
+}
+
+#[cfg(target_os = "linux")]
+fn decode(data: &[u8]) -> Option<Vec<String>> {
+    let text = std::str::from_utf8(data).ok()?;
+    Some(
+        text.split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn encode(tags: &[String]) -> Vec<u8> {
+    plist::encode(tags)
+}
+
+#[cfg(target_os = "macos")]
+fn decode(data: &[u8]) -> Option<Vec<String>> {
+    // Finder appends the color of a tag after a newline. eg Red\n6
+    let tags = plist::decode(data)?;
+    Some(
+        tags.into_iter()
+            .map(|tag| match tag.split_once('\n') {
+                Some((tag, _color)) => tag.to_owned(),
+                None => tag,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn encode(_tags: &[String]) -> Vec<u8> {
+    Vec::new()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn decode(_data: &[u8]) -> Option<Vec<String>> {
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn c_path(path: &Path) -> io::Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn get_attribute(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let path = c_path(path)?;
+    let name = CString::new(ATTRIBUTE).unwrap_or_default();
+    // Ask for the size first, then read. The value may grow in between, so
+    // retry until it fits.
+    loop {
+        let size = xattr::get(&path, &name, &mut [])?;
+        let size = match size {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+        let mut buffer = vec![0; size];
+        match xattr::get(&path, &name, &mut buffer) {
+            Ok(Some(read)) => {
+                buffer.truncate(read);
+                return Ok(Some(buffer));
+            }
+            Ok(None) => return Ok(None),
+            Err(err) if err.raw_os_error() == Some(libc::ERANGE) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn set_attribute(path: &Path, data: &[u8]) -> io::Result<()> {
+    let name = CString::new(ATTRIBUTE).unwrap_or_default();
+    xattr::set(&c_path(path)?, &name, data)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn remove_attribute(path: &Path) -> io::Result<()> {
+    let name = CString::new(ATTRIBUTE).unwrap_or_default();
+    xattr::remove(&c_path(path)?, &name)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn get_attribute(_path: &Path) -> io::Result<Option<Vec<u8>>> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn set_attribute(_path: &Path, _data: &[u8]) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn remove_attribute(_path: &Path) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Tags in metadata are only supported on Linux and macOS",
+    )
+}
+
+// Calls into the platform, with a missing attribute read as None.
+#[cfg(target_os = "linux")]
+mod xattr {
+    use std::ffi::CStr;
+    use std::io;
+
+    pub fn get(path: &CStr, name: &CStr, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        let read = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+        result(read).map(|read| read.map(|read| read as usize))
+    }
+
+    pub fn set(path: &CStr, name: &CStr, data: &[u8]) -> io::Result<()> {
+        let written = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                data.as_ptr().cast(),
+                data.len(),
+                0,
+            )
+        };
+        result(written as isize).map(|_| ())
+    }
+
+    pub fn remove(path: &CStr, name: &CStr) -> io::Result<()> {
+        result(unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) } as isize).map(|_| ())
+    }
+
+    fn result(code: isize) -> io::Result<Option<isize>> {
+        if code >= 0 {
+            return Ok(Some(code));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::ENODATA) => Ok(None),
+            _ => Err(err),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod xattr {
+    use std::ffi::CStr;
+    use std::io;
+
+    pub fn get(path: &CStr, name: &CStr, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        let read = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                0,
+                0,
+            )
+        };
+        result(read).map(|read| read.map(|read| read as usize))
+    }
+
+    pub fn set(path: &CStr, name: &CStr, data: &[u8]) -> io::Result<()> {
+        let written = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                data.as_ptr().cast(),
+                data.len(),
+                0,
+                0,
+            )
+        };
+        result(written as isize).map(|_| ())
+    }
+
+    pub fn remove(path: &CStr, name: &CStr) -> io::Result<()> {
+        result(unsafe { libc::removexattr(path.as_ptr(), name.as_ptr(), 0) } as isize).map(|_| ())
+    }
+
+    fn result(code: isize) -> io::Result<Option<isize>> {
+        if code >= 0 {
+            return Ok(Some(code));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::ENOATTR) => Ok(None),
+            _ => Err(err),
+        }
+    }
+}
+
+// The subset of binary property lists Finder uses for tags: an array of strings.
+#[cfg(any(target_os = "macos", test))]
+mod plist {
+    use std::convert::TryFrom;
+
+    const MAGIC: &[u8] = b"bplist00";
+    const TRAILER_LEN: usize = 32;
+
+    pub fn encode(tags: &[String]) -> Vec<u8> {
+        let count = tags.len() + 1;
+        let ref_size = if count < 256 { 1 } else { 2 };
+        let mut out = MAGIC.to_vec();
+        let mut offsets = vec![out.len()];
+        // The array comes first, referring to each string after it.
+        push_marker(&mut out, 0xa0, tags.len());
+        for index in 1..count {
+            out.extend(&index.to_be_bytes()[8 - ref_size..]);
+        }
+        for tag in tags {
+            offsets.push(out.len());
+            if tag.is_ascii() {
+                push_marker(&mut out, 0x50, tag.len());
+                out.extend(tag.as_bytes());
+            } else {
+                let units = tag.encode_utf16().collect::<Vec<_>>();
+                push_marker(&mut out, 0x60, units.len());
+                for unit in units {
+                    out.extend(unit.to_be_bytes());
+                }
+            }
+        }
+        let table = out.len();
+        let offset_size = int_size(table);
+        for offset in offsets {
+            out.extend(&offset.to_be_bytes()[8 - offset_size..]);
+        }
+        out.extend([0; 6]);
+        out.push(offset_size as u8);
+        out.push(ref_size as u8);
+        out.extend((count as u64).to_be_bytes());
+        out.extend(0u64.to_be_bytes());
+        out.extend((table as u64).to_be_bytes());
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Vec<String>> {
+        if !data.starts_with(MAGIC) || data.len() < MAGIC.len() + TRAILER_LEN {
+            return None;
+        }
+        let trailer = &data[data.len() - TRAILER_LEN..];
+        let offset_size = usize::from(trailer[6]);
+        let ref_size = usize::from(trailer[7]);
+        let count = read_int(&trailer[8..16])?;
+        let top = read_int(&trailer[16..24])?;
+        let table = read_int(&trailer[24..32])?;
+        let offset = |index: usize| {
+            let start = table.checked_add(index.checked_mul(offset_size)?)?;
+            read_int(data.get(start..start + offset_size)?)
+        };
+        if top >= count {
+            return None;
+        }
+        let start = offset(top)?;
+        let (marker, length, mut position) = read_marker(data, start)?;
+        if marker != 0xa0 {
+            return None;
+        }
+        let mut tags = Vec::new();
+        for _ in 0..length {
+            let index = read_int(data.get(position..position + ref_size)?)?;
+            position += ref_size;
+            if index >= count {
+                return None;
+            }
+            let (marker, length, body) = read_marker(data, offset(index)?)?;
+            let tag = match marker {
+                0x50 => String::from_utf8(data.get(body..body + length)?.to_vec()).ok()?,
+                0x60 => {
+                    let bytes = data.get(body..body + length * 2)?;
+                    let units = bytes
+                        .chunks(2)
+                        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                        .collect::<Vec<_>>();
+                    String::from_utf16(&units).ok()?
+                }
+                _ => return None,
+            };
+            tags.push(tag);
+        }
+        Some(tags)
+    }
+
+    // An object marker holding its length, which is followed by an int
+    // object when too long to fit.
+    fn push_marker(out: &mut Vec<u8>, marker: u8, length: usize) {
+        if length < 15 {
+            out.push(marker | length as u8);
+            return;
+        }
+        out.push(marker | 0x0f);
+        let size = int_size(length);
+        out.push(0x10 | size.trailing_zeros() as u8);
+        out.extend(&length.to_be_bytes()[8 - size..]);
+    }
+
+    // The kind of an object, its length, and where its body starts.
+    fn read_marker(data: &[u8], start: usize) -> Option<(u8, usize, usize)> {
+        let marker = *data.get(start)?;
+        let length = usize::from(marker & 0x0f);
+        if length < 15 {
+            return Some((marker & 0xf0, length, start + 1));
+        }
+        let int = *data.get(start + 1)?;
+        if int & 0xf0 != 0x10 {
+            return None;
+        }
+        let size = 1 << (int & 0x0f);
+        let length = read_int(data.get(start + 2..start + 2 + size)?)?;
+        Some((marker & 0xf0, length, start + 2 + size))
+    }
+
+    fn read_int(bytes: &[u8]) -> Option<usize> {
+        if bytes.len() > 8 {
+            return None;
+        }
+        let value = bytes
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+        usize::try_from(value).ok()
+    }
+
+    fn int_size(value: usize) -> usize {
+        match value {
+            0..=0xff => 1,
+            0x100..=0xffff => 2,
+            0x1_0000..=0xffff_ffff => 4,
+            _ => 8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plist_round_trip() {
+        let tags = vec![
+            "work".to_owned(),
+            "café".to_owned(),
+            "a much longer tag than fifteen".to_owned(),
+        ];
+        let data = plist::encode(&tags);
+        assert!(data.starts_with(b"bplist00"));
+        assert_eq!(Some(tags), plist::decode(&data));
+        assert_eq!(Some(Vec::new()), plist::decode(&plist::encode(&[])));
+        assert_eq!(None, plist::decode(b"bplist00"));
+    }
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_export_import() {
+        let dir = std::env::temp_dir().join("nametag-metadata");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        std::fs::File::create(&path).unwrap();
+        match export(&NameTag::new("a[x y].txt"), &path) {
+            Ok(()) => {}
+            // Some file systems, eg tmpfs on older kernels, have no user attributes.
+            Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return,
+            Err(err) => panic!("{}", err),
+        }
+        let mut name_tag = NameTag::new("a.txt");
+        import(&path, &mut name_tag, SlugMode::default()).unwrap();
+        assert_eq!(vec!["x", "y"], name_tag.get_tags().collect::<Vec<_>>());
+        write_tags(&path, &[]).unwrap();
+        assert!(read_tags(&path).unwrap().is_empty());
+    }
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_import_slug() {
+        let dir = std::env::temp_dir().join("nametag-metadata-slug");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        std::fs::File::create(&path).unwrap();
+        let tags = vec!["My Project".to_owned(), "Café".to_owned()];
+        match write_tags(&path, &tags) {
+            Ok(()) => {}
+            Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return,
+            Err(err) => panic!("{}", err),
+        }
+        let mut name_tag = NameTag::new("a.txt");
+        import(&path, &mut name_tag, SlugMode::Transliterate).unwrap();
+        assert_eq!(
+            vec!["Cafe", "My-Project"],
+            name_tag.get_tags().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["My%20Project", "Caf%C3%A9"],
+            read_safe_tags(&path, SlugMode::PercentEncode).unwrap()
+        );
+    }
+}
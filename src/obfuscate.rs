@@ -0,0 +1,211 @@
+// Hide sensitive tags in file names behind a key.
+// eg confidential -> x=k5vgqrdnmfxgs3q
+//
+// The obfuscated form is deterministic, so the same tag always looks the
+// same and files can still be grouped by it. This keeps tags from casual
+// readers of a shared drive. It is not a substitute for real encryption.
+use sha2::{Digest, Sha256};
+
+use crate::NameTag;
+
+const PREFIX: &str = "x=";
+const IV_LEN: usize = 4;
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Debug, Clone)]
+pub struct Obfuscator {
+    key: Vec<u8>,
+    sensitive: Vec<String>,
+}
+
+impl Obfuscator {
+    /// Obfuscate the listed tags using a key. eg a passphrase
+    pub fn new<K: Into<Vec<u8>>>(key: K, sensitive: Vec<String>) -> Self {
+        Self {
+            key: key.into(),
+            sensitive,
+        }
+    }
+
+    /// Check if a tag should be hidden.
+    pub fn is_sensitive(&self, tag: &str) -> bool {
+        self.sensitive.iter().any(|sensitive| sensitive == tag)
+    }
+
+    /// Obfuscated form of a tag.
+    pub fn encode(&self, tag: &str) -> String {
+        let iv = &self.digest(0, tag.as_bytes())[..IV_LEN];
+        let mut data = iv.to_vec();
+        data.extend(self.xor(iv, tag.as_bytes()));
+        format!("{}{}", PREFIX, base32_encode(&data))
+    }
+
+    /// The original tag, if this is an obfuscated tag made with the same key.
+    pub fn decode(&self, tag: &str) -> Option<String> {
+        let data = base32_decode(tag.strip_prefix(PREFIX)?)?;
+        if data.len() <= IV_LEN {
+            return None;
+        }
+        let (iv, body) = data.split_at(IV_LEN);
+        let plain = String::from_utf8(self.xor(iv, body)).ok()?;
+        // A different key produces a different iv, and so is rejected.
+        if &self.digest(0, plain.as_bytes())[..IV_LEN] == iv {
+            Some(plain)
+        } else {
+            None
+        }
+    }
+
+    /// A short digest that differs between keys and sensitive tags, without
+    /// giving the key away. eg to tell apart results found with each
+    pub fn fingerprint(&self) -> String {
+        let digest = self.digest(2, self.sensitive.join("\0").as_bytes());
+        digest[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Replace obfuscated tags with their original.
+    pub fn reveal(&self, nametag: &mut NameTag) {
+        let hidden = nametag
+            .get_tags()
+            .filter_map(|tag| {
+                let tag = tag.to_str()?;
+                Some((tag.to_owned(), self.decode(tag)?))
+            })
+            .collect::<Vec<_>>();
+        for (hidden, plain) in hidden {
+            nametag.remove_tag(hidden);
+            nametag.add_tag(plain);
+        }
+    }
+
+    /// Replace sensitive tags with their obfuscated form.
+    pub fn conceal(&self, nametag: &mut NameTag) {
+        let plain = nametag
+            .get_tags()
+            .filter_map(|tag| tag.to_str())
+            .filter(|tag| self.is_sensitive(tag))
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        for plain in plain {
+            nametag.add_tag(self.encode(&plain));
+            nametag.remove_tag(plain);
+        }
+    }
+
+    fn digest(&self, domain: u8, data: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.key);
+        hasher.update([domain]);
+        hasher.update(data);
+        hasher.finalize().into()
+    }
+
+    // Combine data with a keystream drawn from the key and iv
+    fn xor(&self, iv: &[u8], data: &[u8]) -> Vec<u8> {
+        data.chunks(32)
+            .enumerate()
+            .flat_map(|(block, chunk)| {
+                let mut seed = iv.to_vec();
+                seed.extend((block as u32).to_be_bytes());
+                let stream = self.digest(1, &seed);
+                chunk
+                    .iter()
+                    .zip(stream)
+                    .map(|(byte, key)| byte ^ key)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+// Lowercase, unpadded RFC 4648 base32, which is safe in file names on case
+// insensitive file systems.
+fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in data {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let value = BASE32.iter().position(|b| *b == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn obfuscator(key: &str) -> Obfuscator {
+        Obfuscator::new(key, vec!["confidential".to_owned()])
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        for data in &[&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            assert_eq!(*data, &base32_decode(&base32_encode(data)).unwrap()[..]);
+        }
+        assert_eq!("mzxw6ytboi", base32_encode(b"foobar"));
+    }
+    #[test]
+    fn test_encode_decode() {
+        let hidden = obfuscator("secret").encode("confidential");
+        assert!(hidden.starts_with("x="));
+        assert!(!hidden.contains("confidential"));
+        assert_eq!(hidden, obfuscator("secret").encode("confidential"));
+        assert_eq!(
+            Some("confidential".to_owned()),
+            obfuscator("secret").decode(&hidden)
+        );
+    }
+    #[test]
+    fn test_decode_wrong_key() {
+        let hidden = obfuscator("secret").encode("confidential");
+        assert_eq!(None, obfuscator("guess").decode(&hidden));
+        assert_eq!(None, obfuscator("secret").decode("plain"));
+    }
+    #[test]
+    fn test_fingerprint() {
+        let secret = obfuscator("secret").fingerprint();
+        assert_eq!(secret, obfuscator("secret").fingerprint());
+        assert_ne!(secret, obfuscator("guess").fingerprint());
+        assert!(!secret.contains("secret"));
+    }
+    #[test]
+    fn test_conceal_reveal() {
+        let obfuscator = obfuscator("secret");
+        let mut name_tag = NameTag::new("report[confidential draft].pdf");
+        obfuscator.conceal(&mut name_tag);
+        assert!(!name_tag.has_tag("confidential"));
+        assert!(name_tag.has_tag("draft"));
+        obfuscator.reveal(&mut name_tag);
+        assert_eq!(
+            "report[confidential draft].pdf",
+            &String::try_from(name_tag).unwrap()
+        );
+    }
+}
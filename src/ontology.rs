@@ -0,0 +1,135 @@
+// Descriptions of known tags, read from a toml file beside the config.
+// eg ~/.config/nametag/ontology.toml
+//
+// strict = true
+// [tag.invoice]
+// description = "Bills sent to clients"
+// color = "green"
+// category = "finance"
+// open = "evince"
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::color::Color;
+use crate::config::{Config, ConfigError};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TagInfo {
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub category: Option<String>,
+    /// Command opening files carrying the tag, given the path in place of {}
+    /// or after it. eg open = "gimp --new-instance"
+    pub open: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Ontology {
+    /// Treat tags missing from the ontology as mistakes.
+    pub strict: bool,
+    pub tag: BTreeMap<String, TagInfo>,
+}
+
+impl Ontology {
+    /// Load the ontology from a file. A missing file at the default location is an empty ontology.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let data = fs::read_to_string(&path).map_err(|err| ConfigError::Io(path.clone(), err))?;
+        toml::from_str(&data).map_err(|err| ConfigError::Parse(path.clone(), err))
+    }
+
+    /// The ontology lives beside the config file. eg ~/.config/nametag/ontology.toml
+    pub fn default_path() -> Option<PathBuf> {
+        Some(Config::default_path()?.with_file_name("ontology.toml"))
+    }
+
+    /// Information about a tag, if it is known. Value tags fall back to their key. eg year=2012 -> year
+    pub fn get(&self, tag: &str) -> Option<&TagInfo> {
+        self.tag.get(tag).or_else(|| {
+            let (key, _) = tag.split_once('=')?;
+            self.tag.get(key)
+        })
+    }
+
+    /// Color for a tag. Declared colors take precedence over a hashed fallback.
+    /// Value tags share the color of their key.
+    pub fn color(&self, tag: &str) -> Color {
+        self.get(tag)
+            .and_then(|info| info.color.as_deref()?.parse().ok())
+            .unwrap_or_else(|| Color::from_hash(tag.split('=').next().unwrap_or(tag)))
+    }
+
+    /// The opener of the first of a file's tags to have one.
+    pub fn opener<S: AsRef<str>>(&self, tags: &[S]) -> Option<&str> {
+        tags.iter()
+            .find_map(|tag| self.get(tag.as_ref())?.open.as_deref())
+    }
+
+    /// Check if a tag is declared.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.get(tag).is_some()
+    }
+}
+
+impl std::str::FromStr for Ontology {
+    type Err = toml::de::Error;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        toml::from_str(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ontology() {
+        let ontology: Ontology = r#"
+            strict = true
+            [tag.invoice]
+            description = "Bills sent to clients"
+            color = "green"
+            category = "finance"
+        "#
+        .parse()
+        .unwrap();
+        assert!(ontology.strict);
+        assert!(ontology.contains("invoice"));
+        assert!(!ontology.contains("receipt"));
+        let info = ontology.get("invoice").unwrap();
+        assert_eq!(Some("finance"), info.category.as_deref());
+        assert_eq!(Some("green"), info.color.as_deref());
+    }
+    #[test]
+    fn test_tag_color() {
+        let ontology: Ontology = "[tag.invoice]\ncolor = \"green\"".parse().unwrap();
+        assert_eq!(Color::Ansi(32), ontology.color("invoice"));
+        assert_eq!(Color::from_hash("receipt"), ontology.color("receipt"));
+    }
+    #[test]
+    fn test_opener() {
+        let ontology: Ontology = "[tag.invoice]\nopen = \"evince\"\n[tag.work]"
+            .parse()
+            .unwrap();
+        assert_eq!(Some("evince"), ontology.opener(&["work", "invoice"]));
+        assert_eq!(None, ontology.opener(&["work"]));
+    }
+    #[test]
+    fn test_value_tag_lookup() {
+        let ontology: Ontology = "[tag.year]".parse().unwrap();
+        assert!(ontology.contains("year=2012"));
+        assert!(!ontology.contains("month=12"));
+    }
+}
@@ -0,0 +1,71 @@
+// Opening files in the tool suited to their tags, as the ontology gives.
+// eg [tag.invoice]
+//    open = "evince --fullscreen {}"
+//
+// Files without an opener go to whatever the system opens them with.
+use std::path::Path;
+use std::process::Command;
+
+use crate::simulate::split_args;
+
+// Opens a path in its default application.
+#[cfg(target_os = "macos")]
+const SYSTEM_OPENER: &[&str] = &["open"];
+#[cfg(windows)]
+const SYSTEM_OPENER: &[&str] = &["cmd", "/C", "start", ""];
+#[cfg(not(any(target_os = "macos", windows)))]
+const SYSTEM_OPENER: &[&str] = &["xdg-open"];
+
+/// The command opening a path with an opener, else the system's default.
+pub fn command(opener: Option<&str>, path: &Path) -> Result<Command, String> {
+    let words = match opener {
+        Some(opener) => split_args(opener)?,
+        None => SYSTEM_OPENER.iter().map(|word| word.to_string()).collect(),
+    };
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| "An opener needs a command.".to_owned())?;
+    let mut command = Command::new(program);
+    let mut placed = false;
+    for arg in args {
+        match arg.as_str() {
+            "{}" => {
+                command.arg(path);
+                placed = true;
+            }
+            _ => {
+                command.arg(arg);
+            }
+        }
+    }
+    if !placed {
+        command.arg(path);
+    }
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn args(command: &Command) -> Vec<&OsStr> {
+        command.get_args().collect()
+    }
+
+    #[test]
+    fn test_command() {
+        let path = Path::new("a b[invoice].pdf");
+        let opened = command(Some("evince --fullscreen"), path).unwrap();
+        assert_eq!("evince", opened.get_program());
+        assert_eq!(
+            vec![OsStr::new("--fullscreen"), path.as_os_str()],
+            args(&opened)
+        );
+        let opened = command(Some("viewer '{}' -x"), path).unwrap();
+        assert_eq!(vec![path.as_os_str(), OsStr::new("-x")], args(&opened));
+        assert!(command(Some(""), path).is_err());
+        let opened = command(None, path).unwrap();
+        assert_eq!(Some(&path.as_os_str()), args(&opened).last());
+    }
+}
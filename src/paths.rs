@@ -0,0 +1,124 @@
+// Matching up paths recorded on different systems, so the same archive
+// read on macOS and Linux compares as the same files. macOS and Windows
+// ignore case, macOS writes accents as separate marks, and Windows
+// separates directories with backslashes.
+// eg "Photos/Cafe\u{301}.jpg" on macOS matches "photos/Café.jpg" on Linux
+use std::path::Path;
+
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PathMatching {
+    /// Paths match byte for byte.
+    Exact,
+    /// Paths match as the file systems of this platform compare them.
+    #[default]
+    Native,
+    /// Paths match ignoring case, how accents are written and separators,
+    /// for archives shared between platforms.
+    Portable,
+}
+
+impl PathMatching {
+    fn ignore_case(self) -> bool {
+        match self {
+            PathMatching::Exact => false,
+            PathMatching::Native => cfg!(any(target_os = "macos", windows)),
+            PathMatching::Portable => true,
+        }
+    }
+
+    fn compose(self) -> bool {
+        match self {
+            PathMatching::Exact => false,
+            PathMatching::Native => cfg!(target_os = "macos"),
+            PathMatching::Portable => true,
+        }
+    }
+
+    fn backslash_separates(self) -> bool {
+        match self {
+            PathMatching::Exact => false,
+            PathMatching::Native => cfg!(windows),
+            PathMatching::Portable => true,
+        }
+    }
+
+    /// A form of a path equal to that of every path it matches.
+    pub fn key(self, path: &Path) -> String {
+        let mut key = path.to_string_lossy().into_owned();
+        if self.backslash_separates() {
+            key = key.replace('\\', "/");
+        }
+        if self.compose() {
+            key = compose(&key);
+        }
+        if self.ignore_case() {
+            key = key.to_lowercase();
+        }
+        key
+    }
+
+    /// Whether two paths name the same file.
+    pub fn same(self, left: &Path, right: &Path) -> bool {
+        self.key(left) == self.key(right)
+    }
+}
+
+// Letters taking each combining mark, with the single characters for them.
+const COMPOSED: &[(char, &str, &str)] = &[
+    ('\u{300}', "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ('\u{301}', "AEIOUYaeiouyCcNnSsZz", "ÁÉÍÓÚÝáéíóúýĆćŃńŚśŹź"),
+    ('\u{302}', "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    ('\u{303}', "ANOano", "ÃÑÕãñõ"),
+    ('\u{304}', "AEIOUaeiou", "ĀĒĪŌŪāēīōū"),
+    ('\u{307}', "Zz", "Żż"),
+    ('\u{308}', "AEIOUaeiouy", "ÄËÏÖÜäëïöüÿ"),
+    ('\u{30A}', "Aa", "Åå"),
+    ('\u{30C}', "CcEeNnRrSsZz", "ČčĚěŇňŘřŠšŽž"),
+    ('\u{327}', "Cc", "Çç"),
+    ('\u{328}', "Ee", "Ęę"),
+];
+
+// Accented Latin letters written as a letter and a combining mark, as
+// macOS writes them, joined into the single characters written elsewhere.
+fn compose(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        let joined = out.chars().last().and_then(|last| {
+            let (_, bases, composed) = COMPOSED.iter().find(|(mark, _, _)| *mark == c)?;
+            let index = bases.chars().position(|base| base == last)?;
+            composed.chars().nth(index)
+        });
+        match joined {
+            Some(joined) => {
+                out.pop();
+                out.push(joined);
+            }
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose() {
+        assert_eq!("Café Münster", compose("Cafe\u{301} Mu\u{308}nster"));
+        assert_eq!("Café", compose("Café"));
+        assert_eq!("q\u{301}", compose("q\u{301}"));
+    }
+    #[test]
+    fn test_key() {
+        let mac = Path::new("Photos/Cafe\u{301}.jpg");
+        let windows = Path::new("photos\\café.JPG");
+        assert!(PathMatching::Portable.same(mac, windows));
+        assert!(!PathMatching::Exact.same(mac, windows));
+        assert_eq!("photos/café.jpg", PathMatching::Portable.key(mac));
+        assert_eq!("Photos/Cafe\u{301}.jpg", PathMatching::Exact.key(mac));
+    }
+}
@@ -0,0 +1,66 @@
+// Wildcard patterns for tags. eg proj-* or client=?cme
+//
+// "*" matches any run of characters, and "?" matches any one character.
+
+/// Check if text matches a wildcard pattern.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    // Greedy match, backtracking to the most recent star on a mismatch.
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Check if a pattern has any wildcards.
+pub fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("proj-*", "proj-apollo"));
+        assert!(matches("proj-*", "proj-"));
+        assert!(!matches("proj-*", "project"));
+        assert!(matches("*=acme", "client=acme"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "ac"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(!matches("*a*b", "xxbxxa"));
+        assert!(matches("exact", "exact"));
+        assert!(!matches("exact", "exactly"));
+    }
+    #[test]
+    fn test_is_pattern() {
+        assert!(is_pattern("proj-*"));
+        assert!(!is_pattern("proj"));
+    }
+}
@@ -0,0 +1,107 @@
+// Combinations of tags that must not appear together on a file.
+// eg forbid = [["confidential", "public"], ["draft", "final"]]
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::pattern;
+use crate::NameTag;
+
+#[derive(Debug, Default, Clone)]
+pub struct Policy {
+    forbidden: Vec<Vec<String>>,
+}
+
+impl Policy {
+    /// Each combination is a list of tag patterns that may not all match at once.
+    pub fn new(forbidden: Vec<Vec<String>>) -> Self {
+        Self {
+            forbidden: forbidden
+                .into_iter()
+                .filter(|combination| !combination.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Forbidden combinations present on a file.
+    pub fn violations(&self, nametag: &NameTag) -> Vec<&[String]> {
+        let tags = nametag
+            .get_tags()
+            .filter_map(|tag| tag.to_str())
+            .collect::<Vec<_>>();
+        self.forbidden
+            .iter()
+            .filter(|combination| {
+                combination
+                    .iter()
+                    .all(|pattern| tags.iter().any(|tag| pattern::matches(pattern, tag)))
+            })
+            .map(Vec::as_slice)
+            .collect()
+    }
+
+    /// Forbidden combinations present after an edit that were not present before.
+    pub fn introduced<'a>(&'a self, before: &NameTag, after: &NameTag) -> Vec<&'a [String]> {
+        let existing = self.violations(before);
+        self.violations(after)
+            .into_iter()
+            .filter(|violation| !existing.contains(violation))
+            .collect()
+    }
+}
+
+/// An edit refused as it would combine forbidden tags on a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forbidden {
+    pub path: PathBuf,
+    pub combination: Vec<String>,
+}
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: Refusing to combine forbidden tags: {}",
+            self.path.display(),
+            self.combination.join(" + ")
+        )
+    }
+}
+
+impl Error for Forbidden {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> Policy {
+        Policy::new(vec![
+            vec!["confidential".to_owned(), "public".to_owned()],
+            vec!["client=*".to_owned(), "internal".to_owned()],
+        ])
+    }
+
+    #[test]
+    fn test_violations() {
+        let policy = policy();
+        let name_tag = NameTag::new("a[confidential public].txt");
+        assert_eq!(1, policy.violations(&name_tag).len());
+        let name_tag = NameTag::new("a[client=acme internal].txt");
+        assert_eq!(1, policy.violations(&name_tag).len());
+        let name_tag = NameTag::new("a[confidential].txt");
+        assert!(policy.violations(&name_tag).is_empty());
+    }
+    #[test]
+    fn test_introduced() {
+        let policy = policy();
+        let before = NameTag::new("a[confidential].txt");
+        let mut after = NameTag::new("a[confidential].txt");
+        after.add_tag("public");
+        assert_eq!(1, policy.introduced(&before, &after).len());
+        // Existing violations are not blamed on the edit.
+        let before = NameTag::new("a[confidential public].txt");
+        let mut after = NameTag::new("a[confidential public].txt");
+        after.add_tag("draft");
+        assert!(policy.introduced(&before, &after).is_empty());
+    }
+}
@@ -0,0 +1,754 @@
+// Expressions selecting files by their tags.
+// eg work AND (urgent OR NOT done)
+//
+// Tags may use wildcards, eg proj-*, and AND binds tighter than OR.
+// key:value matches files whose multi-valued tag holds the value, either
+// in the name or in a block of machine tags. eg authors:alice
+// Numeric values compare with <, <=, > and >=, or fall in an inclusive
+// range. eg rating>=4, seq:10..20
+// name:~ matches files whose name without its tags holds some text,
+// ignoring case. eg name:~acme
+//
+// Programs may build queries directly instead, so tag names from users
+// need no quoting and match only themselves, wildcards included.
+// eg Query::tag("work").and(Query::not(Query::pattern("proj-*")))
+use std::fmt;
+use std::ops;
+use std::str::FromStr;
+
+use crate::derive::Compare;
+use crate::pattern;
+use crate::{NameTag, VALUE_SEPARATOR};
+
+// Prefix of a word matching names rather than tags.
+const NAME: &str = "name:~";
+
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    /// Character offset of the offending token.
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// A tag pattern, with wildcards.
+    Tag(String),
+    /// A tag matched exactly, as built from text given by users.
+    Exact(String),
+    /// A value of a multi-valued tag, by key and value pattern.
+    Value(String, String),
+    /// Numeric values of a tag compared with a number. eg rating>=4
+    Compare(String, Compare, f64),
+    /// Numeric values of a tag within a range, inclusive. eg seq:10..20
+    Range(String, f64, f64),
+    /// Names holding some text, ignoring case, without their tags. eg name:~acme
+    Name(String),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+/// How each term of a query evaluated for a file, to find out why it did
+/// or did not match.
+#[derive(Debug, PartialEq)]
+pub struct Explanation {
+    /// The term as written in a query. eg NOT done
+    pub term: String,
+    pub matched: bool,
+    /// Tags or values the term found, for tag and value terms.
+    pub found: Vec<String>,
+    /// How the terms within evaluated, for NOT, AND and OR.
+    pub parts: Vec<Explanation>,
+}
+
+impl Explanation {
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(
+            f,
+            "{}{}: {}",
+            "  ".repeat(depth),
+            self.term,
+            if self.matched { "yes" } else { "no" }
+        )?;
+        if !self.found.is_empty() {
+            write!(f, " ({})", self.found.join(" "))?;
+        }
+        writeln!(f)?;
+        self.parts
+            .iter()
+            .try_for_each(|part| part.write(f, depth + 1))
+    }
+}
+
+/// One line per term, with the terms within indented beneath it.
+/// eg work AND NOT done: no
+///      work: yes (work)
+///      NOT done: no
+///        done: yes (done)
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+}
+
+// Tokens with the character offset each starts at
+fn tokenize(text: &str) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+    let mut word: Option<(usize, String)> = None;
+    for (position, c) in text.chars().enumerate() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some((start, word)) = word.take() {
+                tokens.push((start, keyword(word)));
+            }
+            match c {
+                '(' => tokens.push((position, Token::Open)),
+                ')' => tokens.push((position, Token::Close)),
+                _ => {}
+            }
+        } else {
+            word.get_or_insert_with(|| (position, String::new()))
+                .1
+                .push(c);
+        }
+    }
+    if let Some((start, word)) = word {
+        tokens.push((start, keyword(word)));
+    }
+    tokens
+}
+
+fn keyword(word: String) -> Token {
+    match word.as_str() {
+        "AND" => Token::And,
+        "OR" => Token::Or,
+        "NOT" => Token::Not,
+        _ => Token::Word(word),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map_or(self.end, |(position, _)| *position)
+    }
+
+    fn error(&self, message: &str) -> QueryError {
+        QueryError {
+            position: self.position(),
+            message: message.to_owned(),
+        }
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut query = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.index += 1;
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        if self.peek() == Some(&Token::Not) {
+            self.index += 1;
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        self.term()
+    }
+
+    fn term(&mut self) -> Result<Query, QueryError> {
+        match self.peek() {
+            Some(Token::Open) => {
+                self.index += 1;
+                let query = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(self.error("Expected \")\""));
+                }
+                self.index += 1;
+                Ok(query)
+            }
+            Some(Token::Word(word)) => {
+                let query = term(word, self.position())?;
+                self.index += 1;
+                Ok(query)
+            }
+            Some(_) => Err(self.error("Expected a tag")),
+            None => Err(self.error("Expected a tag, but the query ended")),
+        }
+    }
+}
+
+// A single word of a query, starting at a character offset.
+fn term(word: &str, start: usize) -> Result<Query, QueryError> {
+    if let Some(text) = word.strip_prefix(NAME) {
+        if text.is_empty() {
+            return Err(QueryError {
+                position: start + NAME.len(),
+                message: format!("Expected text after \"{}\"", NAME),
+            });
+        }
+        return Ok(Query::Name(text.to_owned()));
+    }
+    let number = |text: &str, position: usize| {
+        text.parse::<f64>().map_err(|_| QueryError {
+            position,
+            message: format!("Expected a number, not \"{}\"", text),
+        })
+    };
+    if let Some((key, compare, target)) = Compare::split(word) {
+        if key.is_empty() {
+            return Err(QueryError {
+                position: start,
+                message: format!("Expected a tag before \"{}\"", compare.symbol()),
+            });
+        }
+        let position = start + key.chars().count() + compare.symbol().len();
+        return Ok(Query::Compare(
+            key.to_owned(),
+            compare,
+            number(target, position)?,
+        ));
+    }
+    match word.split_once(':') {
+        Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+            let position = start + key.chars().count() + 1;
+            match value.split_once("..") {
+                Some((low, high)) => Ok(Query::Range(
+                    key.to_owned(),
+                    number(low, position)?,
+                    number(high, position + low.chars().count() + 2)?,
+                )),
+                None => Ok(Query::Value(key.to_owned(), value.to_owned())),
+            }
+        }
+        _ => Ok(Query::Tag(word.to_owned())),
+    }
+}
+
+/// The query as it would be written, with parentheses only where needed.
+/// Exact tags are written as they are, so may read back as patterns.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Tag(tag) | Query::Exact(tag) => write!(f, "{}", tag),
+            Query::Value(key, value) => write!(f, "{}:{}", key, value),
+            Query::Compare(key, compare, target) => {
+                write!(f, "{}{}{}", key, compare.symbol(), target)
+            }
+            Query::Range(key, low, high) => write!(f, "{}:{}..{}", key, low, high),
+            Query::Name(text) => write!(f, "{}{}", NAME, text),
+            Query::Not(query) => match **query {
+                Query::And(..) | Query::Or(..) => write!(f, "NOT ({})", query),
+                _ => write!(f, "NOT {}", query),
+            },
+            Query::And(left, right) => {
+                let side = |query: &Query| match query {
+                    Query::Or(..) => format!("({})", query),
+                    _ => query.to_string(),
+                };
+                write!(f, "{} AND {}", side(left), side(right))
+            }
+            Query::Or(left, right) => write!(f, "{} OR {}", left, right),
+        }
+    }
+}
+
+/// Files not matching a query. eg !Query::tag("done")
+impl ops::Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query::Not(Box::new(self))
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(text),
+            index: 0,
+            end: text.chars().count(),
+        };
+        let query = parser.or()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("Expected AND or OR"));
+        }
+        Ok(query)
+    }
+}
+
+impl Query {
+    /// Files carrying a tag, matched exactly, so * and ? in it are plain.
+    pub fn tag<S: Into<String>>(tag: S) -> Self {
+        Query::Exact(tag.into())
+    }
+
+    /// Files carrying a tag matching a pattern. eg proj-*
+    pub fn pattern<S: Into<String>>(pattern: S) -> Self {
+        Query::Tag(pattern.into())
+    }
+
+    /// Files whose name without its tags holds some text, ignoring case.
+    pub fn name<S: Into<String>>(text: S) -> Self {
+        Query::Name(text.into())
+    }
+
+    /// Files whose multi-valued tag holds a value matching a pattern.
+    /// eg Query::value("authors", "alice")
+    pub fn value<K: Into<String>, V: Into<String>>(key: K, value: V) -> Self {
+        Query::Value(key.into(), value.into())
+    }
+
+    /// Files not matching a query, as ! does, without importing ops::Not.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(query: Query) -> Self {
+        Query::Not(Box::new(query))
+    }
+
+    /// Files matching both this query and another.
+    pub fn and(self, other: Query) -> Self {
+        Query::And(Box::new(self), Box::new(other))
+    }
+
+    /// Files matching either this query or another.
+    pub fn or(self, other: Query) -> Self {
+        Query::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Check if a set of tags satisfies the query. Without a name, name
+    /// terms match nothing.
+    pub fn matches<S: AsRef<str>>(&self, tags: &[S]) -> bool {
+        self.matches_named(tags, "")
+    }
+
+    /// As matches, for a file of a name given without its tags.
+    pub fn matches_named<S: AsRef<str>>(&self, tags: &[S], name: &str) -> bool {
+        self.matches_with(tags, &|key| values(tags, key), name)
+    }
+
+    // As matches_named, with values_of giving the values of a multi-valued tag.
+    fn matches_with<S: AsRef<str>>(
+        &self,
+        tags: &[S],
+        values_of: &dyn Fn(&str) -> Vec<String>,
+        name: &str,
+    ) -> bool {
+        match self {
+            Query::Name(text) => name.to_lowercase().contains(&text.to_lowercase()),
+            Query::Tag(_) | Query::Exact(_) => tags
+                .iter()
+                .any(|candidate| self.matches_tag(candidate.as_ref())),
+            Query::Value(key, _) | Query::Compare(key, ..) | Query::Range(key, ..) => {
+                values_of(key).iter().any(|value| self.accepts(value))
+            }
+            Query::Not(query) => !query.matches_with(tags, values_of, name),
+            Query::And(left, right) => {
+                left.matches_with(tags, values_of, name)
+                    && right.matches_with(tags, values_of, name)
+            }
+            Query::Or(left, right) => {
+                left.matches_with(tags, values_of, name)
+                    || right.matches_with(tags, values_of, name)
+            }
+        }
+    }
+
+    /// How each term evaluates for a set of tags, as matches_named would.
+    pub fn explain<S: AsRef<str>>(&self, tags: &[S], name: &str) -> Explanation {
+        self.explain_with(tags, &|key| values(tags, key), name)
+    }
+
+    /// How each term evaluates for the tags on a name, as matches_name would.
+    pub fn explain_name(&self, nametag: &NameTag) -> Explanation {
+        let tags = nametag
+            .get_tags()
+            .map(|tag| tag.to_string_lossy())
+            .collect::<Vec<_>>();
+        let name = nametag.untagged();
+        self.explain_with(
+            &tags,
+            &|key| nametag.get_values(key),
+            &name.to_string_lossy(),
+        )
+    }
+
+    fn explain_with<S: AsRef<str>>(
+        &self,
+        tags: &[S],
+        values_of: &dyn Fn(&str) -> Vec<String>,
+        name: &str,
+    ) -> Explanation {
+        let (found, parts) = match self {
+            Query::Name(_) => (Vec::new(), Vec::new()),
+            Query::Tag(_) | Query::Exact(_) => (
+                tags.iter()
+                    .map(AsRef::as_ref)
+                    .filter(|tag| self.matches_tag(tag))
+                    .map(str::to_owned)
+                    .collect(),
+                Vec::new(),
+            ),
+            Query::Value(key, _) | Query::Compare(key, ..) | Query::Range(key, ..) => (
+                values_of(key)
+                    .into_iter()
+                    .filter(|value| self.accepts(value))
+                    .collect(),
+                Vec::new(),
+            ),
+            Query::Not(query) => (Vec::new(), vec![query.explain_with(tags, values_of, name)]),
+            // Chains of the same operator are explained as one term.
+            Query::And(..) | Query::Or(..) => (
+                Vec::new(),
+                self.operands()
+                    .iter()
+                    .map(|query| query.explain_with(tags, values_of, name))
+                    .collect(),
+            ),
+        };
+        Explanation {
+            term: self.to_string(),
+            matched: self.matches_with(tags, values_of, name),
+            found,
+            parts,
+        }
+    }
+
+    /// The same query in a canonical form: double negations removed, and the
+    /// terms of each chain of AND or OR in order with repeats dropped. eg
+    /// "b AND a AND NOT NOT b" -> "a AND b"
+    pub fn normalize(&self) -> Query {
+        match self {
+            Query::Not(query) => match query.normalize() {
+                Query::Not(query) => *query,
+                query => !query,
+            },
+            Query::And(..) | Query::Or(..) => {
+                let mut operands = self
+                    .operands()
+                    .into_iter()
+                    .flat_map(|query| {
+                        let query = query.normalize();
+                        // Normalizing may reveal a chain of the same operator.
+                        match (self, &query) {
+                            (Query::And(..), Query::And(..)) | (Query::Or(..), Query::Or(..)) => {
+                                query.operands().into_iter().cloned().collect()
+                            }
+                            _ => vec![query],
+                        }
+                    })
+                    .map(|query| (query.to_string(), query))
+                    .collect::<Vec<_>>();
+                operands.sort_by(|(a, _), (b, _)| a.cmp(b));
+                operands.dedup_by(|(a, _), (b, _)| a == b);
+                let mut operands = operands.into_iter().map(|(_, query)| query);
+                let first = operands.next().expect("a chain has operands");
+                operands.fold(first, |chain, query| match self {
+                    Query::And(..) => chain.and(query),
+                    _ => chain.or(query),
+                })
+            }
+            query => query.clone(),
+        }
+    }
+
+    /// Parts of a query that can never match, such as "a AND NOT a" or an
+    /// empty range, as found after normalizing.
+    pub fn contradictions(&self) -> Vec<Query> {
+        let mut found = Vec::new();
+        self.normalize().contradictions_into(&mut found);
+        found
+    }
+
+    fn contradictions_into(&self, found: &mut Vec<Query>) {
+        match self {
+            Query::Range(_, low, high) if low > high => found.push(self.clone()),
+            Query::Not(query) => query.contradictions_into(found),
+            Query::And(..) | Query::Or(..) => {
+                let operands = self.operands();
+                let negated = |query: &Query| operands.contains(&&!query.clone());
+                if matches!(self, Query::And(..)) && operands.iter().any(|query| negated(query)) {
+                    found.push(self.clone());
+                    return;
+                }
+                for query in operands {
+                    query.contradictions_into(found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // The queries joined by a chain of AND, or of OR, at the top of a query.
+    pub(crate) fn operands(&self) -> Vec<&Query> {
+        let mut operands = Vec::new();
+        let mut pending = vec![self];
+        while let Some(query) = pending.pop() {
+            match (self, query) {
+                (Query::And(..), Query::And(left, right))
+                | (Query::Or(..), Query::Or(left, right)) => {
+                    pending.push(right);
+                    pending.push(left);
+                }
+                _ => operands.push(query),
+            }
+        }
+        operands
+    }
+
+    /// Whether a single tag is one a tag, pattern or value query asks for.
+    pub fn matches_tag(&self, candidate: &str) -> bool {
+        match self {
+            Query::Tag(tag) => pattern::matches(tag, candidate),
+            Query::Exact(tag) => tag == candidate,
+            Query::Value(key, _) | Query::Compare(key, ..) | Query::Range(key, ..) => {
+                values(&[candidate], key)
+                    .iter()
+                    .any(|value| self.accepts(value))
+            }
+            _ => false,
+        }
+    }
+
+    // Whether a value of a multi-valued tag is one a value query asks for.
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            Query::Value(_, wanted) => pattern::matches(wanted, value),
+            Query::Compare(_, compare, target) => value
+                .parse::<f64>()
+                .is_ok_and(|value| compare.holds(value, *target)),
+            Query::Range(_, low, high) => value
+                .parse::<f64>()
+                .is_ok_and(|value| *low <= value && value <= *high),
+            _ => false,
+        }
+    }
+
+    /// Check if the tags on a name, and the name itself, satisfy the query.
+    pub fn matches_name(&self, nametag: &NameTag) -> bool {
+        let tags = nametag
+            .get_tags()
+            .map(|tag| tag.to_string_lossy())
+            .collect::<Vec<_>>();
+        let name = nametag.untagged();
+        self.matches_with(
+            &tags,
+            &|key| nametag.get_values(key),
+            &name.to_string_lossy(),
+        )
+    }
+}
+
+// Values of a multi-valued tag among tags. eg authors=alice+bob
+fn values<S: AsRef<str>>(tags: &[S], key: &str) -> Vec<String> {
+    tags.iter()
+        .filter_map(|tag| tag.as_ref().strip_prefix(key)?.strip_prefix('='))
+        .flat_map(|values| values.split(VALUE_SEPARATOR))
+        .filter(|value| !value.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::TagFormat;
+
+    fn query(text: &str) -> Query {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let tag = |tag: &str| Box::new(Query::Tag(tag.to_owned()));
+        assert_eq!(
+            Query::Or(
+                Box::new(Query::And(tag("a"), tag("b"))),
+                Box::new(Query::Not(tag("c")))
+            ),
+            query("a AND b OR NOT c")
+        );
+        assert_eq!(
+            Query::And(tag("a"), Box::new(Query::Or(tag("b"), tag("c")))),
+            query("a AND (b OR c)")
+        );
+    }
+    #[test]
+    fn test_build() {
+        assert_eq!(
+            query("a AND b OR NOT c"),
+            Query::pattern("a")
+                .and(Query::pattern("b"))
+                .or(!Query::pattern("c"))
+        );
+        let query = Query::tag("a OR (b").and(!Query::tag("NOT"));
+        assert!(query.matches(&["a OR (b"]));
+        assert!(!query.matches(&["a", "b"]));
+        assert!(!Query::tag("*").matches(&["work"]));
+        assert!(Query::tag("*").matches(&["*"]));
+        assert!(Query::pattern("proj-*").matches(&["proj-x"]));
+        assert_eq!(!Query::tag("a"), Query::not(Query::tag("a")));
+    }
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| text.parse::<Query>().unwrap_err().position;
+        assert_eq!(9, error("a AND (b c)"));
+        assert_eq!(5, error("a OR "));
+        assert_eq!(8, error("(a AND b"));
+        assert_eq!(2, error("a ) b"));
+        assert_eq!(4, error("NOT AND a"));
+    }
+    #[test]
+    fn test_matches() {
+        let query = query("work AND (urgent OR NOT done)");
+        assert!(query.matches(&["work", "urgent", "done"]));
+        assert!(query.matches(&["work"]));
+        assert!(!query.matches(&["work", "done"]));
+        assert!(!query.matches(&["urgent"]));
+    }
+    #[test]
+    fn test_matches_value() {
+        let query = query("authors:alice AND NOT authors:b*");
+        assert!(query.matches(&["authors=carol+alice"]));
+        assert!(!query.matches(&["authors=alice+bob"]));
+        assert!(!query.matches(&["authors", "alice"]));
+        let machine =
+            NameTag::with_format("a[x]{authors=alice}.txt", TagFormat::named("dual").unwrap());
+        assert!(query.matches_name(&machine));
+        assert_eq!(
+            Query::value("authors", "alice"),
+            self::query("authors:alice")
+        );
+    }
+    #[test]
+    fn test_matches_numeric() {
+        let query = query("rating>=4 AND seq:10..20");
+        assert!(query.matches(&["rating=4", "seq=10"]));
+        assert!(query.matches(&["rating=4.5", "seq=20"]));
+        assert!(!query.matches(&["rating=3", "seq=15"]));
+        assert!(!query.matches(&["rating=5", "seq=21"]));
+        assert!(!query.matches(&["rating=high", "seq=15"]));
+        assert!(self::query("year<2015").matches(&["year=2019+2012"]));
+        assert_eq!(
+            Query::Compare("rating".to_owned(), Compare::GreaterEqual, 4.0),
+            self::query("rating>=4")
+        );
+    }
+    #[test]
+    fn test_parse_numeric_errors() {
+        let error = |text: &str| text.parse::<Query>().unwrap_err();
+        assert_eq!(14, error("a AND rating>=x").position);
+        assert_eq!(6, error("a AND <4").position);
+        assert_eq!(4, error("seq:a..20").position);
+        assert_eq!(8, error("seq:10..z").position);
+        assert_eq!("Expected a number, not \"z\"", error("seq:10..z").message);
+    }
+    #[test]
+    fn test_display() {
+        for text in &[
+            "a AND b OR NOT c",
+            "a AND (b OR c)",
+            "NOT (a AND b)",
+            "authors:alice OR rating>=4.5 AND seq:10..20",
+        ] {
+            assert_eq!(*text, query(text).to_string());
+        }
+        assert_eq!("a AND b", query("(a AND b)").to_string());
+    }
+    #[test]
+    fn test_explain() {
+        let explanation =
+            query("work AND NOT done AND rating>=4").explain(&["work", "rating=3"], "a.txt");
+        assert_eq!(
+            "work AND NOT done AND rating>=4: no\n  \
+             work: yes (work)\n  \
+             NOT done: yes\n    \
+             done: no\n  \
+             rating>=4: no\n",
+            explanation.to_string()
+        );
+        let machine = NameTag::with_format("a[x]{rating=5}.txt", TagFormat::named("dual").unwrap());
+        let explanation = query("rating>=4").explain_name(&machine);
+        assert!(explanation.matched);
+        assert_eq!(vec!["5"], explanation.found);
+    }
+    #[test]
+    fn test_normalize() {
+        let normal = |text: &str| query(text).normalize().to_string();
+        assert_eq!("a AND b", normal("b AND a AND NOT NOT b"));
+        assert_eq!("a AND b AND c", normal("c AND (b AND a)"));
+        assert_eq!("(a OR b) AND c", normal("c AND (b OR a OR b)"));
+        assert_eq!("NOT a", normal("NOT NOT NOT a"));
+        assert_eq!(query("a OR b").normalize(), query("b OR a").normalize());
+    }
+    #[test]
+    fn test_contradictions() {
+        assert_eq!(
+            vec![query("a AND NOT a").normalize()],
+            query("b OR NOT a AND a").contradictions()
+        );
+        assert_eq!(
+            vec![query("seq:20..10")],
+            query("x AND seq:20..10").contradictions()
+        );
+        assert!(query("a OR NOT a").contradictions().is_empty());
+        assert!(query("a AND NOT b").contradictions().is_empty());
+    }
+    #[test]
+    fn test_matches_name() {
+        let query = query("name:~ACME AND invoice");
+        assert!(query.matches_name(&NameTag::new("2020 Acme Corp[invoice].pdf")));
+        assert!(!query.matches_name(&NameTag::new("2020 Corp[invoice acme].pdf")));
+        assert!(!query.matches(&["invoice"]));
+        assert!(query.matches_named(&["invoice"], "acme.pdf"));
+        assert_eq!(Query::name("ACME"), self::query("name:~ACME"));
+        assert_eq!(6, "name:~".parse::<Query>().unwrap_err().position);
+    }
+    #[test]
+    fn test_matches_wildcard() {
+        let query = query("proj-* AND NOT client=?cme");
+        assert!(query.matches_name(&NameTag::new("a[proj-apollo].txt")));
+        assert!(!query.matches_name(&NameTag::new("a[proj-apollo client=acme].txt")));
+        assert!(!query.matches_name(&NameTag::new("a[project].txt")));
+    }
+}
@@ -0,0 +1,92 @@
+// Markdown listings of a directory's files and their tags, for handing an
+// archive to people without nametag. eg INDEX.md
+use crate::ontology::Ontology;
+
+/// File name listed in each directory.
+pub const INDEX_NAME: &str = "INDEX.md";
+
+/// A file as listed, with the tags to show for it.
+pub struct Listed {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+/// Markdown listing a directory. Subdirectories link to their own listing.
+pub fn render(title: &str, dirs: &[String], files: &[Listed], ontology: &Ontology) -> String {
+    let mut out = format!("# {}\n", title);
+    if !dirs.is_empty() {
+        out.push_str("\n## Directories\n\n");
+        for dir in dirs {
+            out.push_str(&format!(
+                "- [{}/]({}/{})\n",
+                escape(dir),
+                link(dir),
+                INDEX_NAME
+            ));
+        }
+    }
+    if !files.is_empty() {
+        out.push_str("\n## Files\n\n");
+        for file in files {
+            out.push_str(&format!("- {}\n", escape(&file.name)));
+            for tag in &file.tags {
+                match ontology
+                    .get(tag)
+                    .and_then(|info| info.description.as_deref())
+                {
+                    Some(description) => {
+                        out.push_str(&format!("  - `{}`: {}\n", tag, escape(description)))
+                    }
+                    None => out.push_str(&format!("  - `{}`\n", tag)),
+                }
+            }
+        }
+    }
+    out
+}
+
+// Keep brackets and other markdown syntax in file names literal.
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if matches!(c, '\\' | '[' | ']' | '*' | '_' | '`' | '#' | '<' | '>') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Spaces and brackets would end a markdown link target.
+fn link(dir: &str) -> String {
+    dir.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('(', "%28")
+        .replace(')', "%29")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let ontology: Ontology = r#"tag.invoice.description = "Bills sent to clients""#
+            .parse()
+            .unwrap();
+        let files = vec![Listed {
+            name: "a[invoice 2023].pdf".to_owned(),
+            tags: vec!["2023".to_owned(), "invoice".to_owned()],
+        }];
+        assert_eq!(
+            "# archive\n\
+             \n## Directories\n\n\
+             - [old stuff/](old%20stuff/INDEX.md)\n\
+             \n## Files\n\n\
+             - a\\[invoice 2023\\].pdf\n  \
+             - `2023`\n  \
+             - `invoice`: Bills sent to clients\n",
+            render("archive", &["old stuff".to_owned()], &files, &ontology)
+        );
+    }
+}
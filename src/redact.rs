@@ -0,0 +1,89 @@
+// Mask tags and shorten paths in output meant for sharing outside the team.
+//
+// [redact.external]
+// tags = ["client=*", "salary*"]
+// path_components = 1
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::pattern;
+
+const MASK: &str = "***";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Redaction {
+    /// Patterns of tags to mask. Value tags keep their key. eg client=acme -> client=***
+    pub tags: Vec<String>,
+    /// Keep only this many trailing components of each path.
+    pub path_components: Option<usize>,
+}
+
+impl Redaction {
+    /// A tag as it may be shown.
+    pub fn tag(&self, tag: &str) -> String {
+        if !self
+            .tags
+            .iter()
+            .any(|pattern| pattern::matches(pattern, tag))
+        {
+            return tag.to_owned();
+        }
+        match tag.split_once('=') {
+            Some((key, _)) => format!("{}={}", key, MASK),
+            None => MASK.to_owned(),
+        }
+    }
+
+    /// A path as it may be shown. eg /home/me/clients/acme/a.txt -> .../a.txt
+    pub fn path(&self, path: &Path) -> PathBuf {
+        let keep = match self.path_components {
+            Some(keep) => keep,
+            None => return path.to_path_buf(),
+        };
+        let parts = path
+            .components()
+            .filter(|part| matches!(part, Component::Normal(_)))
+            .collect::<Vec<_>>();
+        if parts.len() <= keep {
+            return path.to_path_buf();
+        }
+        let mut short = PathBuf::from("...");
+        short.extend(&parts[parts.len() - keep..]);
+        short
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redaction() -> Redaction {
+        Redaction {
+            tags: vec!["client=*".to_owned(), "salary*".to_owned()],
+            path_components: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_redact_tag() {
+        let redaction = redaction();
+        assert_eq!("client=***", redaction.tag("client=acme"));
+        assert_eq!("***", redaction.tag("salary-review"));
+        assert_eq!("invoice", redaction.tag("invoice"));
+    }
+    #[test]
+    fn test_redact_path() {
+        let redaction = redaction();
+        assert_eq!(
+            PathBuf::from(".../a.txt"),
+            redaction.path(Path::new("/home/me/acme/a.txt"))
+        );
+        assert_eq!(PathBuf::from("a.txt"), redaction.path(Path::new("a.txt")));
+        assert_eq!(
+            PathBuf::from("/a/b"),
+            Redaction::default().path(Path::new("/a/b"))
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Choices for renames that cannot go ahead as planned: those that would
+// overwrite another file, and edits that would give a file a forbidden
+// combination of tags. Each is asked for in turn, and kept so a similar
+// batch later can be settled the same way.
+// eg ~/.local/state/nametag/resolutions.json
+//
+// A choice is kept against the kind of problem and the name of the file, so
+// the same file meeting the same problem again is settled alike, wherever
+// the batch was run from.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::state_dir;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    /// The new name is taken by another file.
+    Collision,
+    /// The edit combines forbidden tags.
+    Forbidden,
+}
+
+impl Problem {
+    fn name(self) -> &'static str {
+        match self {
+            Problem::Collision => "collision",
+            Problem::Forbidden => "forbidden",
+        }
+    }
+
+    /// The choices open for the problem, as asked.
+    pub fn choices(self) -> &'static str {
+        match self {
+            Problem::Collision => "[s]kip, [r]ename NAME, [o]verwrite",
+            Problem::Forbidden => "[s]kip, [e]dit TAGS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Resolution {
+    /// Leave the file as it is.
+    Skip,
+    /// Give the file another name in the same directory.
+    Rename { name: String },
+    /// Replace the file with the new name.
+    Overwrite,
+    /// Give the file these tags instead.
+    Edit { tags: Vec<String> },
+}
+
+impl Resolution {
+    /// A choice as answered, if it is one open for the problem. eg "r a-2.txt"
+    pub fn parse(problem: Problem, answer: &str) -> Option<Self> {
+        let answer = answer.trim();
+        let (word, rest) = match answer.split_once(char::is_whitespace) {
+            Some((word, rest)) => (word, rest.trim()),
+            None => (answer, ""),
+        };
+        let resolution = match word {
+            "s" | "skip" if rest.is_empty() => Resolution::Skip,
+            "o" | "overwrite" if rest.is_empty() => Resolution::Overwrite,
+            "r" | "rename" if !rest.is_empty() && !rest.contains(['/', '\\']) => {
+                Resolution::Rename {
+                    name: rest.to_owned(),
+                }
+            }
+            "e" | "edit" => Resolution::Edit {
+                tags: rest.split_whitespace().map(str::to_owned).collect(),
+            },
+            _ => return None,
+        };
+        match (problem, &resolution) {
+            (_, Resolution::Skip)
+            | (Problem::Collision, Resolution::Rename { .. })
+            | (Problem::Collision, Resolution::Overwrite)
+            | (Problem::Forbidden, Resolution::Edit { .. }) => Some(resolution),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resolutions {
+    choices: BTreeMap<String, Resolution>,
+}
+
+impl Resolutions {
+    /// Where choices are kept.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("resolutions.json"))
+    }
+
+    /// Choices kept at a path, none if there are none yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    fn key(problem: Problem, path: &Path) -> String {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        format!("{}:{}", problem.name(), name.to_string_lossy())
+    }
+
+    /// The choice made before for a file meeting a problem.
+    pub fn get(&self, problem: Problem, path: &Path) -> Option<&Resolution> {
+        self.choices.get(&Self::key(problem, path))
+    }
+
+    pub fn record(&mut self, problem: Problem, path: &Path, resolution: Resolution) {
+        self.choices.insert(Self::key(problem, path), resolution);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Some(Resolution::Skip),
+            Resolution::parse(Problem::Collision, "s\n")
+        );
+        assert_eq!(
+            Some(Resolution::Rename {
+                name: "a 2.txt".to_owned()
+            }),
+            Resolution::parse(Problem::Collision, "r a 2.txt")
+        );
+        assert_eq!(
+            Some(Resolution::Edit {
+                tags: vec!["x".to_owned(), "y".to_owned()]
+            }),
+            Resolution::parse(Problem::Forbidden, "edit x  y")
+        );
+        // Not open for the problem, or malformed
+        assert_eq!(None, Resolution::parse(Problem::Forbidden, "o"));
+        assert_eq!(None, Resolution::parse(Problem::Collision, "e x"));
+        assert_eq!(None, Resolution::parse(Problem::Collision, "r"));
+        assert_eq!(None, Resolution::parse(Problem::Collision, "r ../a.txt"));
+        assert_eq!(None, Resolution::parse(Problem::Collision, "yes"));
+    }
+    #[test]
+    fn test_record() {
+        let path = env::temp_dir()
+            .join("nametag-resolutions")
+            .join("resolutions.json");
+        let _ = fs::remove_file(&path);
+        let mut resolutions = Resolutions::load(&path).unwrap();
+        resolutions.record(
+            Problem::Collision,
+            Path::new("/one/a.txt"),
+            Resolution::Overwrite,
+        );
+        resolutions.save(&path).unwrap();
+        let resolutions = Resolutions::load(&path).unwrap();
+        // Matched by name, wherever the file is.
+        assert_eq!(
+            Some(&Resolution::Overwrite),
+            resolutions.get(Problem::Collision, Path::new("other/a.txt"))
+        );
+        assert_eq!(
+            None,
+            resolutions.get(Problem::Forbidden, Path::new("a.txt"))
+        );
+    }
+}
@@ -0,0 +1,142 @@
+// How risky a batch of renames is, so sweeping changes are stopped for
+// confirmation before they happen. eg clearing the tags of a whole disk
+//
+// Points are scored for the number of files and directories touched, for
+// tags being removed rather than added, and for adding tags the ontology
+// does not know. A batch scoring HIGH or more is high risk.
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::batch::Rename;
+use crate::format::TagFormat;
+use crate::ontology::Ontology;
+use crate::{Extension, NameTag};
+
+/// Score at which a batch is high risk.
+pub const HIGH: u32 = 3;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Assessment {
+    pub score: u32,
+    /// What contributed to the score.
+    pub reasons: Vec<String>,
+}
+
+impl Assessment {
+    pub fn is_high(&self) -> bool {
+        self.score >= HIGH
+    }
+
+    fn add(&mut self, points: u32, reason: String) {
+        self.score += points;
+        self.reasons.push(reason);
+    }
+}
+
+fn tags(path: &Path, format: &TagFormat, extension: Extension) -> BTreeSet<String> {
+    NameTag::from_path_with_format(path, extension, format.clone())
+        .get_tags()
+        .map(|tag| tag.to_string_lossy().into_owned())
+        .collect()
+}
+
+pub fn assess(
+    renames: &[Rename],
+    format: &TagFormat,
+    extension: Extension,
+    ontology: &Ontology,
+) -> Assessment {
+    let mut assessment = Assessment::default();
+    let (mut added, mut removed, mut cleared) = (0, 0, 0);
+    let mut unknown = BTreeSet::new();
+    let mut dirs = BTreeSet::new();
+    for rename in renames {
+        let before = tags(&rename.from, format, extension);
+        let after = tags(&rename.to, format, extension);
+        added += after.difference(&before).count();
+        removed += before.difference(&after).count();
+        if !before.is_empty() && after.is_empty() {
+            cleared += 1;
+        }
+        if !ontology.tag.is_empty() {
+            unknown.extend(
+                after
+                    .difference(&before)
+                    .filter(|tag| ontology.get(tag).is_none())
+                    .cloned(),
+            );
+        }
+        dirs.insert(rename.from.parent().unwrap_or_else(|| Path::new("")));
+    }
+    let files = renames.len();
+    if files >= 100 {
+        let points = if files >= 1000 { 2 } else { 1 };
+        assessment.add(points, format!("renames {} files", files));
+    }
+    if dirs.len() >= 10 {
+        let points = if dirs.len() >= 100 { 2 } else { 1 };
+        assessment.add(points, format!("spans {} directories", dirs.len()));
+    }
+    if cleared >= 10 {
+        assessment.add(2, format!("clears every tag from {} files", cleared));
+    } else if removed >= 50 && removed > added {
+        assessment.add(1, format!("removes {} tags", removed));
+    }
+    if !unknown.is_empty() {
+        let unknown = unknown.into_iter().collect::<Vec<_>>();
+        assessment.add(
+            1,
+            format!("adds tags missing from the ontology: {}", unknown.join(" ")),
+        );
+    }
+    assessment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ontology::TagInfo;
+    use std::path::PathBuf;
+
+    fn renames(count: usize, from: &str, to: &str) -> Vec<Rename> {
+        (0..count)
+            .map(|index| Rename {
+                from: PathBuf::from(format!("dir{}/{}{}", index, index, from)),
+                to: PathBuf::from(format!("dir{}/{}{}", index, index, to)),
+            })
+            .collect()
+    }
+
+    fn assess(renames: &[Rename], ontology: &Ontology) -> Assessment {
+        super::assess(
+            renames,
+            &TagFormat::default(),
+            Extension::FirstDot,
+            ontology,
+        )
+    }
+
+    #[test]
+    fn test_small_batch() {
+        let assessment = assess(&renames(5, "[a b].txt", ".txt"), &Ontology::default());
+        assert_eq!(Assessment::default(), assessment);
+    }
+    #[test]
+    fn test_sweeping_clear() {
+        let assessment = assess(&renames(200, "[a b].txt", ".txt"), &Ontology::default());
+        assert_eq!(5, assessment.score);
+        assert!(assessment.is_high());
+        assert_eq!("clears every tag from 200 files", assessment.reasons[2]);
+    }
+    #[test]
+    fn test_unknown_tags() {
+        let mut ontology = Ontology::default();
+        ontology.tag.insert("a".to_owned(), TagInfo::default());
+        let assessment = assess(&renames(1, ".txt", "[a b].txt"), &ontology);
+        assert_eq!(1, assessment.score);
+        assert_eq!(
+            vec!["adds tags missing from the ontology: b".to_owned()],
+            assessment.reasons
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// Paths of results as written out: relative to the root each was found
+// under, so they read the same on any machine and can be checked in, or
+// absolute. eg searching ../photos, ../photos/2019/a[x].jpg is 2019/a[x].jpg
+//
+// A result may be reached through the root as given, as it was recorded
+// from the working directory, or through the real path it leads to, as an
+// index records. Each is recognised, so a root holding .. or leading through
+// a link reads the same whichever way its files were found.
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug)]
+struct Root {
+    /// As given, without . or .. between names.
+    given: PathBuf,
+    /// As given, from the working directory.
+    from_cwd: PathBuf,
+    /// With links followed, if it exists.
+    real: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Roots {
+    roots: Vec<Root>,
+    absolute: bool,
+}
+
+impl Roots {
+    pub fn new(paths: &[PathBuf], absolute: bool) -> io::Result<Self> {
+        let cwd = env::current_dir()?;
+        let roots = paths
+            .iter()
+            .map(|path| Root {
+                given: normalize(path),
+                from_cwd: normalize(&cwd.join(path)),
+                real: fs::canonicalize(path).ok(),
+            })
+            .collect();
+        Ok(Self { roots, absolute })
+    }
+
+    /// A result as it should be shown. A file given as a root itself is
+    /// shown as given, and a path under no root is left as it is.
+    pub fn show(&self, path: &Path) -> PathBuf {
+        let path = normalize(path);
+        for root in &self.roots {
+            let bases = [Some(&root.given), Some(&root.from_cwd), root.real.as_ref()];
+            let rest = match bases
+                .iter()
+                .flatten()
+                .find_map(|base| path.strip_prefix(base).ok())
+            {
+                Some(rest) => rest,
+                None => continue,
+            };
+            if self.absolute {
+                let base = root.real.as_ref().unwrap_or(&root.from_cwd);
+                return match rest.as_os_str().is_empty() {
+                    true => base.clone(),
+                    false => base.join(rest),
+                };
+            }
+            return match rest.as_os_str().is_empty() {
+                true => root.given.clone(),
+                false => rest.to_path_buf(),
+            };
+        }
+        path
+    }
+}
+
+/// A path without . and with each .. taking away the name before it, where
+/// there is one. The same place unless a name taken away was a link.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normal.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normal.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normal.push(".."),
+            },
+            _ => normal.push(component),
+        }
+    }
+    normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(PathBuf::from("a/c"), normalize(Path::new("./a/b/../c")));
+        assert_eq!(
+            PathBuf::from("../../a"),
+            normalize(Path::new("../x/../../a"))
+        );
+        assert_eq!(PathBuf::from("/a"), normalize(Path::new("/../a")));
+        assert_eq!(PathBuf::new(), normalize(Path::new(".")));
+    }
+    #[test]
+    fn test_show() {
+        let root = env::temp_dir().join("nametag-roots");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("tree/sub")).unwrap();
+        fs::write(root.join("tree/sub/a[x].txt"), "").unwrap();
+        let real = fs::canonicalize(root.join("tree")).unwrap();
+        let paths = [root.join("tree/sub/..")];
+        let given = &paths[0];
+        let relative = Roots::new(&paths, false).unwrap();
+        let absolute = Roots::new(&paths, true).unwrap();
+        for found in [given.join("sub/a[x].txt"), real.join("sub/a[x].txt")] {
+            assert_eq!(PathBuf::from("sub/a[x].txt"), relative.show(&found));
+            assert_eq!(real.join("sub/a[x].txt"), absolute.show(&found));
+        }
+        // Outside every root
+        assert_eq!(PathBuf::from("/b.txt"), relative.show(Path::new("/b.txt")));
+        let file = Roots::new(&[given.join("sub/a[x].txt")], false).unwrap();
+        assert_eq!(
+            normalize(&given.join("sub/a[x].txt")),
+            file.show(&real.join("sub/a[x].txt"))
+        );
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_show_through_link() {
+        let root = env::temp_dir().join("nametag-roots-link");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("real")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+        let real = fs::canonicalize(root.join("real")).unwrap();
+        let roots = Roots::new(&[root.join("link")], false).unwrap();
+        assert_eq!(PathBuf::from("a.txt"), roots.show(&root.join("link/a.txt")));
+        assert_eq!(PathBuf::from("a.txt"), roots.show(&real.join("a.txt")));
+        let roots = Roots::new(&[root.join("link")], true).unwrap();
+        assert_eq!(real.join("a.txt"), roots.show(&root.join("link/a.txt")));
+    }
+}
@@ -0,0 +1,94 @@
+// Files below a directory whose tags satisfy a query, given as they are
+// found rather than once the whole tree is read, so a program embedding
+// the library can show the first results straight away.
+// eg for found in Search::new(root, query, &options, format, extension) { ... }
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::format::TagFormat;
+use crate::query::Query;
+use crate::walk::{self, Files, WalkOptions};
+use crate::{Extension, NameTag};
+
+/// A file satisfying a query.
+#[derive(Debug, Clone)]
+pub struct Found {
+    pub path: PathBuf,
+    pub nametag: NameTag,
+}
+
+/// Files satisfying a query, one at a time in the order of a walk.
+pub struct Search {
+    files: Files,
+    query: Query,
+    format: TagFormat,
+    extension: Extension,
+}
+
+impl Search {
+    pub fn new(
+        root: &Path,
+        query: Query,
+        options: &WalkOptions,
+        format: TagFormat,
+        extension: Extension,
+    ) -> Self {
+        Self {
+            files: walk::files(root, options),
+            query,
+            format,
+            extension,
+        }
+    }
+}
+
+impl Iterator for Search {
+    type Item = io::Result<Found>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for path in &mut self.files {
+            let path = match path {
+                Ok(path) => path,
+                Err(err) => return Some(Err(err)),
+            };
+            let nametag =
+                NameTag::from_path_with_format(&path, self.extension, self.format.clone());
+            if self.query.matches_name(&nametag) {
+                return Some(Ok(Found { path, nametag }));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_search() {
+        let root = env::temp_dir().join("nametag-search");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for name in ["a[work].txt", "b[home].txt", "sub/c[work done].txt"] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let mut search = Search::new(
+            &root,
+            Query::tag("work"),
+            &WalkOptions::default(),
+            TagFormat::default(),
+            Extension::default(),
+        );
+        let first = search.next().unwrap().unwrap();
+        assert_eq!(root.join("a[work].txt"), first.path);
+        assert!(first.nametag.has_tag("work"));
+        assert_eq!(
+            root.join("sub/c[work done].txt"),
+            search.next().unwrap().unwrap().path
+        );
+        assert!(search.next().is_none());
+    }
+}
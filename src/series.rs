@@ -0,0 +1,182 @@
+// Structured numbering in the names of a series of files.
+// eg S{season=}E{ep=} matches "show S01E03" as season=1 and ep=3
+//
+// Each field matches a run of digits, so fields need literal text between
+// them to be told apart.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+pub enum SeriesError {
+    BadField(String),
+    AdjacentFields(String),
+    NoFields,
+}
+
+impl fmt::Display for SeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeriesError::BadField(field) => {
+                write!(f, "Invalid series field \"{}\". eg {{ep=}}", field)
+            }
+            SeriesError::AdjacentFields(key) => write!(
+                f,
+                "Series field \"{}\" needs text separating it from the field before",
+                key
+            ),
+            SeriesError::NoFields => write!(f, "Series pattern has no fields. eg E{{ep=}}"),
+        }
+    }
+}
+
+impl std::error::Error for SeriesError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    segments: Vec<Segment>,
+}
+
+/// Where a pattern was found in a name, with the values of its fields.
+#[derive(Debug, PartialEq)]
+pub struct Found {
+    pub start: usize,
+    pub end: usize,
+    pub values: BTreeMap<String, u64>,
+}
+
+impl FromStr for Series {
+    type Err = SeriesError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = pattern;
+        while !rest.is_empty() {
+            let (text, field) = match rest.find('{') {
+                Some(index) => (&rest[..index], Some(&rest[index..])),
+                None => (rest, None),
+            };
+            if !text.is_empty() {
+                segments.push(Segment::Text(text.to_owned()));
+            }
+            rest = "";
+            if let Some(field) = field {
+                let close = field
+                    .find('}')
+                    .ok_or_else(|| SeriesError::BadField(field.to_owned()))?;
+                let key = field[1..close]
+                    .strip_suffix('=')
+                    .filter(|key| !key.is_empty() && !key.contains(['{', '=']))
+                    .ok_or_else(|| SeriesError::BadField(field[..=close].to_owned()))?;
+                if let Some(Segment::Field(_)) = segments.last() {
+                    return Err(SeriesError::AdjacentFields(key.to_owned()));
+                }
+                segments.push(Segment::Field(key.to_owned()));
+                rest = &field[close + 1..];
+            }
+        }
+        if !segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field(_)))
+        {
+            return Err(SeriesError::NoFields);
+        }
+        Ok(Self { segments })
+    }
+}
+
+impl Series {
+    /// Keys of the fields, in pattern order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Field(key) => Some(key.as_str()),
+            Segment::Text(_) => None,
+        })
+    }
+
+    /// First place in a name the pattern matches.
+    pub fn find(&self, name: &str) -> Option<Found> {
+        name.char_indices()
+            .map(|(start, _)| start)
+            .find_map(|start| self.match_at(name, start))
+    }
+
+    fn match_at(&self, name: &str, start: usize) -> Option<Found> {
+        let mut end = start;
+        let mut values = BTreeMap::new();
+        for segment in &self.segments {
+            let rest = &name[end..];
+            match segment {
+                Segment::Text(text) => {
+                    if !rest.starts_with(text.as_str()) {
+                        return None;
+                    }
+                    end += text.len();
+                }
+                Segment::Field(key) => {
+                    let digits =
+                        rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                    let value = rest[..digits].parse().ok()?;
+                    values.insert(key.clone(), value);
+                    end += digits;
+                }
+            }
+        }
+        Some(Found { start, end, values })
+    }
+
+    /// Pattern filled in with values, zero padded to a width. Missing values render as 0.
+    pub fn render(&self, values: &BTreeMap<String, u64>, width: usize) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Field(key) => {
+                    format!("{:0width$}", values.get(key).unwrap_or(&0), width = width)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pattern() {
+        let series: Series = "S{season=}E{ep=}".parse().unwrap();
+        assert_eq!(vec!["season", "ep"], series.keys().collect::<Vec<_>>());
+        assert_eq!(
+            Err(SeriesError::AdjacentFields("ep".to_owned())),
+            "{season=}{ep=}".parse::<Series>()
+        );
+        assert_eq!(
+            Err(SeriesError::BadField("{ep}".to_owned())),
+            "E{ep}".parse::<Series>()
+        );
+        assert_eq!(Err(SeriesError::NoFields), "S01".parse::<Series>());
+    }
+    #[test]
+    fn test_find() {
+        let series: Series = "S{season=}E{ep=}".parse().unwrap();
+        let found = series.find("Show S01E003 Pilot").unwrap();
+        assert_eq!((5, 12), (found.start, found.end));
+        assert_eq!(Some(&1), found.values.get("season"));
+        assert_eq!(Some(&3), found.values.get("ep"));
+        assert_eq!(None, series.find("Show SE01"));
+    }
+    #[test]
+    fn test_render() {
+        let series: Series = "S{season=}E{ep=}".parse().unwrap();
+        let values = series.find("S1E3").unwrap().values;
+        assert_eq!("S01E03", series.render(&values, 2));
+        assert_eq!("S1E3", series.render(&values, 1));
+    }
+}
@@ -0,0 +1,121 @@
+// Rehearsal of commands on a copy of a tree's names, every file in it
+// empty, so a reorganization in several steps can be tried out before it
+// is made. eg nametag simulate photos -- "add -t trip a.jpg" "maintain"
+//
+// Only names are copied, so commands reading what files hold, such as
+// those taking tags from metadata, find nothing.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::walk::{walk, WalkOptions};
+
+/// Copy the names of the directories and files below one directory into
+/// another, giving the files copied.
+pub fn copy_names(from: &Path, to: &Path, options: &WalkOptions) -> io::Result<usize> {
+    let found = walk(from, options)?;
+    for dir in &found.dirs {
+        fs::create_dir_all(to.join(dir.strip_prefix(from).unwrap_or(dir)))?;
+    }
+    for file in &found.files {
+        fs::File::create(to.join(file.strip_prefix(from).unwrap_or(file)))?;
+    }
+    Ok(found.files.len())
+}
+
+/// The arguments of a command line, split at whitespace outside quotes as
+/// a shell would, without expanding wildcards. eg add -t 'two words' "a b.txt"
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Whether an argument has begun, as '' is one though empty.
+    let mut started = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("Unclosed quote in: {}", line)),
+                    }
+                }
+            }
+            '"' => {
+                started = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(format!("Unclosed quote in: {}", line)),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(format!("Unclosed quote in: {}", line)),
+                    }
+                }
+            }
+            '\\' => {
+                started = true;
+                if let Some(c) = chars.next() {
+                    arg.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if started {
+                    args.push(std::mem::take(&mut arg));
+                    started = false;
+                }
+            }
+            c => {
+                started = true;
+                arg.push(c);
+            }
+        }
+    }
+    if started {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            Ok(vec![
+                "add".to_owned(),
+                "-t".to_owned(),
+                "two words".to_owned(),
+                "a \"b\".txt".to_owned(),
+                "".to_owned(),
+                "c d".to_owned()
+            ]),
+            split_args("add  -t 'two words' \"a \\\"b\\\".txt\" '' c\\ d ")
+        );
+        assert!(split_args("add -t 'x").is_err());
+    }
+    #[test]
+    fn test_copy_names() {
+        let root = env::temp_dir().join("nametag-simulate");
+        let _ = fs::remove_dir_all(&root);
+        let from = root.join("from");
+        fs::create_dir_all(from.join("empty")).unwrap();
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("sub/a[x].txt"), "contents").unwrap();
+        let to = root.join("to");
+        assert_eq!(1, copy_names(&from, &to, &WalkOptions::default()).unwrap());
+        assert_eq!("", fs::read_to_string(to.join("sub/a[x].txt")).unwrap());
+        assert!(to.join("empty").is_dir());
+    }
+}
@@ -0,0 +1,173 @@
+// Turn tag text from messy sources, eg EXIF or front matter, into a form
+// that is safe both in file names and in the tag scheme.
+// eg "Café Münster / 2019" -> Cafe-Munster-2019
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SlugMode {
+    /// Replace accented letters with plain ones, and unsafe runs with a dash.
+    #[default]
+    Transliterate,
+    /// Drop anything unsafe, with whitespace becoming a dash.
+    Strip,
+    /// Keep everything, writing unsafe characters as %XX of their utf8 bytes.
+    PercentEncode,
+}
+
+// Characters with a meaning to file systems or to the tag scheme itself.
+fn is_reserved(c: char) -> bool {
+    c.is_control()
+        || c.is_whitespace()
+        || matches!(
+            c,
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '[' | ']' | ',' | '=' | '+'
+        )
+}
+
+/// A filename and scheme safe form of text.
+pub fn slug(text: &str, mode: SlugMode) -> String {
+    match mode {
+        SlugMode::Transliterate => dashed(text, transliterate),
+        SlugMode::Strip => dashed(text, |c| {
+            if c.is_ascii() {
+                Some(c.to_string())
+            } else {
+                Some(String::new())
+            }
+        }),
+        SlugMode::PercentEncode => {
+            let mut out = String::new();
+            for c in text.chars() {
+                if c == '%' || !c.is_ascii() || is_reserved(c) {
+                    let mut buffer = [0; 4];
+                    for byte in c.encode_utf8(&mut buffer).bytes() {
+                        out.push_str(&format!("%{:02X}", byte));
+                    }
+                } else {
+                    out.push(c);
+                }
+            }
+            out
+        }
+    }
+}
+
+/// As slug, for a whole tag, keeping the key and values of a value tag
+/// apart. eg "Year Taken=2019+2020" -> Year-Taken=2019+2020
+pub fn slug_tag(tag: &str, mode: SlugMode) -> String {
+    match tag.split_once('=') {
+        Some((key, values)) => format!(
+            "{}={}",
+            slug(key, mode),
+            values
+                .split('+')
+                .map(|value| slug(value, mode))
+                .collect::<Vec<_>>()
+                .join("+")
+        ),
+        None => slug(tag, mode),
+    }
+}
+
+// Map each character, with runs of reserved characters becoming one dash.
+fn dashed<F: Fn(char) -> Option<String>>(text: &str, map: F) -> String {
+    let mut out = String::new();
+    let mut dash = false;
+    for c in text.chars() {
+        let mapped = if is_reserved(c) { None } else { map(c) };
+        match mapped {
+            Some(mapped) if mapped.is_empty() => {}
+            Some(mapped) => {
+                if dash && !out.is_empty() {
+                    out.push('-');
+                }
+                dash = false;
+                out.push_str(&mapped);
+            }
+            None => dash = true,
+        }
+    }
+    out
+}
+
+// Plain letters for common accented Latin ones. Anything else outside ascii is dropped.
+fn transliterate(c: char) -> Option<String> {
+    if c.is_ascii() {
+        return Some(c.to_string());
+    }
+    let plain = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' => "A",
+        'ç' | 'ć' | 'č' => "c",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ę' => "E",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' => "I",
+        'ñ' | 'ń' => "n",
+        'Ñ' | 'Ń' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' => "O",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' => "Y",
+        'š' | 'ś' => "s",
+        'Š' | 'Ś' => "S",
+        'ž' | 'ź' | 'ż' => "z",
+        'Ž' | 'Ź' | 'Ż' => "Z",
+        'ł' => "l",
+        'Ł' => "L",
+        'ß' => "ss",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ð' => "d",
+        'Ð' => "D",
+        _ => "",
+    };
+    Some(plain.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transliterate() {
+        assert_eq!(
+            "Cafe-Munster-2019",
+            slug("Café Münster / 2019", SlugMode::Transliterate)
+        );
+        assert_eq!("Strasse", slug("Straße", SlugMode::Transliterate));
+        assert_eq!("a-b", slug("  [a, b]  ", SlugMode::Transliterate));
+    }
+    #[test]
+    fn test_slug_tag() {
+        assert_eq!(
+            "My-Project",
+            slug_tag("My Project", SlugMode::Transliterate)
+        );
+        assert_eq!(
+            "Year-Taken=2019+2020",
+            slug_tag("Year Taken=2019+2020", SlugMode::Transliterate)
+        );
+        assert_eq!("work", slug_tag("work", SlugMode::Strip));
+    }
+    #[test]
+    fn test_strip() {
+        assert_eq!("Caf-Mnster", slug("Café Münster", SlugMode::Strip));
+        assert_eq!("a-b", slug("a=b", SlugMode::Strip));
+    }
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(
+            "Caf%C3%A9%20%5B1%5D%25",
+            slug("Café [1]%", SlugMode::PercentEncode)
+        );
+    }
+}
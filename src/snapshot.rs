@@ -0,0 +1,254 @@
+// Record of the tags on every file under a directory, to compare over time.
+//
+// Files are keyed by their path relative to the root with tags removed,
+// so a file keeps its identity as tags are added and removed from its name.
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::state_dir;
+use crate::format::TagFormat;
+use crate::paths::PathMatching;
+use crate::walk::{walk, WalkOptions};
+use crate::NameTag;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Directory that was scanned.
+    pub root: PathBuf,
+    /// Untagged relative path to the tags on that file.
+    pub files: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Appeared(PathBuf, BTreeSet<String>),
+    Disappeared(PathBuf, BTreeSet<String>),
+    Retagged {
+        path: PathBuf,
+        before: BTreeSet<String>,
+        after: BTreeSet<String>,
+    },
+}
+
+impl Snapshot {
+    /// Walk a directory recording the tags of each file, as written in a format.
+    pub fn scan(root: &Path, format: &TagFormat, options: &WalkOptions) -> io::Result<Self> {
+        let mut snapshot = Self {
+            root: env::current_dir()?.join(root),
+            files: BTreeMap::new(),
+        };
+        for path in walk(root, options)?.files {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let mut nametag =
+                NameTag::with_format(relative.file_name().unwrap_or_default(), format.clone());
+            let tags = nametag
+                .get_tags()
+                .map(|tag| tag.to_string_lossy().into_owned())
+                .collect();
+            nametag.clear_tags();
+            snapshot
+                .files
+                .insert(relative.with_file_name(OsString::from(nametag)), tags);
+        }
+        Ok(snapshot)
+    }
+
+    /// Where named snapshots are stored. eg ~/.local/state/nametag/snapshots/<name>.json
+    pub fn named_path(name: &str) -> Option<PathBuf> {
+        Some(Self::dir()?.join(format!("{}.json", name)))
+    }
+
+    fn dir() -> Option<PathBuf> {
+        Some(state_dir()?.join("snapshots"))
+    }
+
+    /// The most recently saved named snapshot of a root directory, if any.
+    pub fn latest_for(root: &Path) -> io::Result<Option<Self>> {
+        let dir = match Self::dir() {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut latest = None;
+        for entry in entries {
+            let entry = entry?;
+            let modified = entry.metadata()?.modified()?;
+            let snapshot = match Self::load(&entry.path()) {
+                Ok(snapshot) if snapshot.root == root => snapshot,
+                _ => continue,
+            };
+            if latest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                latest = Some((modified, snapshot));
+            }
+        }
+        Ok(latest.map(|(_, snapshot)| snapshot))
+    }
+
+    /// Paths of the files in the snapshot, with their tags written in a format.
+    pub fn tagged_paths(&self, format: &TagFormat) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .map(|(path, tags)| {
+                let mut nametag =
+                    NameTag::with_format(path.file_name().unwrap_or_default(), format.clone());
+                for tag in tags {
+                    nametag.add_tag(tag);
+                }
+                self.root.join(path.with_file_name(OsString::from(nametag)))
+            })
+            .collect()
+    }
+
+    /// As tagged_paths, for only the files at or below a path. Paths are
+    /// given without any "." they were scanned with. eg /archive/./a.txt
+    pub fn tagged_paths_within(&self, path: &Path, format: &TagFormat) -> Vec<PathBuf> {
+        self.tagged_paths(format)
+            .into_iter()
+            .filter(|tagged| tagged.starts_with(path))
+            .map(|tagged| tagged.components().collect())
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Files whose tags differ between this snapshot and a later one.
+    pub fn diff(&self, later: &Snapshot) -> Vec<Change> {
+        self.diff_matching(later, PathMatching::Exact)
+    }
+
+    /// As diff, with paths matched between the snapshots as given, so
+    /// snapshots taken on different systems compare. Changes are given
+    /// under the paths of this snapshot.
+    pub fn diff_matching(&self, later: &Snapshot, matching: PathMatching) -> Vec<Change> {
+        let earlier = self
+            .files
+            .keys()
+            .map(|path| matching.key(path))
+            .collect::<BTreeSet<_>>();
+        let later_keyed = later
+            .files
+            .iter()
+            .map(|(path, tags)| (matching.key(path), tags))
+            .collect::<BTreeMap<_, _>>();
+        let mut changes = Vec::new();
+        for (path, before) in &self.files {
+            match later_keyed.get(&matching.key(path)).copied() {
+                None => changes.push(Change::Disappeared(path.clone(), before.clone())),
+                Some(after) if after != before => changes.push(Change::Retagged {
+                    path: path.clone(),
+                    before: before.clone(),
+                    after: after.clone(),
+                }),
+                _ => {}
+            }
+        }
+        for (path, after) in &later.files {
+            if !earlier.contains(&matching.key(path)) {
+                changes.push(Change::Appeared(path.clone(), after.clone()));
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(files: &[(&str, &[&str])]) -> Snapshot {
+        Snapshot {
+            root: PathBuf::from("/archive"),
+            files: files
+                .iter()
+                .map(|(path, tags)| {
+                    (
+                        PathBuf::from(path),
+                        tags.iter().map(|tag| tag.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = env::temp_dir().join("nametag-snapshot-scan");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::File::create(dir.join("a[tagA].txt")).unwrap();
+        fs::File::create(dir.join("sub").join("b.txt")).unwrap();
+        let scanned = Snapshot::scan(&dir, &TagFormat::default(), &WalkOptions::default()).unwrap();
+        assert_eq!(
+            snapshot(&[("a.txt", &["tagA"]), ("sub/b.txt", &[])]).files,
+            scanned.files
+        );
+    }
+    #[test]
+    fn test_diff() {
+        let before = snapshot(&[("a.txt", &["x"]), ("b.txt", &["y"]), ("c.txt", &[])]);
+        let after = snapshot(&[("a.txt", &["x", "z"]), ("c.txt", &[]), ("d.txt", &["w"])]);
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect();
+        assert_eq!(
+            vec![
+                Change::Retagged {
+                    path: PathBuf::from("a.txt"),
+                    before: tags(&["x"]),
+                    after: tags(&["x", "z"]),
+                },
+                Change::Disappeared(PathBuf::from("b.txt"), tags(&["y"])),
+                Change::Appeared(PathBuf::from("d.txt"), tags(&["w"])),
+            ],
+            before.diff(&after)
+        );
+    }
+    #[test]
+    fn test_diff_matching() {
+        let mac = snapshot(&[("Photos/Cafe\u{301}.jpg", &["x"])]);
+        let linux = snapshot(&[("photos/Café.jpg", &["x"])]);
+        assert_eq!(2, mac.diff(&linux).len());
+        assert!(mac.diff_matching(&linux, PathMatching::Portable).is_empty());
+    }
+    #[test]
+    fn test_tagged_paths() {
+        let snapshot = snapshot(&[("sub/a.txt", &["y", "x"])]);
+        assert_eq!(
+            vec![PathBuf::from("/archive/sub/a[x y].txt")],
+            snapshot.tagged_paths(&TagFormat::default())
+        );
+    }
+    #[test]
+    fn test_tagged_paths_within() {
+        let snapshot = snapshot(&[("sub/a.txt", &["x"]), ("b.txt", &["y"])]);
+        assert_eq!(
+            vec![PathBuf::from("/archive/sub/a[x].txt")],
+            snapshot.tagged_paths_within(Path::new("/archive/./sub"), &TagFormat::default())
+        );
+    }
+    #[test]
+    fn test_save_load() {
+        let path = env::temp_dir().join("nametag-snapshot-save.json");
+        let saved = snapshot(&[("a.txt", &["x"])]);
+        saved.save(&path).unwrap();
+        assert_eq!(saved, Snapshot::load(&path).unwrap());
+    }
+}
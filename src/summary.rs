@@ -0,0 +1,102 @@
+// Statistics of a run, reported when it finishes so scripted runs can log
+// what they did. eg 0.21s: 120 scanned, 3 changed, 1 skipped, 0 failed
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::batch::Batch;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for SummaryFormat {
+    type Err = &'static str;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(SummaryFormat::Text),
+            "json" => Ok(SummaryFormat::Json),
+            _ => Err("Expected one of text or json."),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    /// Files looked at while planning renames.
+    pub scanned: usize,
+    pub changed: usize,
+    /// Files left as they were, eg as their new name was taken.
+    pub skipped: usize,
+    /// Files whose renames did not complete.
+    pub failed: usize,
+}
+
+impl Summary {
+    /// Count a batch, either applied or failed as a whole.
+    pub fn record(&mut self, batch: &Batch, applied: bool) {
+        self.scanned += batch.scanned();
+        self.skipped += batch.skipped();
+        if applied {
+            self.changed += batch.len();
+        } else {
+            self.failed += batch.len();
+        }
+    }
+
+    /// The summary as a line of text or json.
+    pub fn render(&self, elapsed: Duration, format: SummaryFormat) -> String {
+        match format {
+            SummaryFormat::Text => format!(
+                "{:.2}s: {} scanned, {} changed, {} skipped, {} failed",
+                elapsed.as_secs_f64(),
+                self.scanned,
+                self.changed,
+                self.skipped,
+                self.failed
+            ),
+            SummaryFormat::Json => {
+                #[derive(Serialize)]
+                struct Report<'a> {
+                    elapsed: f64,
+                    #[serde(flatten)]
+                    summary: &'a Summary,
+                }
+                let report = Report {
+                    elapsed: elapsed.as_secs_f64(),
+                    summary: self,
+                };
+                serde_json::to_string(&report).expect("summary serializes")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut batch = Batch::new();
+        batch.push("a.txt", "a[x].txt");
+        batch.push("b[x].txt", "b[x].txt");
+        batch.skip();
+        let mut summary = Summary::default();
+        summary.record(&batch, true);
+        summary.record(&batch, false);
+        let elapsed = Duration::from_millis(1500);
+        assert_eq!(
+            "1.50s: 6 scanned, 1 changed, 2 skipped, 1 failed",
+            summary.render(elapsed, SummaryFormat::Text)
+        );
+        assert_eq!(
+            r#"{"elapsed":1.5,"scanned":6,"changed":1,"skipped":2,"failed":1}"#,
+            summary.render(elapsed, SummaryFormat::Json)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+// Helpers for testing tools built on nametag against its real handling of
+// names: trees of files in a temporary directory, checks of the tags on a
+// file, and batches of renames written out to compare with expected text.
+// eg let tree = Tree::with_files("plugin", &["a[x].txt"]);
+//    assert_tags(tree.path("a[x].txt"), &["x"]);
+//
+// Built with the testing feature.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::batch::Rename;
+use crate::walk::{walk, WalkOptions};
+use crate::{Extension, NameTag};
+
+// Trees made by this process, keeping their directories apart.
+static TREES: AtomicUsize = AtomicUsize::new(0);
+
+/// Files in a new temporary directory, removed when dropped.
+#[derive(Debug)]
+pub struct Tree {
+    root: PathBuf,
+}
+
+impl Tree {
+    /// An empty tree, in a directory named for the test and unique to it.
+    pub fn new(name: &str) -> Self {
+        let count = TREES.fetch_add(1, Ordering::SeqCst);
+        let root = env::temp_dir().join(format!("{}-{}-{}", name, process::id(), count));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).expect("temporary directory is writable");
+        Self { root }
+    }
+
+    /// A tree holding empty files at paths relative to its root.
+    pub fn with_files(name: &str, paths: &[&str]) -> Self {
+        let tree = Self::new(name);
+        for path in paths {
+            tree.file(path);
+        }
+        tree
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// A path relative to the root.
+    pub fn path<P: AsRef<Path>>(&self, relative: P) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    /// Add an empty file, and any directories above it.
+    pub fn file<P: AsRef<Path>>(&self, relative: P) -> PathBuf {
+        let path = self.path(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("temporary directory is writable");
+        }
+        fs::write(&path, "").expect("temporary directory is writable");
+        path
+    }
+
+    /// Paths of the files in the tree relative to its root, in order and
+    /// separated by /, to compare with expected names.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = walk(&self.root, &WalkOptions::default())
+            .expect("tree is readable")
+            .files
+            .iter()
+            .map(|path| relative(path, &self.root))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+impl Drop for Tree {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+// A path relative to root, separated by /.
+fn relative(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Tags on the name of a file, in the default format, in order.
+pub fn tags_of<P: AsRef<Path>>(path: P) -> Vec<String> {
+    NameTag::from_path(path, Extension::default())
+        .get_tags()
+        .map(|tag| tag.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Panic unless the name of a file carries exactly the tags given.
+pub fn assert_tags<P: AsRef<Path>>(path: P, expected: &[&str]) {
+    let path = path.as_ref();
+    let mut expected = expected.to_vec();
+    expected.sort_unstable();
+    let found = tags_of(path);
+    assert!(
+        found == expected,
+        "{}: expected tags {:?}, found {:?}",
+        path.display(),
+        expected,
+        found
+    );
+}
+
+/// A batch of renames as lines of "from -> to", relative to root, to
+/// compare a plan with expected text.
+pub fn plan(renames: &[Rename], root: &Path) -> String {
+    renames
+        .iter()
+        .map(|rename| {
+            format!(
+                "{} -> {}\n",
+                relative(&rename.from, root),
+                relative(&rename.to, root)
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::Batch;
+
+    #[test]
+    fn test_tree() {
+        let tree = Tree::with_files("nametag-testing", &["b.txt", "sub/a[x y].txt"]);
+        assert_eq!(vec!["b.txt", "sub/a[x y].txt"], tree.names());
+        assert_tags(tree.path("sub/a[x y].txt"), &["y", "x"]);
+        assert_tags(tree.path("b.txt"), &[]);
+        let root = tree.root().to_path_buf();
+        drop(tree);
+        assert!(!root.exists());
+    }
+    #[test]
+    #[should_panic(expected = "expected tags [\"x\"], found []")]
+    fn test_assert_tags() {
+        assert_tags("a.txt", &["x"]);
+    }
+    #[test]
+    fn test_plan() {
+        let tree = Tree::new("nametag-testing-plan");
+        let mut batch = Batch::new();
+        batch.push(tree.path("a.txt"), tree.path("a[x].txt"));
+        assert_eq!("a.txt -> a[x].txt\n", plan(batch.renames(), tree.root()));
+    }
+}
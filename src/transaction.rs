@@ -0,0 +1,155 @@
+// Renames gathered from several commands, to review and apply as one batch.
+// Stored in the state directory. eg ~/.local/state/nametag/transaction.json
+//
+// Files keep their names until the transaction is committed, so later
+// commands see each file under the name it is planned to take.
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::batch::{Batch, Rename};
+use crate::config::state_dir;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    renames: Vec<Rename>,
+}
+
+impl Transaction {
+    /// Where the open transaction lives. eg ~/.local/state/nametag/transaction.json
+    pub fn default_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("transaction.json"))
+    }
+
+    /// Open an empty transaction. Fails if one is already open.
+    pub fn begin(path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    err.kind(),
+                    "A transaction is already open. Commit or abort it first.",
+                ),
+                _ => err,
+            })?;
+        file.write_all(&serde_json::to_vec_pretty(&Self::default())?)
+    }
+
+    /// Where changes staged for a commit live. eg ~/.local/state/nametag/stage.json
+    pub fn stage_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("stage.json"))
+    }
+
+    /// The open transaction, if any.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+
+    pub fn renames(&self) -> &[Rename] {
+        &self.renames
+    }
+
+    /// Name a file will take once committed. Files without a planned rename keep their path.
+    pub fn planned(&self, path: &Path) -> io::Result<PathBuf> {
+        let absolute = env::current_dir()?.join(path);
+        Ok(self
+            .renames
+            .iter()
+            .find(|rename| rename.from == absolute)
+            .map_or_else(|| path.to_path_buf(), |rename| rename.to.clone()))
+    }
+
+    /// Plan a rename of a file, given by its current or its planned name.
+    /// Renames back to the current name are dropped.
+    pub fn record(&mut self, path: &Path, to: &Path) -> io::Result<()> {
+        let cwd = env::current_dir()?;
+        let (path, to) = (cwd.join(path), cwd.join(to));
+        match self
+            .renames
+            .iter()
+            .position(|rename| rename.from == path || rename.to == path)
+        {
+            Some(index) if self.renames[index].from == to => {
+                self.renames.remove(index);
+            }
+            Some(index) => self.renames[index].to = to,
+            None if path != to => self.renames.push(Rename { from: path, to }),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// The planned renames as a batch to apply.
+    pub fn batch(&self) -> Batch {
+        let mut batch = Batch::new();
+        for rename in &self.renames {
+            batch.push(&rename.from, &rename.to);
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut transaction = Transaction::default();
+        transaction
+            .record(Path::new("/a/b.txt"), Path::new("/a/b[x].txt"))
+            .unwrap();
+        assert_eq!(
+            PathBuf::from("/a/b[x].txt"),
+            transaction.planned(Path::new("/a/b.txt")).unwrap()
+        );
+        // Edits stack, whether the file is named by its current or planned name.
+        transaction
+            .record(Path::new("/a/b[x].txt"), Path::new("/a/b[x y].txt"))
+            .unwrap();
+        transaction
+            .record(Path::new("/a/b.txt"), Path::new("/a/b[x y z].txt"))
+            .unwrap();
+        assert_eq!(1, transaction.renames().len());
+        assert_eq!(
+            PathBuf::from("/a/b[x y z].txt"),
+            transaction.renames()[0].to
+        );
+        // Undoing every edit drops the rename.
+        transaction
+            .record(Path::new("/a/b.txt"), Path::new("/a/b.txt"))
+            .unwrap();
+        assert!(transaction.renames().is_empty());
+    }
+    #[test]
+    fn test_begin_load() {
+        let path = env::temp_dir().join("nametag-transaction.json");
+        let _ = fs::remove_file(&path);
+        assert_eq!(None, Transaction::load(&path).unwrap());
+        Transaction::begin(&path).unwrap();
+        assert!(Transaction::begin(&path).is_err());
+        assert_eq!(
+            Some(Transaction::default()),
+            Transaction::load(&path).unwrap()
+        );
+    }
+}
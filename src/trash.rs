@@ -0,0 +1,106 @@
+// Soft deletion, for shared archives where a file removed by mistake is
+// hard to get back. Trashed files are tagged with the date instead, left
+// out of queries, and removed or archived for good once old enough.
+// eg report[trashed trashed-on=2026-10-16].pdf
+use crate::config::{days_from_civil, days_today, today};
+use crate::query::Query;
+
+/// Tag marking a file trashed.
+pub const TRASHED: &str = "trashed";
+
+/// Key of the value recording when a file was trashed.
+pub const TRASHED_ON: &str = "trashed-on";
+
+/// Tags to trash a file today.
+pub fn tags() -> Vec<String> {
+    let (year, month, day) = today();
+    vec![
+        TRASHED.to_owned(),
+        format!("{}={:04}-{:02}-{:02}", TRASHED_ON, year, month, day),
+    ]
+}
+
+/// A query leaving out trashed files, unless it asks after them itself.
+pub fn hide(query: Query) -> Query {
+    match mentions(&query) {
+        true => query,
+        false => query.and(!Query::tag(TRASHED)),
+    }
+}
+
+// Whether a query names the tags of trashed files, rather than matching
+// them by a wildcard.
+fn mentions(query: &Query) -> bool {
+    match query {
+        Query::Tag(tag) | Query::Exact(tag) => tag == TRASHED || tag.starts_with(TRASHED_ON),
+        Query::Value(key, _) | Query::Compare(key, ..) | Query::Range(key, ..) => key == TRASHED_ON,
+        Query::Name(_) => false,
+        Query::Not(query) => mentions(query),
+        Query::And(left, right) | Query::Or(left, right) => mentions(left) || mentions(right),
+    }
+}
+
+/// Whether tags mark a file trashed at least a number of days ago. Files
+/// trashed without a date are never old enough.
+pub fn expired<S: AsRef<str>>(tags: &[S], days: i64) -> bool {
+    age(tags, days_today()).is_some_and(|age| age >= days)
+}
+
+// Days between a file being trashed and today, if it was.
+fn age<S: AsRef<str>>(tags: &[S], today: i64) -> Option<i64> {
+    let tags = tags.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    if !tags.contains(&TRASHED) {
+        return None;
+    }
+    let date = tags
+        .iter()
+        .find_map(|tag| tag.strip_prefix(TRASHED_ON)?.strip_prefix('='))?;
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Some(today - days_from_civil(year, month, day))
+}
+
+/// Days in an age such as 30d or 2w.
+pub fn parse_days(text: &str) -> Result<i64, String> {
+    let (number, unit) = text.split_at(text.len() - text.ends_with(['d', 'w']) as usize);
+    let number = number
+        .parse::<i64>()
+        .map_err(|_| format!("Expected an age such as 30d or 2w, not \"{}\".", text))?;
+    Ok(match unit {
+        "w" => number * 7,
+        _ => number,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hide() {
+        let hidden = hide("work".parse().unwrap());
+        assert!(hidden.matches(&["work"]));
+        assert!(!hidden.matches(&["work", "trashed"]));
+        let asked = hide("work AND trashed".parse().unwrap());
+        assert!(asked.matches(&["work", "trashed"]));
+    }
+    #[test]
+    fn test_age() {
+        let today = days_from_civil(2026, 10, 16);
+        assert_eq!(Some(30), age(&["trashed", "trashed-on=2026-09-16"], today));
+        assert_eq!(None, age(&["trashed"], today));
+        assert_eq!(None, age(&["trashed-on=2026-09-16"], today));
+        assert!(expired(&tags(), 0));
+        assert!(!expired(&tags(), 1));
+    }
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(Ok(30), parse_days("30d"));
+        assert_eq!(Ok(14), parse_days("2w"));
+        assert_eq!(Ok(5), parse_days("5"));
+        assert!(parse_days("d").is_err());
+        assert!(parse_days("").is_err());
+    }
+}
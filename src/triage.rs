@@ -0,0 +1,165 @@
+// Order for files waiting to be tagged, most informative first, so a
+// session tagging by hand covers the most valuable files early.
+//
+// Files score for being large, being recent, being named in the text of
+// other files and having an extension few others share. Each is ranked
+// against the rest, so no single measure swamps the others.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// Files larger than this are not read for names of others
+const MAX_TEXT_BYTES: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Seconds since the epoch.
+    pub modified: u64,
+    /// Other files whose text names this one.
+    pub references: usize,
+}
+
+impl Candidate {
+    /// A file as found on disk, named nowhere yet.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: metadata.len(),
+            modified,
+            references: 0,
+        })
+    }
+
+    fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+/// Count, for each candidate, the other files whose text holds its name.
+/// Files that are large or not text are not read.
+pub fn count_references(candidates: &mut [Candidate], files: &[PathBuf]) -> io::Result<()> {
+    for file in files {
+        if fs::metadata(file)?.len() > MAX_TEXT_BYTES {
+            continue;
+        }
+        let text = match String::from_utf8(fs::read(file)?) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        for candidate in candidates.iter_mut() {
+            let named = candidate
+                .path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| text.contains(name));
+            if named && candidate.path != *file {
+                candidate.references += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Candidates with the most informative first.
+pub fn rank(mut candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let count = candidates.len();
+    if count < 2 {
+        return candidates;
+    }
+    let mut extensions = BTreeMap::new();
+    for candidate in &candidates {
+        *extensions.entry(candidate.extension()).or_insert(0) += 1;
+    }
+    let sizes = percentiles(&candidates, |candidate| candidate.size);
+    let ages = percentiles(&candidates, |candidate| candidate.modified);
+    let references = percentiles(&candidates, |candidate| candidate.references as u64);
+    let mut scored = candidates
+        .drain(..)
+        .enumerate()
+        .map(|(index, candidate)| {
+            let rarity = 1.0 - extensions[&candidate.extension()] as f64 / count as f64;
+            let score = sizes[index] + ages[index] + references[index] + rarity;
+            (score, candidate)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by(|(a, first), (b, second)| {
+        b.total_cmp(a).then_with(|| first.path.cmp(&second.path))
+    });
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+// Where each candidate's measure falls among all, from 0 for the lowest to
+// 1 for the highest, equal measures sharing a place.
+fn percentiles<F: Fn(&Candidate) -> u64>(candidates: &[Candidate], measure: F) -> Vec<f64> {
+    let mut sorted = candidates.iter().map(&measure).collect::<Vec<_>>();
+    sorted.sort_unstable();
+    let last = (sorted.len() - 1) as f64;
+    candidates
+        .iter()
+        .map(|candidate| sorted.partition_point(|value| *value < measure(candidate)) as f64 / last)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn candidate(path: &str, size: u64, modified: u64) -> Candidate {
+        Candidate {
+            path: PathBuf::from(path),
+            size,
+            modified,
+            references: 0,
+        }
+    }
+
+    fn paths(candidates: &[Candidate]) -> Vec<&str> {
+        candidates
+            .iter()
+            .map(|candidate| candidate.path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rank() {
+        let candidates = vec![
+            candidate("a.txt", 10, 100),
+            candidate("b.txt", 10, 100),
+            candidate("c.txt", 5000, 900),
+            candidate("d.blend", 10, 100),
+        ];
+        assert_eq!(
+            vec!["c.txt", "d.blend", "a.txt", "b.txt"],
+            paths(&rank(candidates))
+        );
+    }
+    #[test]
+    fn test_count_references() {
+        let dir = env::temp_dir().join("nametag-triage");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("notes.md"), "See plan.pdf and plan.pdf again").unwrap();
+        fs::write(dir.join("plan.pdf"), [0xff, 0xfe]).unwrap();
+        let files = vec![dir.join("notes.md"), dir.join("plan.pdf")];
+        let mut candidates = files
+            .iter()
+            .map(|file| Candidate::read(file).unwrap())
+            .collect::<Vec<_>>();
+        count_references(&mut candidates, &files).unwrap();
+        assert_eq!(0, candidates[0].references);
+        assert_eq!(1, candidates[1].references);
+    }
+}
@@ -0,0 +1,435 @@
+// Traversal of the files below a directory, shared by every command that
+// searches directories so they all agree on what is found.
+//
+// Names listed in an ignore file are left out of the directory holding it
+// and those below. eg .nametagignore
+// # Patterns of names, with * and ? wildcards
+// *.tmp
+// node_modules
+//
+// Links are skipped unless followed, in which case a link back to a
+// directory above it is not, so loops end. Results are sorted, so they are
+// the same whether or not directories are read in parallel.
+//
+// Files may also be taken one at a time as they are found, in the same
+// order, for callers showing results while a large tree is still read.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use serde::Deserialize;
+
+use crate::cancel::Cancel;
+use crate::pattern;
+use crate::workspace;
+
+/// File listing names to leave out of a walk.
+pub const IGNORE_FILE: &str = ".nametagignore";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Symlinks {
+    /// Leave out links, to files and to directories.
+    #[default]
+    Skip,
+    /// Treat links as what they point to.
+    Follow,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WalkOptions {
+    /// Levels of directories below the root to enter, or every level if
+    /// unset. eg 0 for the files of the root alone
+    pub max_depth: Option<usize>,
+    pub symlinks: Symlinks,
+    /// Include names starting with a period.
+    pub hidden: bool,
+    /// Leave out names listed in ignore files.
+    pub ignore: bool,
+    /// Patterns file names must match one of, for the file to be found. eg ["*.jpg"]
+    pub names: Vec<String>,
+    /// Directories read at once. Network shares may benefit from several.
+    pub threads: usize,
+    /// Stops the walk before the next directory is read.
+    #[serde(skip)]
+    pub cancel: Cancel,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            symlinks: Symlinks::Skip,
+            hidden: true,
+            ignore: true,
+            names: Vec::new(),
+            threads: 1,
+            cancel: Cancel::default(),
+        }
+    }
+}
+
+/// What a walk found.
+#[derive(Debug, Default, PartialEq)]
+pub struct Walk {
+    /// Files, in sorted order.
+    pub files: Vec<PathBuf>,
+    /// Directories entered, the root among them, in sorted order.
+    pub dirs: Vec<PathBuf>,
+}
+
+// A directory to read, with the ignore patterns of those above it
+struct Pending {
+    dir: PathBuf,
+    depth: usize,
+    ignored: Vec<String>,
+    // Real paths of the directory and those above it, when following links
+    ancestors: Vec<PathBuf>,
+}
+
+// What reading one directory found
+#[derive(Default)]
+struct Listing {
+    files: Vec<PathBuf>,
+    dirs: Vec<Pending>,
+}
+
+/// Walk the tree below a directory.
+pub fn walk(root: &Path, options: &WalkOptions) -> io::Result<Walk> {
+    let mut found = Walk::default();
+    let ancestors = match options.symlinks {
+        Symlinks::Follow => vec![fs::canonicalize(root)?],
+        Symlinks::Skip => Vec::new(),
+    };
+    let mut level = vec![Pending {
+        dir: root.to_path_buf(),
+        depth: 0,
+        ignored: Vec::new(),
+        ancestors,
+    }];
+    // Directories are read a level at a time, split between the threads.
+    while !level.is_empty() {
+        let listings = read_level(&level, options)?;
+        found
+            .dirs
+            .extend(level.into_iter().map(|pending| pending.dir));
+        level = Vec::new();
+        for listing in listings {
+            found.files.extend(listing.files);
+            level.extend(listing.dirs);
+        }
+    }
+    found.files.sort();
+    found.dirs.sort();
+    Ok(found)
+}
+
+/// Files below a directory, found as they are asked for, in the order of
+/// walk. Directories are read one at a time, whatever the threads given.
+pub fn files(root: &Path, options: &WalkOptions) -> Files {
+    let ancestors = match options.symlinks {
+        Symlinks::Follow => match fs::canonicalize(root) {
+            Ok(real) => vec![real],
+            Err(err) => {
+                return Files {
+                    options: options.clone(),
+                    stack: vec![vec![Entry::Failed(err)]],
+                }
+            }
+        },
+        Symlinks::Skip => Vec::new(),
+    };
+    let root = Pending {
+        dir: root.to_path_buf(),
+        depth: 0,
+        ignored: Vec::new(),
+        ancestors,
+    };
+    Files {
+        options: options.clone(),
+        stack: vec![vec![Entry::Dir(root)]],
+    }
+}
+
+/// Files of a walk, one at a time. A directory that cannot be read gives an
+/// error in place of its files, and the walk carries on, unless cancelled.
+pub struct Files {
+    options: WalkOptions,
+    // Entries of each directory entered yet to be visited, the next last.
+    stack: Vec<Vec<Entry>>,
+}
+
+enum Entry {
+    File(PathBuf),
+    Dir(Pending),
+    Failed(io::Error),
+}
+
+impl Iterator for Files {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.stack.is_empty() {
+            if let Err(err) = self.options.cancel.check() {
+                self.stack.clear();
+                return Some(Err(err));
+            }
+        }
+        loop {
+            let entry = match self.stack.last_mut()?.pop() {
+                Some(entry) => entry,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            match entry {
+                Entry::File(path) => return Some(Ok(path)),
+                Entry::Failed(err) => return Some(Err(err)),
+                Entry::Dir(pending) => match read(&pending, &self.options) {
+                    Ok(listing) => {
+                        let mut entries = listing
+                            .files
+                            .into_iter()
+                            .map(Entry::File)
+                            .chain(listing.dirs.into_iter().map(Entry::Dir))
+                            .collect::<Vec<_>>();
+                        // Descending, so the least is taken first.
+                        entries.sort_by(|a, b| b.path().cmp(a.path()));
+                        self.stack.push(entries);
+                    }
+                    Err(err) => return Some(Err(err)),
+                },
+            }
+        }
+    }
+}
+
+impl Entry {
+    fn path(&self) -> &Path {
+        match self {
+            Entry::File(path) => path,
+            Entry::Dir(pending) => &pending.dir,
+            Entry::Failed(_) => Path::new(""),
+        }
+    }
+}
+
+fn read_level(level: &[Pending], options: &WalkOptions) -> io::Result<Vec<Listing>> {
+    let threads = options.threads.clamp(1, level.len().max(1));
+    if threads == 1 {
+        return level.iter().map(|pending| read(pending, options)).collect();
+    }
+    let chunk = level.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers = level
+            .chunks(chunk)
+            .map(|pendings| {
+                scope.spawn(move || {
+                    pendings
+                        .iter()
+                        .map(|pending| read(pending, options))
+                        .collect::<io::Result<Vec<_>>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut listings = Vec::new();
+        for worker in workers {
+            listings.extend(worker.join().expect("walk thread panicked")?);
+        }
+        Ok(listings)
+    })
+}
+
+// Read one directory.
+fn read(pending: &Pending, options: &WalkOptions) -> io::Result<Listing> {
+    options.cancel.check()?;
+    let mut ignored = pending.ignored.clone();
+    if options.ignore {
+        match fs::read_to_string(pending.dir.join(IGNORE_FILE)) {
+            Ok(data) => ignored.extend(parse_ignore(&data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+    let mut listing = Listing::default();
+    for entry in fs::read_dir(&pending.dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if (!options.hidden && name.starts_with('.'))
+            || name == workspace::MARKER
+            || ignored
+                .iter()
+                .any(|pattern| pattern::matches(pattern, &name))
+        {
+            continue;
+        }
+        let mut file_type = entry.file_type()?;
+        if file_type.is_symlink() && options.symlinks == Symlinks::Follow {
+            file_type = match fs::metadata(entry.path()) {
+                Ok(metadata) => metadata.file_type(),
+                // A broken link leads nowhere.
+                Err(_) => continue,
+            };
+        }
+        if file_type.is_dir() {
+            if options
+                .max_depth
+                .is_some_and(|max_depth| pending.depth >= max_depth)
+            {
+                continue;
+            }
+            let mut ancestors = Vec::new();
+            if options.symlinks == Symlinks::Follow {
+                let real = fs::canonicalize(entry.path())?;
+                if pending.ancestors.contains(&real) {
+                    continue;
+                }
+                ancestors = pending.ancestors.clone();
+                ancestors.push(real);
+            }
+            listing.dirs.push(Pending {
+                dir: entry.path(),
+                depth: pending.depth + 1,
+                ignored: ignored.clone(),
+                ancestors,
+            });
+        } else if file_type.is_file()
+            && (options.names.is_empty()
+                || options
+                    .names
+                    .iter()
+                    .any(|pattern| pattern::matches(pattern, &name)))
+        {
+            listing.files.push(entry.path());
+        }
+    }
+    Ok(listing)
+}
+
+// Patterns of an ignore file, skipping blank lines and comments.
+fn parse_ignore(data: &str) -> impl Iterator<Item = String> + '_ {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // A scratch tree, emptied for each test
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let root = env::temp_dir().join(format!("nametag-walk-{}", name));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        root
+    }
+
+    fn names(root: &Path, paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_walk() {
+        let root = tree("plain", &["b.txt", "a/c.txt", "a/d/e.txt", ".hidden"]);
+        let found = walk(&root, &WalkOptions::default()).unwrap();
+        assert_eq!(
+            vec![".hidden", "a/c.txt", "a/d/e.txt", "b.txt"],
+            names(&root, &found.files)
+        );
+        assert_eq!(vec!["", "a", "a/d"], names(&root, &found.dirs));
+        let options = WalkOptions {
+            max_depth: Some(1),
+            hidden: false,
+            names: vec!["*.txt".to_owned()],
+            ..WalkOptions::default()
+        };
+        let found = walk(&root, &options).unwrap();
+        assert_eq!(vec!["a/c.txt", "b.txt"], names(&root, &found.files));
+    }
+    #[test]
+    fn test_ignore_file() {
+        let root = tree(
+            "ignore",
+            &["a.tmp", "keep/b.tmp", "keep/c.txt", "skip/d.txt"],
+        );
+        fs::write(root.join(IGNORE_FILE), "# scratch\n*.tmp\nskip\n").unwrap();
+        let found = walk(&root, &WalkOptions::default()).unwrap();
+        assert_eq!(vec![IGNORE_FILE, "keep/c.txt"], names(&root, &found.files));
+        let options = WalkOptions {
+            ignore: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(5, walk(&root, &options).unwrap().files.len());
+    }
+    #[test]
+    fn test_parallel_matches_serial() {
+        let files = (0..40)
+            .map(|index| format!("d{}/e{}/f.txt", index % 7, index))
+            .collect::<Vec<_>>();
+        let root = tree(
+            "parallel",
+            &files.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+        let serial = walk(&root, &WalkOptions::default()).unwrap();
+        let options = WalkOptions {
+            threads: 4,
+            ..WalkOptions::default()
+        };
+        assert_eq!(serial, walk(&root, &options).unwrap());
+        assert_eq!(40, serial.files.len());
+    }
+    #[test]
+    fn test_files() {
+        let root = tree(
+            "lazy",
+            &["b.txt", "a/c.txt", "a/d/e.txt", "a.txt", "a/d.txt"],
+        );
+        let options = WalkOptions::default();
+        let found = files(&root, &options).collect::<io::Result<Vec<_>>>();
+        assert_eq!(walk(&root, &options).unwrap().files, found.unwrap());
+        assert!(files(&root.join("missing"), &options)
+            .next()
+            .unwrap()
+            .is_err());
+        options.cancel.cancel();
+        let mut cancelled = files(&root, &options);
+        assert!(cancelled.next().unwrap().is_err());
+        assert!(cancelled.next().is_none());
+        assert!(walk(&root, &options).is_err());
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks() {
+        let root = tree("links", &["real/a.txt"]);
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+        // A loop back to the root
+        std::os::unix::fs::symlink(&root, root.join("real/up")).unwrap();
+        let found = walk(&root, &WalkOptions::default()).unwrap();
+        assert_eq!(vec!["real/a.txt"], names(&root, &found.files));
+        let options = WalkOptions {
+            symlinks: Symlinks::Follow,
+            ..WalkOptions::default()
+        };
+        let found = walk(&root, &options).unwrap();
+        assert_eq!(vec!["link/a.txt", "real/a.txt"], names(&root, &found.files));
+    }
+}
@@ -0,0 +1,56 @@
+// Workspaces, each the tree below a directory holding a marker, so commands
+// run anywhere inside an archive behave as if run from its root.
+// eg archive/.nametag-root
+//
+// A marker that is a directory also holds the workspace's own config,
+// ontology and bundles, used in place of those of the user. An empty file
+// marks the root alone.
+// eg archive/.nametag-root/ontology.toml
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Name marking the root of a workspace. Never found by walks.
+pub const MARKER: &str = ".nametag-root";
+
+/// The directory holding the nearest marker at or above a directory.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(MARKER).exists())
+        .map(Path::to_path_buf)
+}
+
+/// The root of the workspace holding the current directory, if any.
+pub fn current() -> Option<PathBuf> {
+    find(&env::current_dir().ok()?)
+}
+
+/// The config of the workspace holding the current directory, if its
+/// marker is a directory. Files beside it complete the vocabulary.
+pub fn config_path() -> Option<PathBuf> {
+    let marker = current()?.join(MARKER);
+    match marker.is_dir() {
+        true => Some(marker.join("config.toml")),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find() {
+        let root = env::temp_dir().join("nametag-workspace");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("archive/a/b/c")).unwrap();
+        fs::write(root.join("archive").join(MARKER), "").unwrap();
+        assert_eq!(
+            Some(root.join("archive")),
+            find(&root.join("archive/a/b/c"))
+        );
+        assert_eq!(Some(root.join("archive")), find(&root.join("archive")));
+        assert_eq!(None, find(&root));
+    }
+}